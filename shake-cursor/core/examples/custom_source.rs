@@ -0,0 +1,136 @@
+//! Embedding shake detection with a custom source of pointer motion.
+//!
+//! Reads pointer positions as `X Y TIMESTAMP_MS` lines from stdin (say,
+//! from a touch panel driver or a remote desktop protocol) and runs them
+//! through the cursor state machine. The "display" only prints the cursor
+//! size it is asked for. A shake followed by a slow drift:
+//!
+//! ```sh
+//! awk 'BEGIN {
+//!     for (i = 0; i < 40; i++) print (i % 2 ? 400 : 500), 300, i * 16
+//!     for (i = 40; i < 240; i++) print 500 + i, 300, i * 16
+//! }' | cargo run -p shake-cursor-core --example custom_source
+//! ```
+
+use std::io;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+
+use shake_cursor_core::effect::{EffectChain, ResizeEffect};
+use shake_cursor_core::{Config, Controller, DisplayBackend, InputEvent, MotionEvent, SystemClock, Waker};
+
+/// What next_event() waits for: a line from stdin, its end, or a wakeup.
+enum Input {
+    Line(String),
+    End,
+    Wake,
+}
+
+/// Motion read from stdin. Implementing DisplayBackend is all it takes to
+/// plug a new source of events into the Controller.
+///
+/// next_event() has to return when woken, as well as on input:
+/// SystemClock calls the waker when a cooldown runs out, so the cursor is
+/// restored while no input arrives. So stdin is read on a thread, which
+/// sends lines down the same channel as the waker.
+struct StdinSource {
+    sender: Sender<Input>,
+    receiver: Receiver<Input>,
+    size: u32,
+}
+
+impl StdinSource {
+    fn new() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self { sender, receiver, size: 24 }
+    }
+}
+
+impl DisplayBackend for StdinSource {
+    fn connect(&mut self) -> Result<(), String> {
+        let sender = self.sender.clone();
+        thread::spawn(move || {
+            for line in io::stdin().lines() {
+                let Ok(line) = line else { break };
+                if sender.send(Input::Line(line)).is_err() {
+                    return;
+                }
+            }
+            let _ = sender.send(Input::End);
+        });
+        Ok(())
+    }
+
+    fn next_event(&mut self) -> Option<InputEvent> {
+        loop {
+            match self.receiver.recv().ok()? {
+                Input::Line(line) => match parse(&line) {
+                    Some(motion) => return Some(InputEvent::Motion(motion)),
+                    None => eprintln!("Skipping '{}' (expected X Y TIMESTAMP_MS)", line),
+                },
+                // End of input ends the event stream, like a lost connection
+                Input::End => return None,
+                Input::Wake => return Some(InputEvent::Interrupted),
+            }
+        }
+    }
+
+    fn waker(&self) -> Waker {
+        let sender = self.sender.clone();
+        Arc::new(move || {
+            let _ = sender.send(Input::Wake);
+        })
+    }
+
+    fn set_cursor_size(&mut self, size: u32) -> Result<(), String> {
+        println!("cursor size {} -> {}", self.size, size);
+        self.size = size;
+        Ok(())
+    }
+
+    fn original_cursor_size(&self) -> u32 {
+        24
+    }
+
+    fn disconnect(&mut self) {}
+}
+
+fn parse(line: &str) -> Option<MotionEvent> {
+    let mut fields = line.split_whitespace();
+    let x = fields.next()?.parse().ok()?;
+    let y = fields.next()?.parse().ok()?;
+    let timestamp = fields.next()?.parse().ok()?;
+    fields.next().is_none().then_some(MotionEvent { x, y, timestamp, device: 0 })
+}
+
+/// The effects to start on a shake: only resizing, which goes through
+/// the backend. Called again whenever the config changes.
+fn effects(config: &Config, _: &dyn DisplayBackend) -> EffectChain {
+    EffectChain::new(vec![Box::new(ResizeEffect::new(config.enlarged_size))])
+}
+
+fn main() {
+    let mut source = StdinSource::new();
+    if let Err(err) = source.connect() {
+        eprintln!("{}", err);
+        return;
+    }
+
+    let config = Config { cooldown_ms: 1000, ..Config::default() };
+    let mut controller = Controller::new(Box::new(source), Box::new(SystemClock::start()), config, effects);
+
+    // Wakeups go to handle() too: they end cooldowns between motion
+    while let Some(event) = controller.next_event() {
+        let timestamp = match &event {
+            InputEvent::Motion(motion) => Some(motion.timestamp),
+            _ => None,
+        };
+        if let (Some(shake), Some(timestamp)) = (controller.handle(event, false), timestamp) {
+            println!("t={}: shake at ({}, {})", timestamp, shake.x, shake.y);
+        }
+    }
+
+    // Leaves the cursor at its original size if input ended mid-effect
+    controller.shutdown();
+}
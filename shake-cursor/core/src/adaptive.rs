@@ -0,0 +1,413 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+use crate::detector::{MotionStats, Thresholds};
+
+/// Weight of each new sample once the baseline has warmed up, so roughly
+/// the last 1/ALPHA windows of movement dominate it.
+const ALPHA: f64 = 0.002;
+
+/// Samples needed before the baseline replaces the configured thresholds.
+const WARMUP_SAMPLES: u64 = 200;
+
+/// How many standard deviations above the user's normal movement a
+/// window must be to count as a shake.
+const DEVIATIONS: f64 = 3.0;
+
+/// Save the baseline every this many samples, so a crash loses little.
+const SAVE_EVERY: u64 = 500;
+
+/// An enlargement dismissed within this long (ms) of the shake that
+/// started it was probably not wanted.
+const DISMISS_WINDOW_MS: u32 = 1500;
+
+/// A shake within this long (ms) of the cursor being restored means the
+/// user wasn't done looking for it.
+const RESHAKE_WINDOW_MS: u32 = 2000;
+
+/// Limits of the learned multipliers.
+const MAX_STRICTNESS: f64 = 1.5;
+const MAX_COOLDOWN_SCALE: f64 = 3.0;
+
+/// Exponentially weighted mean and variance of one quantity.
+#[derive(Clone, Copy, Default)]
+struct Running {
+    mean: f64,
+    variance: f64,
+}
+
+impl Running {
+    fn add(&mut self, value: f64, alpha: f64) {
+        let delta = value - self.mean;
+        self.mean += alpha * delta;
+        self.variance = (1.0 - alpha) * (self.variance + alpha * delta * delta);
+    }
+
+    fn deviation(&self) -> f64 {
+        self.variance.sqrt()
+    }
+}
+
+/// Adaptive mode of the ShakeDetector: learns the user's normal movement
+/// and sets the shake thresholds relative to it.
+///
+/// One sample of the detector's window statistics is taken per time
+/// window while the cursor is idle and the window isn't a shake. Once
+/// warmed up, the thresholds are the baseline's mean plus DEVIATIONS
+/// standard deviations, kept within half and double the configured
+/// values so an odd baseline can neither disable detection nor make it
+/// fire constantly.
+///
+/// Two learning signals adjust it further:
+///   - a quick dismissal (`ctl dismiss` right after a shake) makes the
+///     thresholds stricter;
+///   - shaking again right after the cursor was restored lengthens the
+///     shake cooldown.
+///
+/// Both relax slowly when enlargements run their course without them.
+///
+/// A baseline from new() lives in memory only. One from load() is saved
+/// back to its file every SAVE_EVERY samples, on each learning signal and
+/// when the detector saves its state.
+pub struct Adaptive {
+    /// Where the baseline is saved, if anywhere.
+    path: Option<PathBuf>,
+
+    samples: u64,
+    velocity: Running,
+    reversals: Running,
+
+    /// Multiplier (>= 1) on both thresholds, raised by quick dismissals.
+    strictness: f64,
+
+    /// Multiplier (>= 1) on cooldown_ms, raised by quick re-shakes.
+    cooldown_scale: f64,
+
+    /// Timestamp of the last sample.
+    last_sample: Option<u32>,
+
+    /// When the current shake enlargement started, if there is one.
+    enlarged_at: Option<u32>,
+
+    /// When the last shake enlargement ended by running out.
+    restored_at: Option<u32>,
+}
+
+impl Default for Adaptive {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Adaptive {
+    /// An empty baseline, kept in memory only.
+    pub fn new() -> Self {
+        Self {
+            path: None,
+            samples: 0,
+            velocity: Running::default(),
+            reversals: Running::default(),
+            strictness: 1.0,
+            cooldown_scale: 1.0,
+            last_sample: None,
+            enlarged_at: None,
+            restored_at: None,
+        }
+    }
+
+    /// The baseline saved at `path`, or an empty one if there is none
+    /// yet. Either way it is saved back to `path`.
+    pub fn load(path: &Path) -> Self {
+        let mut adaptive = Self { path: Some(path.to_path_buf()), ..Self::new() };
+        let Ok(content) = fs::read_to_string(path) else {
+            return adaptive;
+        };
+        for line in content.lines() {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let Ok(value) = value.trim().parse::<f64>() else {
+                log::warn!("Ignoring bad value in {}: {}", path.display(), line);
+                continue;
+            };
+            match key.trim() {
+                "samples" => adaptive.samples = value as u64,
+                "velocity_mean" => adaptive.velocity.mean = value,
+                "velocity_variance" => adaptive.velocity.variance = value,
+                "reversals_mean" => adaptive.reversals.mean = value,
+                "reversals_variance" => adaptive.reversals.variance = value,
+                "strictness" => adaptive.strictness = value.clamp(1.0, MAX_STRICTNESS),
+                "cooldown_scale" => adaptive.cooldown_scale = value.clamp(1.0, MAX_COOLDOWN_SCALE),
+                _ => {}
+            }
+        }
+        log::info!("Loaded adaptive baseline from {} ({} samples)", path.display(), adaptive.samples);
+        adaptive
+    }
+
+    /// Persist the baseline, if it came from load(). Failures are
+    /// logged; detection keeps going.
+    pub fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let content = format!(
+            "samples = {}\nvelocity_mean = {}\nvelocity_variance = {}\n\
+             reversals_mean = {}\nreversals_variance = {}\nstrictness = {}\ncooldown_scale = {}\n",
+            self.samples,
+            self.velocity.mean,
+            self.velocity.variance,
+            self.reversals.mean,
+            self.reversals.variance,
+            self.strictness,
+            self.cooldown_scale,
+        );
+        let result = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|()| fs::write(path, content));
+        if let Err(err) = result {
+            log::warn!("Failed to save adaptive baseline to {}: {}", path.display(), err);
+        }
+    }
+
+    /// True if a window ending at `timestamp` should be sampled: one
+    /// sample per time window, so samples don't overlap.
+    pub(crate) fn sample_due(&self, timestamp: u32, time_window_ms: u32) -> bool {
+        self.last_sample
+            .is_none_or(|last| timestamp.wrapping_sub(last) >= time_window_ms)
+    }
+
+    /// Add one window of normal movement to the baseline.
+    pub(crate) fn sample(&mut self, stats: &MotionStats, timestamp: u32) {
+        self.last_sample = Some(timestamp);
+
+        // A cumulative average until warmed up, then exponential weighting
+        self.samples += 1;
+        let alpha = (1.0 / self.samples as f64).max(ALPHA);
+        self.velocity.add(stats.velocity, alpha);
+        self.reversals.add(stats.reversals() as f64, alpha);
+
+        if self.samples == WARMUP_SAMPLES {
+            log::info!("Adaptive baseline warmed up, thresholds now follow your movement.");
+        }
+        if self.samples.is_multiple_of(SAVE_EVERY) {
+            self.save();
+        }
+    }
+
+    /// Shake thresholds for `config`, relative to the baseline.
+    pub(crate) fn thresholds(&self, config: &Config) -> Thresholds {
+        let mut thresholds = Thresholds::of(config);
+        if self.samples >= WARMUP_SAMPLES {
+            let velocity = self.velocity.mean + DEVIATIONS * self.velocity.deviation();
+            thresholds.min_velocity = velocity
+                .max(config.min_velocity / 2.0)
+                .min(config.min_velocity * 2.0);
+
+            // Reversals are counts: strictly more than the baseline allows
+            let reversals = (self.reversals.mean + DEVIATIONS * self.reversals.deviation()).floor() as u32 + 1;
+            thresholds.min_reversals = reversals
+                .max(config.min_reversals.div_ceil(2))
+                .min(config.min_reversals * 2);
+        }
+
+        thresholds.min_velocity *= self.strictness;
+        thresholds.min_reversals = (thresholds.min_reversals as f64 * self.strictness).ceil() as u32;
+        thresholds
+    }
+
+    /// The shake cooldown, lengthened by quick re-shakes.
+    pub(crate) fn cooldown_ms(&self, cooldown_ms: u32) -> u32 {
+        (cooldown_ms as f64 * self.cooldown_scale) as u32
+    }
+
+    /// A shake started the locate effects at `timestamp`.
+    pub(crate) fn shake_started(&mut self, timestamp: u32) {
+        match self.restored_at.take() {
+            Some(restored) if timestamp.wrapping_sub(restored) <= RESHAKE_WINDOW_MS => {
+                self.cooldown_scale = (self.cooldown_scale * 1.25).min(MAX_COOLDOWN_SCALE);
+                log::info!(
+                    "Shaken again {}ms after restoring, cooldown now {:.2}x.",
+                    timestamp.wrapping_sub(restored),
+                    self.cooldown_scale
+                );
+                self.save();
+            }
+            _ => self.cooldown_scale = (self.cooldown_scale / 1.02).max(1.0),
+        }
+        self.enlarged_at = Some(timestamp);
+    }
+
+    /// The user ended the enlargement at `timestamp`.
+    pub(crate) fn dismissed(&mut self, timestamp: u32) {
+        if let Some(enlarged) = self.enlarged_at.take()
+            && timestamp.wrapping_sub(enlarged) <= DISMISS_WINDOW_MS
+        {
+            self.strictness = (self.strictness * 1.1).min(MAX_STRICTNESS);
+            log::info!(
+                "Dismissed {}ms after a shake, thresholds now {:.2}x.",
+                timestamp.wrapping_sub(enlarged),
+                self.strictness
+            );
+            self.save();
+        }
+    }
+
+    /// The cooldown ran out and the cursor was restored at `timestamp`.
+    pub(crate) fn restored(&mut self, timestamp: u32) {
+        if self.enlarged_at.take().is_some() {
+            self.strictness = (self.strictness / 1.02).max(1.0);
+            self.restored_at = Some(timestamp);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp_dir::TempDir;
+
+    fn stats(reversals: u32, velocity: f64) -> MotionStats {
+        MotionStats { x_reversals: reversals, y_reversals: 0, velocity, time_span_ms: 500 }
+    }
+
+    /// A baseline warmed up on windows of the given movement.
+    fn warmed_up(reversals: u32, velocity: f64) -> Adaptive {
+        let mut adaptive = Adaptive::new();
+        for sample in 0..WARMUP_SAMPLES as u32 {
+            adaptive.sample(&stats(reversals, velocity), sample * 500);
+        }
+        adaptive
+    }
+
+    #[test]
+    fn running_starts_as_the_plain_mean() {
+        let mut running = Running::default();
+        for (count, value) in [2.0, 4.0, 6.0].into_iter().enumerate() {
+            running.add(value, 1.0 / (count + 1) as f64);
+        }
+        assert!((running.mean - 4.0).abs() < 1e-9);
+
+        // A constant has no deviation
+        let mut constant = Running::default();
+        for count in 1..=100 {
+            constant.add(7.0, 1.0 / count as f64);
+        }
+        assert!((constant.mean - 7.0).abs() < 1e-9);
+        assert!(constant.deviation() < 1e-9);
+    }
+
+    #[test]
+    fn sampling_waits_a_time_window() {
+        let mut adaptive = Adaptive::new();
+        assert!(adaptive.sample_due(1000, 500));
+        adaptive.sample(&stats(1, 100.0), 1000);
+        assert!(!adaptive.sample_due(1499, 500));
+        assert!(adaptive.sample_due(1500, 500));
+    }
+
+    #[test]
+    fn configured_thresholds_until_warmed_up() {
+        let config = Config::default();
+        let mut adaptive = Adaptive::new();
+        adaptive.sample(&stats(0, 10.0), 0);
+
+        let thresholds = adaptive.thresholds(&config);
+        assert_eq!(thresholds.min_reversals, config.min_reversals);
+        assert_eq!(thresholds.min_velocity, config.min_velocity);
+    }
+
+    #[test]
+    fn thresholds_follow_the_baseline_within_limits() {
+        let config = Config::default();
+
+        // A constant baseline: just above it
+        let thresholds = warmed_up(2, 600.0).thresholds(&config);
+        assert_eq!(thresholds.min_reversals, 3);
+        assert!((thresholds.min_velocity - 600.0).abs() < 1e-6);
+
+        // Very calm movement: no lower than half the configured values
+        let thresholds = warmed_up(0, 10.0).thresholds(&config);
+        assert_eq!(thresholds.min_reversals, config.min_reversals.div_ceil(2));
+        assert_eq!(thresholds.min_velocity, config.min_velocity / 2.0);
+
+        // Very busy movement: no higher than double
+        let thresholds = warmed_up(50, 20000.0).thresholds(&config);
+        assert_eq!(thresholds.min_reversals, config.min_reversals * 2);
+        assert_eq!(thresholds.min_velocity, config.min_velocity * 2.0);
+    }
+
+    #[test]
+    fn quick_dismissal_makes_thresholds_stricter() {
+        let config = Config::default();
+        let mut adaptive = Adaptive::new();
+
+        adaptive.shake_started(1000);
+        adaptive.dismissed(1000 + DISMISS_WINDOW_MS);
+        let thresholds = adaptive.thresholds(&config);
+        assert_eq!(thresholds.min_velocity, config.min_velocity * 1.1);
+        assert_eq!(thresholds.min_reversals, (config.min_reversals as f64 * 1.1).ceil() as u32);
+
+        // A late dismissal is not a signal
+        adaptive.shake_started(10000);
+        adaptive.dismissed(10001 + DISMISS_WINDOW_MS);
+        assert_eq!(adaptive.thresholds(&config).min_velocity, config.min_velocity * 1.1);
+
+        // Enlargements that run their course relax it again
+        adaptive.shake_started(20000);
+        adaptive.restored(22000);
+        assert!(adaptive.thresholds(&config).min_velocity < config.min_velocity * 1.1);
+    }
+
+    #[test]
+    fn strictness_is_capped() {
+        let mut adaptive = Adaptive::new();
+        for shake in 0..50 {
+            adaptive.shake_started(shake * 10000);
+            adaptive.dismissed(shake * 10000 + 100);
+        }
+        assert_eq!(adaptive.thresholds(&Config::default()).min_velocity, 500.0 * MAX_STRICTNESS);
+    }
+
+    #[test]
+    fn quick_reshake_lengthens_cooldown() {
+        let mut adaptive = Adaptive::new();
+        adaptive.shake_started(0);
+        adaptive.restored(3000);
+
+        // Shaken again within the window, then much later
+        adaptive.shake_started(3000 + RESHAKE_WINDOW_MS);
+        assert_eq!(adaptive.cooldown_ms(2000), 2500);
+        adaptive.restored(8000);
+        adaptive.shake_started(60000);
+        assert_eq!(adaptive.cooldown_ms(2000), (2000.0 * 1.25 / 1.02) as u32);
+
+        // Up to MAX_COOLDOWN_SCALE
+        for shake in 0..20 {
+            adaptive.restored(100000 * (shake + 1));
+            adaptive.shake_started(100000 * (shake + 1) + 10);
+        }
+        assert_eq!(adaptive.cooldown_ms(2000), (2000.0 * MAX_COOLDOWN_SCALE) as u32);
+    }
+
+    #[test]
+    fn load_reads_what_save_wrote() {
+        let dir = TempDir::new("adaptive");
+        let path = dir.0.join("adaptive");
+        let mut adaptive = Adaptive::load(&path);
+        for sample in 0..WARMUP_SAMPLES as u32 {
+            adaptive.sample(&stats(sample % 3, 200.0 + sample as f64), sample * 500);
+        }
+        adaptive.shake_started(0);
+        adaptive.dismissed(100);
+        adaptive.save();
+
+        let loaded = Adaptive::load(&path);
+        let config = Config::default();
+        assert_eq!(loaded.samples, WARMUP_SAMPLES);
+        assert_eq!(loaded.thresholds(&config).min_reversals, adaptive.thresholds(&config).min_reversals);
+        assert!((loaded.thresholds(&config).min_velocity - adaptive.thresholds(&config).min_velocity).abs() < 1e-6);
+    }
+}
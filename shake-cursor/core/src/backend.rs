@@ -0,0 +1,100 @@
+use std::sync::Arc;
+
+/// A single mouse motion event received from the display server.
+///
+/// This is the raw data that flows from X11 (or Wayland in the future)
+/// into the shake detector. Each event captures where the cursor was
+/// and when it was there.
+#[derive(Clone, Copy, Debug)]
+pub struct MotionEvent {
+    /// Cursor X position in pixels, relative to the root window origin (top-left corner).
+    pub x: i16,
+
+    /// Cursor Y position in pixels, relative to the root window origin (top-left corner).
+    pub y: i16,
+
+    /// Timestamp in milliseconds, as reported by the X server.
+    /// This is a monotonic counter that resets when the server restarts,
+    /// not a wall-clock time. Used to calculate velocity and evict old events.
+    pub timestamp: u32,
+
+    /// Input device that produced the event: the XInput2 source (slave)
+    /// device id on X11, the device's reader index on Wayland. Lets
+    /// traces tell a mouse from a touchpad.
+    pub device: u16,
+}
+
+/// Wakes the event loop while it is blocked in
+/// DisplayBackend::next_event(), from any thread. Obtained from the
+/// backend with waker() and shared with the control front ends.
+pub type Waker = Arc<dyn Fn() + Send + Sync>;
+
+/// Something from the display server the event loop has to react to.
+pub enum InputEvent {
+    /// The pointer moved.
+    Motion(MotionEvent),
+
+    /// The locate-pointer hotkey was pressed while the pointer was at
+    /// (x, y). Timestamps are on the same clock as MotionEvent's.
+    Hotkey { x: i16, y: i16, timestamp: u32 },
+
+    /// next_event() returned early because the waker was called, so the
+    /// loop can act on a signal or control request right away.
+    Interrupted,
+}
+
+/// The contract that any display server backend must fulfill.
+///
+/// This is the Strategy pattern. The Controller calls these methods without
+/// knowing whether it's talking to X11 or Wayland. To add a new backend,
+/// implement this trait — no existing code needs to change.
+pub trait DisplayBackend {
+    /// Connect to the display server and subscribe to mouse motion events.
+    /// Returns an error if the connection fails (e.g., no X server running).
+    fn connect(&mut self) -> Result<(), String>;
+
+    /// Block until the next mouse motion event (or hotkey press) arrives.
+    /// Returns None if the display server disconnects (e.g., Xorg crashed).
+    fn next_event(&mut self) -> Option<InputEvent>;
+
+    /// A handle that makes a blocked next_event() return
+    /// InputEvent::Interrupted. Call after connect().
+    fn waker(&self) -> Waker;
+
+    /// Change the cursor to the given size in pixels.
+    /// Used both for enlarging (96px) and restoring (original size).
+    fn set_cursor_size(&mut self, size: u32) -> Result<(), String>;
+
+    /// The user's cursor size in pixels before any change, read from their
+    /// settings during connect(). This is what the cursor is restored to.
+    fn original_cursor_size(&self) -> u32;
+
+    /// True if MotionEvent positions are screen coordinates, so screen
+    /// overlays can be drawn at them. Backends that track the pointer by
+    /// integrating relative motion into a position of their own return
+    /// false.
+    fn screen_coordinates(&self) -> bool {
+        true
+    }
+
+    /// Disconnect from the display server and free resources.
+    fn disconnect(&mut self);
+}
+
+/// What a backend found when checking whether it can run in this session.
+///
+/// Probes are cheap checks (environment variables, a short-lived test
+/// connection, device permissions) run before any backend is connected,
+/// so the registry can pick one and `--list-backends` can explain why.
+pub struct BackendProbe {
+    /// True if the backend can connect and visibly change the cursor.
+    pub usable: bool,
+
+    /// Human-readable summary of what the probe found,
+    /// e.g. "DISPLAY=:0, Xorg" or "WAYLAND_DISPLAY unset".
+    pub detail: String,
+
+    /// Set when the backend would run but with reduced effect,
+    /// e.g. X11 under XWayland only reaches X11 clients.
+    pub warning: Option<String>,
+}
@@ -0,0 +1,603 @@
+use std::env;
+use std::fmt;
+use std::fs;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+
+/// Diameters in pixels the ring effect can draw. Its window holds one
+/// ARGB image this size, redrawn for every frame of the animation.
+pub const RING_DIAMETER: RangeInclusive<u32> = 16..=512;
+
+/// Time windows (ms) the detector accepts. The window bounds how many
+/// events it buffers and walks for every new one.
+pub const TIME_WINDOW_MS: RangeInclusive<u32> = 10..=10_000;
+
+/// Direction reversals a shake can be required to have.
+pub const MIN_REVERSALS: RangeInclusive<u32> = 1..=100;
+
+/// Velocities (px/s) a shake can be required to reach. Excludes NaN.
+pub const MIN_VELOCITY: RangeInclusive<f64> = 0.0..=1_000_000.0;
+
+/// A specific cursor image to show in the enlarged state instead of the
+/// theme's `left_ptr` at a larger size, e.g. a high-contrast arrow.
+#[derive(Clone)]
+pub struct CustomCursor {
+    /// Path to a PNG or Xcursor file.
+    pub path: PathBuf,
+
+    /// Hotspot in the image's own pixel coordinates. Required in practice
+    /// for PNGs (defaults to the top-left corner); overrides the hotspot
+    /// stored in an Xcursor file when given.
+    pub hotspot: Option<(u32, u32)>,
+}
+
+/// A visual response that shows the user where the pointer is after a
+/// shake. Several can be chained; they run together in the listed order.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LocateEffect {
+    /// Swap in a larger cursor from the theme (the original behaviour).
+    Enlarge,
+
+    /// Draw an animated translucent ring around the pointer. Useful when
+    /// the cursor theme has no large assets to enlarge to.
+    Ring,
+
+    /// Draw horizontal and vertical lines spanning the screen that
+    /// intersect at the pointer.
+    Crosshair,
+
+    /// Dim the whole screen except for a circle around the pointer.
+    /// Requires a compositing manager.
+    Spotlight,
+}
+
+impl LocateEffect {
+    /// Parse an effect name as used on the command line.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "enlarge" => Some(Self::Enlarge),
+            "ring" => Some(Self::Ring),
+            "crosshair" => Some(Self::Crosshair),
+            "spotlight" => Some(Self::Spotlight),
+            _ => None,
+        }
+    }
+
+    /// Parse a comma-separated effect chain such as "enlarge,ring".
+    pub fn parse_list(list: &str) -> Result<Vec<Self>, String> {
+        list.split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(|name| {
+                Self::from_name(name).ok_or_else(|| {
+                    format!("Unknown effect '{}' (expected enlarge, ring, crosshair, or spotlight)", name)
+                })
+            })
+            .collect()
+    }
+
+    /// The name accepted by from_name().
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Enlarge => "enlarge",
+            Self::Ring => "ring",
+            Self::Crosshair => "crosshair",
+            Self::Spotlight => "spotlight",
+        }
+    }
+}
+
+/// A modifier key in a hotkey combination.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Modifier {
+    Ctrl,
+    Shift,
+    Alt,
+    Super,
+}
+
+impl Modifier {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "ctrl" | "control" => Some(Self::Ctrl),
+            "shift" => Some(Self::Shift),
+            "alt" => Some(Self::Alt),
+            "super" | "logo" => Some(Self::Super),
+            _ => None,
+        }
+    }
+
+    /// Lowercase name, as written in a hotkey combination.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Ctrl => "ctrl",
+            Self::Shift => "shift",
+            Self::Alt => "alt",
+            Self::Super => "super",
+        }
+    }
+}
+
+/// Key combination that shows the locate effects without shaking,
+/// for users who cannot shake a mouse.
+///
+/// Either modifiers plus one key ("super+l", "ctrl+alt+f12"), which is
+/// grabbed from other applications, or a single modifier on its own
+/// ("ctrl"), which fires when that modifier is pressed and released with
+/// nothing else in between, like GNOME's locate-pointer setting.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Hotkey {
+    /// Modifiers held with the key, or the single tapped modifier.
+    pub modifiers: Vec<Modifier>,
+
+    /// Key name, e.g. "l", "f12" or "space". None for a modifier tap.
+    pub key: Option<String>,
+}
+
+impl Hotkey {
+    /// Parse a combination such as "ctrl" or "super+shift+l".
+    pub fn parse(combo: &str) -> Result<Self, String> {
+        let mut modifiers = Vec::new();
+        let mut key = None;
+
+        for part in combo.split('+').map(|part| part.trim().to_lowercase()) {
+            if part.is_empty() {
+                return Err(format!("Invalid hotkey '{}'", combo));
+            }
+            match Modifier::from_name(&part) {
+                Some(modifier) if key.is_none() => {
+                    if !modifiers.contains(&modifier) {
+                        modifiers.push(modifier);
+                    }
+                }
+                None if key.is_none() => key = Some(part),
+                _ => return Err(format!("Invalid hotkey '{}' (the key must come last)", combo)),
+            }
+        }
+
+        if key.is_none() && modifiers.len() != 1 {
+            return Err(format!(
+                "Invalid hotkey '{}' (expected one modifier to tap, or modifiers plus a key)",
+                combo
+            ));
+        }
+        Ok(Self { modifiers, key })
+    }
+
+    /// True if this hotkey is a lone modifier tap.
+    pub fn is_tap(&self) -> bool {
+        self.key.is_none()
+    }
+
+    /// The combination in the form parse() accepts.
+    pub fn name(&self) -> String {
+        let mut parts: Vec<&str> = self.modifiers.iter().map(|modifier| modifier.name()).collect();
+        if let Some(key) = &self.key {
+            parts.push(key);
+        }
+        parts.join("+")
+    }
+}
+
+/// Configuration for shake detection and cursor enlargement.
+///
+/// These parameters control how sensitive the shake detection is
+/// and how the cursor responds when a shake is detected.
+#[derive(Clone)]
+pub struct Config {
+    /// How far back in time (ms) to analyze mouse motion events.
+    /// Only events within this window are considered for shake detection.
+    /// A shorter window requires faster shaking. A longer window is more forgiving.
+    pub time_window_ms: u32,
+
+    /// Minimum number of direction reversals required to count as a shake.
+    /// A reversal is when the cursor changes direction on either the X or Y axis.
+    /// Normal mouse movement rarely produces more than 1-2 reversals in 500ms.
+    pub min_reversals: u32,
+
+    /// Minimum average velocity (pixels per second) required to count as a shake.
+    /// This filters out slow, deliberate cursor adjustments that happen to reverse
+    /// direction multiple times. Only fast, intentional shaking passes this threshold.
+    pub min_velocity: f64,
+
+    /// How long (ms) to wait after the last detected shake before restoring
+    /// the cursor to its original size. Resets every time a new shake is detected,
+    /// so continuous shaking keeps the cursor enlarged.
+    pub cooldown_ms: u32,
+
+    /// Cursor size in pixels when enlarged. The original size is read from the
+    /// user's cursor theme settings on startup. Most cursor themes ship assets
+    /// at 24, 32, 48, 64, and 96 pixels.
+    pub enlarged_size: u32,
+
+    /// Image to use for the enlarged cursor instead of the theme's arrow.
+    /// Scaled to enlarged_size. X11 only.
+    pub enlarged_cursor: Option<CustomCursor>,
+
+    /// Locate effects to run while a shake is active, started in order
+    /// and stopped in reverse order. An empty chain only logs shakes.
+    pub effects: Vec<LocateEffect>,
+
+    /// Outer diameter in pixels of the highlight ring, when the
+    /// effect draws one.
+    pub ring_diameter: u32,
+
+    /// Radius in pixels of the undimmed circle left around the pointer
+    /// by the spotlight effect.
+    pub spotlight_radius: u32,
+
+    /// Whether `hotkey` shows the locate effects. X11 only.
+    pub hotkey_enabled: bool,
+
+    /// Key combination that shows the locate effects as if a shake had
+    /// been detected, with the same cooldown.
+    pub hotkey: Hotkey,
+
+    /// Whether the first motion after the pointer sat still for
+    /// wake_idle_ms shows the locate effects, e.g. when returning to
+    /// the desk after a break.
+    pub wake_enabled: bool,
+
+    /// How long (ms) the pointer must not move before the next motion
+    /// counts as waking it.
+    pub wake_idle_ms: u32,
+
+    /// How long (ms) a wake keeps the cursor enlarged. Usually shorter
+    /// than cooldown_ms, since the user didn't ask for it.
+    pub wake_cooldown_ms: u32,
+
+    /// Whether to learn the user's normal movement and set the shake
+    /// thresholds relative to it, within half and double min_reversals
+    /// and min_velocity. The daemon keeps the baseline in $XDG_STATE_HOME.
+    pub adaptive_enabled: bool,
+
+    /// File to keep the daemon's metrics in, in the Prometheus text
+    /// format, for node_exporter's textfile collector. None: not written.
+    pub metrics_textfile: Option<PathBuf>,
+}
+
+// Default ass the <Default> Values 
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            time_window_ms: 500,
+            min_reversals: 3,
+            min_velocity: 500.0,
+            cooldown_ms: 2000,
+            enlarged_size: 96,
+            enlarged_cursor: None,
+            effects: vec![LocateEffect::Enlarge],
+            ring_diameter: 160,
+            spotlight_radius: 120,
+            hotkey_enabled: false,
+            hotkey: Hotkey { modifiers: vec![Modifier::Ctrl], key: None },
+            wake_enabled: false,
+            wake_idle_ms: 30000,
+            wake_cooldown_ms: 1000,
+            adaptive_enabled: false,
+            metrics_textfile: None,
+        }
+    }
+}
+
+impl Config {
+    /// Default config file location:
+    /// `$XDG_CONFIG_HOME/shake-cursor/config`, falling back to
+    /// `~/.config/shake-cursor/config`.
+    pub fn default_path() -> Option<PathBuf> {
+        let base = env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(base.join("shake-cursor").join("config"))
+    }
+
+    /// Load a config file of `key = value` lines on top of the defaults.
+    /// Blank lines and lines starting with `#` are ignored. Keys are the
+    /// same ones accepted by set().
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|err| format!("Failed to read {}: {}", path.display(), err))?;
+
+        let mut config = Self::default();
+        for (number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line.split_once('=').ok_or_else(|| {
+                format!("{}:{}: expected 'key = value'", path.display(), number + 1)
+            })?;
+            config.set(key.trim(), value.trim())
+                .map_err(|err| format!("{}:{}: {}", path.display(), number + 1, err))?;
+        }
+        Ok(config)
+    }
+
+    /// Set one parameter from its textual form, as used by config files
+    /// and `shake-cursor ctl set <key> <value>`. Numbers outside the
+    /// range the daemon can work with, and NaN, are rejected.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        fn parsed<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, String> {
+            value.parse().map_err(|_| format!("Invalid value '{}' for {}", value, key))
+        }
+        fn ranged<T>(key: &str, value: &str, range: RangeInclusive<T>) -> Result<T, String>
+        where
+            T: std::str::FromStr + PartialOrd + fmt::Display,
+        {
+            let number = parsed(key, value)?;
+            if range.contains(&number) {
+                Ok(number)
+            } else {
+                Err(format!("Invalid value '{}' for {} (expected {} to {})", value, key, range.start(), range.end()))
+            }
+        }
+
+        match key {
+            "time_window_ms" => self.time_window_ms = ranged(key, value, TIME_WINDOW_MS)?,
+            "min_reversals" => self.min_reversals = ranged(key, value, MIN_REVERSALS)?,
+            "min_velocity" => self.min_velocity = ranged(key, value, MIN_VELOCITY)?,
+            "cooldown_ms" => self.cooldown_ms = ranged(key, value, 100..=600_000)?,
+            "enlarged_size" => self.enlarged_size = ranged(key, value, 8..=512)?,
+            "ring_diameter" => self.ring_diameter = ranged(key, value, RING_DIAMETER)?,
+            "spotlight_radius" => self.spotlight_radius = ranged(key, value, 8..=4096)?,
+            "enlarged_cursor" => {
+                self.enlarged_cursor = if value.is_empty() {
+                    None
+                } else {
+                    Some(CustomCursor {
+                        path: PathBuf::from(value),
+                        hotspot: self.enlarged_cursor.as_ref().and_then(|cursor| cursor.hotspot),
+                    })
+                };
+            }
+            "enlarged_cursor_hotspot" if value.is_empty() && self.enlarged_cursor.is_none() => {
+                // Nothing to clear; this is what entries() lists without a cursor
+            }
+            "enlarged_cursor_hotspot" => {
+                let cursor = self.enlarged_cursor.as_mut()
+                    .ok_or_else(|| "enlarged_cursor_hotspot requires enlarged_cursor".to_string())?;
+                cursor.hotspot = if value.is_empty() {
+                    None
+                } else {
+                    let (x, y) = value.split_once(',')
+                        .ok_or_else(|| format!("Invalid hotspot '{}' (expected X,Y)", value))?;
+                    Some((parsed(key, x.trim())?, parsed(key, y.trim())?))
+                };
+            }
+            "effects" => self.effects = LocateEffect::parse_list(value)?,
+            "hotkey_enabled" => self.hotkey_enabled = parsed(key, value)?,
+            "hotkey" => self.hotkey = Hotkey::parse(value)?,
+            "wake_enabled" => self.wake_enabled = parsed(key, value)?,
+            "wake_idle_ms" => self.wake_idle_ms = ranged(key, value, 1000..=86_400_000)?,
+            "wake_cooldown_ms" => self.wake_cooldown_ms = ranged(key, value, 100..=600_000)?,
+            "adaptive_enabled" => self.adaptive_enabled = parsed(key, value)?,
+            "metrics_textfile" => {
+                self.metrics_textfile = if value.is_empty() { None } else { Some(PathBuf::from(value)) };
+            }
+            _ => return Err(format!("Unknown config key '{}'", key)),
+        }
+        Ok(())
+    }
+
+    /// Every parameter as a (key, value) pair in the textual form set()
+    /// accepts, in a stable order. Used by `ctl get-config`.
+    pub fn entries(&self) -> Vec<(&'static str, String)> {
+        let cursor = self.enlarged_cursor.as_ref();
+        vec![
+            ("time_window_ms", self.time_window_ms.to_string()),
+            ("min_reversals", self.min_reversals.to_string()),
+            ("min_velocity", self.min_velocity.to_string()),
+            ("cooldown_ms", self.cooldown_ms.to_string()),
+            ("enlarged_size", self.enlarged_size.to_string()),
+            (
+                "enlarged_cursor",
+                cursor.map(|cursor| cursor.path.display().to_string()).unwrap_or_default(),
+            ),
+            (
+                "enlarged_cursor_hotspot",
+                cursor
+                    .and_then(|cursor| cursor.hotspot)
+                    .map(|(x, y)| format!("{},{}", x, y))
+                    .unwrap_or_default(),
+            ),
+            (
+                "effects",
+                self.effects.iter().map(|effect| effect.name()).collect::<Vec<_>>().join(","),
+            ),
+            ("ring_diameter", self.ring_diameter.to_string()),
+            ("spotlight_radius", self.spotlight_radius.to_string()),
+            ("hotkey_enabled", self.hotkey_enabled.to_string()),
+            ("hotkey", self.hotkey.name()),
+            ("wake_enabled", self.wake_enabled.to_string()),
+            ("wake_idle_ms", self.wake_idle_ms.to_string()),
+            ("wake_cooldown_ms", self.wake_cooldown_ms.to_string()),
+            ("adaptive_enabled", self.adaptive_enabled.to_string()),
+            (
+                "metrics_textfile",
+                self.metrics_textfile.as_ref().map(|path| path.display().to_string()).unwrap_or_default(),
+            ),
+        ]
+    }
+}
+
+/// Write `values` into the config file at `path`, keeping the rest of
+/// it (other keys, comments) as it is. Lines for those keys are replaced
+/// in place and missing ones appended. Creates the file and its directory
+/// if needed.
+pub fn update_file(path: &Path, values: &[(&str, String)]) -> Result<(), String> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(format!("Failed to read {}: {}", path.display(), err)),
+    };
+
+    let mut written = vec![false; values.len()];
+    let mut lines: Vec<String> = content
+        .lines()
+        .map(|line| {
+            let key = line.split_once('=').map(|(key, _)| key.trim());
+            match values.iter().position(|(name, _)| Some(*name) == key) {
+                Some(index) if !line.trim_start().starts_with('#') => {
+                    written[index] = true;
+                    format!("{} = {}", values[index].0, values[index].1)
+                }
+                _ => line.to_string(),
+            }
+        })
+        .collect();
+    for ((key, value), written) in values.iter().zip(written) {
+        if !written {
+            lines.push(format!("{} = {}", key, value));
+        }
+    }
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .map_err(|err| format!("Failed to create {}: {}", dir.display(), err))?;
+    }
+    fs::write(path, lines.join("\n") + "\n")
+        .map_err(|err| format!("Failed to write {}: {}", path.display(), err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp_dir::TempDir;
+    use std::path::PathBuf;
+
+    /// Where the config file goes in `dir`; its directory doesn't exist yet.
+    fn config_file(dir: &TempDir) -> PathBuf {
+        dir.0.join("shake-cursor").join("config")
+    }
+
+    #[test]
+    fn entries_round_trip_through_set() {
+        let mut config = Config {
+            time_window_ms: 400,
+            min_reversals: 5,
+            min_velocity: 812.5,
+            enlarged_cursor: Some(CustomCursor { path: PathBuf::from("/tmp/big arrow.png"), hotspot: Some((3, 7)) }),
+            effects: vec![LocateEffect::Ring, LocateEffect::Enlarge],
+            hotkey_enabled: true,
+            adaptive_enabled: true,
+            metrics_textfile: Some(PathBuf::from("/var/lib/node_exporter/shake-cursor.prom")),
+            ..Config::default()
+        };
+        config.set("hotkey", "super+shift+l").unwrap();
+
+        let mut read = Config::default();
+        for (key, value) in config.entries() {
+            read.set(key, &value).unwrap_or_else(|err| panic!("{} = {}: {}", key, value, err));
+        }
+        assert_eq!(read.entries(), config.entries());
+
+        // And back to the defaults, including the optional values
+        for (key, value) in Config::default().entries() {
+            read.set(key, &value).unwrap();
+        }
+        assert_eq!(read.entries(), Config::default().entries());
+    }
+
+    #[test]
+    fn load_applies_lines_on_top_of_the_defaults() {
+        let dir = TempDir::new("config-load");
+        let file = config_file(&dir);
+        fs::create_dir_all(file.parent().unwrap()).unwrap();
+        fs::write(&file, "# Stricter\n\n  min_reversals = 5\nmin_velocity=750\n").unwrap();
+        let config = Config::load(&file).unwrap();
+        assert_eq!((config.min_reversals, config.min_velocity), (5, 750.0));
+        assert_eq!(config.cooldown_ms, Config::default().cooldown_ms);
+
+        for (content, line) in [("min_reversals = 5\nverbose\n", 2), ("\ncolour = red\n", 2), ("cooldown_ms = 0\n", 1)] {
+            fs::write(&file, content).unwrap();
+            let err = Config::load(&file).err().unwrap();
+            assert!(err.contains(&format!(":{}: ", line)), "{}", err);
+        }
+    }
+
+    #[test]
+    fn set_rejects_values_out_of_range() {
+        let mut config = Config::default();
+        for (key, value) in [
+            ("time_window_ms", "0"),
+            ("time_window_ms", "60000"),
+            ("min_reversals", "0"),
+            ("min_velocity", "NaN"),
+            ("min_velocity", "inf"),
+            ("min_velocity", "-1"),
+            ("cooldown_ms", "0"),
+            ("enlarged_size", "0"),
+            ("ring_diameter", "0"),
+            ("ring_diameter", "1000"),
+            ("spotlight_radius", "100000"),
+            ("wake_idle_ms", "0"),
+            ("wake_cooldown_ms", "0"),
+            ("cooldown_ms", "-5"),
+            ("min_reversals", "three"),
+        ] {
+            let err = config.set(key, value).err().unwrap_or_else(|| panic!("accepted {} = {}", key, value));
+            assert!(err.contains(key), "{}", err);
+        }
+        // Rejected values leave the config as it was
+        assert_eq!(config.entries(), Config::default().entries());
+
+        for (key, value) in [("time_window_ms", "10"), ("min_velocity", "0"), ("cooldown_ms", "600000")] {
+            config.set(key, value).unwrap();
+        }
+        assert_eq!((config.time_window_ms, config.min_velocity, config.cooldown_ms), (10, 0.0, 600_000));
+    }
+
+    #[test]
+    fn set_checks_the_other_keys() {
+        let mut config = Config::default();
+        assert!(config.set("colour", "red").is_err());
+        assert!(config.set("effects", "sparkles").is_err());
+        assert!(config.set("hotkey", "l+ctrl").is_err());
+        assert!(config.set("hotkey", "ctrl+shift").is_err());
+        assert!(config.set("enlarged_cursor_hotspot", "1,2").is_err());
+        assert!(config.set("enlarged_cursor_hotspot", "").is_ok());
+
+        config.set("enlarged_cursor", "/tmp/arrow.png").unwrap();
+        assert!(config.set("enlarged_cursor_hotspot", "1").is_err());
+        config.set("enlarged_cursor_hotspot", "1, 2").unwrap();
+        // Changing the image keeps the hotspot, clearing it drops both
+        config.set("enlarged_cursor", "/tmp/other.png").unwrap();
+        assert_eq!(config.enlarged_cursor.as_ref().unwrap().hotspot, Some((1, 2)));
+        config.set("enlarged_cursor", "").unwrap();
+        assert!(config.enlarged_cursor.is_none());
+    }
+
+    #[test]
+    fn update_file_keeps_comments_and_other_keys() {
+        let dir = TempDir::new("config-update");
+        let file = config_file(&dir);
+        fs::create_dir_all(file.parent().unwrap()).unwrap();
+        fs::write(
+            &file,
+            "# Shake thresholds\n# min_reversals = 9\nmin_reversals=3\n\ncooldown_ms = 1500\nmin_velocity = 500\n",
+        )
+        .unwrap();
+
+        update_file(&file, &[("min_reversals", "5".to_string()), ("min_velocity", "750".to_string())]).unwrap();
+        assert_eq!(
+            fs::read_to_string(&file).unwrap(),
+            "# Shake thresholds\n# min_reversals = 9\nmin_reversals = 5\n\ncooldown_ms = 1500\nmin_velocity = 750\n"
+        );
+        let config = Config::load(&file).unwrap();
+        assert_eq!((config.min_reversals, config.cooldown_ms, config.min_velocity), (5, 1500, 750.0));
+    }
+
+    #[test]
+    fn update_file_appends_missing_keys_and_creates_the_file() {
+        let dir = TempDir::new("config-append");
+        let file = config_file(&dir);
+        update_file(&file, &[("min_reversals", "5".to_string())]).unwrap();
+        assert_eq!(fs::read_to_string(&file).unwrap(), "min_reversals = 5\n");
+
+        update_file(&file, &[("min_velocity", "750".to_string()), ("min_reversals", "6".to_string())]).unwrap();
+        assert_eq!(fs::read_to_string(&file).unwrap(), "min_reversals = 6\nmin_velocity = 750\n");
+        let config = Config::load(&file).unwrap();
+        assert_eq!((config.min_reversals, config.min_velocity), (6, 750.0));
+    }
+}
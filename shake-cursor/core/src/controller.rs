@@ -0,0 +1,765 @@
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::adaptive::Adaptive;
+use crate::backend::{DisplayBackend, InputEvent, Waker};
+use crate::config::Config;
+use crate::detector::{CursorState, ShakeDetector, Trigger};
+use crate::effect::EffectChain;
+use crate::metrics::{Metrics, SharedMetrics, TimedBackend};
+use crate::trace::TraceWriter;
+
+/// Score at which idle motion counts as a false start if it then dies
+/// down without becoming a shake.
+const FALSE_START_SCORE: f64 = 0.5;
+
+/// Monotonic time, used to estimate the backend's clock when the loop is
+/// woken without an event, and to wake it when a cooldown runs out while
+/// the pointer is still. A trait so tests can control time.
+pub trait Clock {
+    /// Time elapsed since an arbitrary, fixed origin.
+    fn now(&self) -> Duration;
+
+    /// Call `waker` once now() reaches `deadline`, replacing any earlier
+    /// request that hasn't fired yet.
+    fn wake_at(&self, deadline: Duration, waker: Waker);
+}
+
+/// The real clock. Wakeups come from a background thread, started by
+/// the first wake_at().
+pub struct SystemClock {
+    origin: Instant,
+    alarm: Arc<Alarm>,
+}
+
+/// The pending wakeup of a SystemClock, shared with its thread.
+#[derive(Default)]
+struct Alarm {
+    state: Mutex<AlarmState>,
+    changed: Condvar,
+}
+
+#[derive(Default)]
+struct AlarmState {
+    pending: Option<(Instant, Waker)>,
+    started: bool,
+    stopped: bool,
+}
+
+impl SystemClock {
+    /// A clock whose origin is now.
+    pub fn start() -> Self {
+        Self { origin: Instant::now(), alarm: Arc::default() }
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.origin.elapsed()
+    }
+
+    fn wake_at(&self, deadline: Duration, waker: Waker) {
+        let mut state = self.alarm.state.lock().unwrap();
+        state.pending = Some((self.origin + deadline, waker));
+        if !state.started {
+            state.started = true;
+            let alarm = Arc::clone(&self.alarm);
+            thread::spawn(move || alarm.run());
+        }
+        self.alarm.changed.notify_one();
+    }
+}
+
+impl Drop for SystemClock {
+    fn drop(&mut self) {
+        self.alarm.state.lock().unwrap().stopped = true;
+        self.alarm.changed.notify_one();
+    }
+}
+
+impl Alarm {
+    /// Sleep until the pending deadline and call its waker, until the
+    /// clock is dropped.
+    fn run(&self) {
+        let mut state = self.state.lock().unwrap();
+        while !state.stopped {
+            let Some((deadline, _)) = &state.pending else {
+                state = self.changed.wait(state).unwrap();
+                continue;
+            };
+            let now = Instant::now();
+            if *deadline > now {
+                let timeout = *deadline - now;
+                state = self.changed.wait_timeout(state, timeout).unwrap().0;
+                continue;
+            }
+            let (_, waker) = state.pending.take().unwrap();
+            drop(state);
+            waker();
+            state = self.state.lock().unwrap();
+        }
+    }
+}
+
+/// A shake that started the locate effects, at pointer position (x, y).
+pub struct ShakeStarted {
+    pub x: i16,
+    pub y: i16,
+}
+
+/// The cursor state machine, driving the locate effects from input events.
+///
+/// Owns the backend, the detector and the effect chain. The event loop
+/// calls next_event() to wait for input, applies control requests through
+/// the action methods, then passes the event to handle(). Reporting
+/// (D-Bus, systemd status) stays with the caller.
+pub struct Controller {
+    backend: Box<dyn DisplayBackend>,
+    clock: Box<dyn Clock>,
+    detector: ShakeDetector,
+    effects: EffectChain,
+
+    /// Factory for the effect chain, called again when the config changes.
+    build_effects: fn(&Config, &dyn DisplayBackend) -> EffectChain,
+
+    /// Position and backend timestamp of the latest event, so actions run
+    /// between events can place effects at the pointer.
+    position: (i16, i16, u32),
+
+    /// Clock time when `position` was taken, to estimate the backend's
+    /// clock when woken without an event.
+    position_at: Duration,
+
+    /// With `--record`, every motion event and decision is logged here.
+    trace: Option<TraceWriter>,
+
+    /// Shared with whoever reads them, see shared_metrics().
+    metrics: SharedMetrics,
+
+    /// Backend timestamp when the running effects started.
+    effects_started: Option<u32>,
+
+    /// Idle motion has reached FALSE_START_SCORE without becoming a shake.
+    false_start: bool,
+}
+
+impl Controller {
+    /// Take over a connected backend and build the effects for `config`
+    /// with `build_effects`, which is also given the backend to check what
+    /// it supports. Which effects exist is up to the caller: the daemon
+    /// adds screen overlays to the ResizeEffect.
+    pub fn new(
+        backend: Box<dyn DisplayBackend>,
+        clock: Box<dyn Clock>,
+        config: Config,
+        build_effects: fn(&Config, &dyn DisplayBackend) -> EffectChain,
+    ) -> Self {
+        let position_at = clock.now();
+        let effects = build_effects(&config, backend.as_ref());
+        Self {
+            backend,
+            clock,
+            effects,
+            build_effects,
+            detector: ShakeDetector::new(config),
+            position: (0, 0, 0),
+            position_at,
+            trace: None,
+            metrics: SharedMetrics::default(),
+            effects_started: None,
+            false_start: false,
+        }
+    }
+
+    /// Continue adaptive mode from a learned baseline, e.g. one loaded
+    /// with Adaptive::load(), which is then saved back at shutdown.
+    pub fn set_adaptive(&mut self, adaptive: Adaptive) {
+        self.detector.set_adaptive(adaptive);
+    }
+
+    /// Log every motion event and the decision it led to.
+    pub fn record_to(&mut self, trace: TraceWriter) {
+        self.trace = Some(trace);
+    }
+
+    /// Wakes next_event() from another thread.
+    pub fn waker(&self) -> Waker {
+        self.backend.waker()
+    }
+
+    /// Name of the current CursorState.
+    pub fn state_name(&self) -> &'static str {
+        self.detector.state.name()
+    }
+
+    /// The detector's shake score for the buffered motion.
+    pub fn score(&self) -> f64 {
+        self.detector.score()
+    }
+
+    /// Counters and histograms since the controller was created.
+    pub fn metrics(&self) -> MutexGuard<'_, Metrics> {
+        self.metrics.lock().unwrap()
+    }
+
+    /// The same metrics, for reading from other threads. The controller
+    /// keeps updating them in place.
+    pub fn shared_metrics(&self) -> SharedMetrics {
+        Arc::clone(&self.metrics)
+    }
+
+    /// Block until the next input event, or until woken (Interrupted).
+    /// None if the display server connection was lost.
+    pub fn next_event(&mut self) -> Option<InputEvent> {
+        let event = self.backend.next_event()?;
+        let now = self.clock.now();
+
+        let (x, y, timestamp) = match &event {
+            InputEvent::Motion(motion) => (motion.x, motion.y, motion.timestamp),
+            InputEvent::Hotkey { x, y, timestamp } => (*x, *y, *timestamp),
+            InputEvent::Interrupted => {
+                let (x, y, timestamp) = self.position;
+                let elapsed = now.saturating_sub(self.position_at);
+                (x, y, timestamp.wrapping_add(elapsed.as_millis() as u32))
+            }
+        };
+        self.position = (x, y, timestamp);
+        self.position_at = now;
+        log::debug!("Event: x={}, y={}, t={}", x, y, timestamp);
+        Some(event)
+    }
+
+    /// Show the locate effects now, as if a shake had been detected.
+    pub fn trigger(&mut self) {
+        if matches!(self.detector.state, CursorState::Idle) {
+            log::info!("Triggered by control client, starting locate effects.");
+            self.start_effects();
+        }
+        self.enlarge(Trigger::Shake);
+    }
+
+    /// End the locate effects now. Early dismissals teach adaptive mode
+    /// that the shake was unwanted.
+    pub fn dismiss(&mut self) {
+        if !matches!(self.detector.state, CursorState::Idle) {
+            log::info!("Dismissed by control client, restoring cursor.");
+            self.stop_effects();
+            self.detector.dismissed(self.position.2);
+        }
+    }
+
+    /// Detection was paused: end any running effect.
+    pub fn pause(&mut self) {
+        log::info!("Detection paused.");
+        if !matches!(self.detector.state, CursorState::Idle) {
+            self.stop_effects();
+        }
+    }
+
+    /// Switch to a new configuration. Effects may have changed, so the
+    /// current one ends and the chain is rebuilt. Backend settings need
+    /// a restart.
+    pub fn apply_config(&mut self, config: Config) {
+        if !matches!(self.detector.state, CursorState::Idle) {
+            self.stop_effects();
+        }
+        self.effects = (self.build_effects)(&config, self.backend.as_ref());
+        self.detector.set_config(config);
+        log::info!("Configuration applied.");
+    }
+
+    /// Run one event through the state machine. Returns where a shake
+    /// started the locate effects, if this event did that.
+    pub fn handle(&mut self, event: InputEvent, paused: bool) -> Option<ShakeStarted> {
+        let started = self.clock.now();
+        let shake = self.handle_event(event, paused);
+        let took = self.clock.now().saturating_sub(started);
+        self.metrics().handle_seconds.observe(took.as_secs_f64());
+        shake
+    }
+
+    fn handle_event(&mut self, event: InputEvent, paused: bool) -> Option<ShakeStarted> {
+        let (x, y, timestamp) = self.position;
+
+        // The hotkey forces the enlarged state exactly like a shake,
+        // including while detection is paused, and shares its cooldown
+        let event = match event {
+            InputEvent::Motion(motion) => motion,
+            InputEvent::Hotkey { .. } => {
+                if matches!(self.detector.state, CursorState::Idle) {
+                    log::info!("Locate hotkey pressed, starting locate effects.");
+                    self.start_effects();
+                }
+                self.enlarge(Trigger::Shake);
+                return None;
+            }
+            // Woken without motion: a cooldown may have run out
+            InputEvent::Interrupted => {
+                self.check_cooldown(timestamp);
+                return None;
+            }
+        };
+        self.metrics().motion_events += 1;
+
+        // While paused, motion is ignored, but it still means the pointer
+        // wasn't idle
+        if paused && matches!(self.detector.state, CursorState::Idle) {
+            self.detector.ignore_motion(event);
+            return None;
+        }
+
+        // Feed the event into the shake detector's ring buffer
+        self.detector.record_motion(event);
+        self.metrics().buffer_events.observe(self.detector.events.len() as f64);
+        let mut started = None;
+
+        // Everything below is decided from the same window statistics
+        let thresholds = self.detector.thresholds();
+        let stats = self.detector.stats();
+        let score = stats.score(&thresholds);
+        let shaking = stats.is_shake(&thresholds);
+
+        // State machine transitions based on shake detection and waking
+        match self.detector.state {
+            CursorState::Idle => {
+                self.track_false_start(score);
+                if shaking {
+                    log::info!("Shake detected, starting locate effects.");
+                    self.start_effects();
+                    self.enlarge(Trigger::Shake);
+                    self.detector.shake_started(timestamp);
+                    self.metrics().shakes += 1;
+                    started = Some(ShakeStarted { x, y });
+                } else if self.detector.is_waking() {
+                    log::info!("Pointer moved after {}ms idle, starting locate effects.", self.detector.idle_ms);
+                    self.start_effects();
+                    self.enlarge(Trigger::Wake);
+                }
+            }
+            CursorState::Enlarged { .. } => {
+                // Effects follow the pointer for as long as they are active
+                let mut backend = TimedBackend {
+                    backend: self.backend.as_mut(),
+                    clock: self.clock.as_ref(),
+                    metrics: &self.metrics,
+                };
+                self.effects.update(&mut backend, x, y, score, timestamp);
+
+                if shaking {
+                    // Still shaking — reset the cooldown timer
+                    self.enlarge(Trigger::Shake);
+                } else {
+                    self.check_cooldown(timestamp);
+                }
+            }
+            CursorState::Restoring => self.check_cooldown(timestamp),
+        }
+
+        // Log the event with the decision it led to
+        if let (Some(writer), Some(event)) = (&mut self.trace, self.detector.events.back()) {
+            writer.write_motion(event, score, shaking, self.detector.state.name());
+        }
+
+        started
+    }
+
+    /// Clean shutdown: stop any active effects, save learned state, and
+    /// disconnect from the display server.
+    pub fn shutdown(&mut self) {
+        if !matches!(self.detector.state, CursorState::Idle) {
+            self.stop_effects();
+        }
+        self.detector.save_state();
+        self.backend.disconnect();
+        if let Some(writer) = &mut self.trace {
+            writer.flush();
+        }
+    }
+
+    fn start_effects(&mut self) {
+        let (x, y, timestamp) = self.position;
+        let mut backend = TimedBackend {
+            backend: self.backend.as_mut(),
+            clock: self.clock.as_ref(),
+            metrics: &self.metrics,
+        };
+        self.effects.start(&mut backend, x, y, timestamp);
+        self.effects_started = Some(timestamp);
+        self.false_start = false;
+    }
+
+    fn stop_effects(&mut self) {
+        let mut backend = TimedBackend {
+            backend: self.backend.as_mut(),
+            clock: self.clock.as_ref(),
+            metrics: &self.metrics,
+        };
+        self.effects.stop(&mut backend);
+        self.detector.state = CursorState::Idle;
+        if let Some(started) = self.effects_started.take() {
+            let enlarged_ms = self.position.2.wrapping_sub(started);
+            self.metrics().enlarged_seconds.observe(enlarged_ms as f64 / 1000.0);
+        }
+    }
+
+    /// Count idle motion that reached FALSE_START_SCORE once it dies down
+    /// again without having become a shake.
+    fn track_false_start(&mut self, score: f64) {
+        if score >= FALSE_START_SCORE {
+            self.false_start = true;
+        } else if self.false_start {
+            self.false_start = false;
+            self.metrics().false_starts += 1;
+        }
+    }
+
+    /// Timed transitions, run on motion and on wakeups alike:
+    ///   Enlarged → Restoring   (cooldown expired, wake again right away)
+    ///   Restoring → Idle       (cursor restored)
+    /// An unexpired cooldown sets the wakeup for when it runs out.
+    fn check_cooldown(&mut self, timestamp: u32) {
+        match self.detector.state {
+            CursorState::Idle => {}
+            CursorState::Enlarged { since, trigger } => {
+                let cooldown_ms = self.detector.cooldown_ms(trigger);
+                if timestamp.wrapping_sub(since) >= cooldown_ms {
+                    // Cooldown expired — begin restoring
+                    log::info!("Cooldown expired, restoring cursor.");
+                    self.detector.state = CursorState::Restoring;
+                    self.wake_at(timestamp);
+                } else {
+                    self.wake_at(since.wrapping_add(cooldown_ms));
+                }
+            }
+            CursorState::Restoring => {
+                self.stop_effects();
+                self.detector.restored(timestamp);
+            }
+        }
+    }
+
+    /// Enter (or stay in) the enlarged state, with the cooldown starting
+    /// now. The wakeup is set on entering; while shaking extends the
+    /// cooldown, check_cooldown() moves it when it fires.
+    fn enlarge(&mut self, trigger: Trigger) {
+        let armed = matches!(self.detector.state, CursorState::Enlarged { trigger: current, .. } if current == trigger);
+        let since = self.position.2;
+        self.detector.state = CursorState::Enlarged { since, trigger };
+        if !armed {
+            self.wake_at(since.wrapping_add(self.detector.cooldown_ms(trigger)));
+        }
+    }
+
+    /// Have the clock wake the event loop at backend time `timestamp`,
+    /// which is never behind the latest event. Wrapping, as the backend
+    /// clock wraps every ~49.7 days.
+    fn wake_at(&self, timestamp: u32) {
+        let (_, _, now) = self.position;
+        let delay = Duration::from_millis(timestamp.wrapping_sub(now) as u64);
+        self.clock.wake_at(self.position_at + delay, self.backend.waker());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::effect::ResizeEffect;
+    use crate::mock_backend::{drift, motion, shake, MockBackend, MockClock, ORIGINAL_SIZE};
+
+    fn resize(config: &Config, _: &dyn DisplayBackend) -> EffectChain {
+        EffectChain::new(vec![Box::new(ResizeEffect::new(config.enlarged_size))])
+    }
+
+    fn controller(backend: MockBackend) -> (Controller, MockClock) {
+        let clock = MockClock::default();
+        let controller = Controller::new(Box::new(backend), Box::new(clock.clone()), Config::default(), resize);
+        (controller, clock)
+    }
+
+    /// Handle every scripted event, returning the timestamp and state
+    /// after each.
+    fn run(controller: &mut Controller, paused: bool) -> Vec<(u32, &'static str)> {
+        let mut states = Vec::new();
+        while let Some(event) = controller.next_event() {
+            controller.handle(event, paused);
+            states.push((controller.position.2, controller.state_name()));
+        }
+        states
+    }
+
+    /// The distinct states visited, in order.
+    fn transitions(states: &[(u32, &'static str)]) -> Vec<&'static str> {
+        let mut names: Vec<&str> = states.iter().map(|(_, name)| *name).collect();
+        names.dedup();
+        names
+    }
+
+    fn first(states: &[(u32, &'static str)], name: &str) -> Option<u32> {
+        states.iter().find(|(_, state)| *state == name).map(|(timestamp, _)| *timestamp)
+    }
+
+    fn enlarged_size() -> u32 {
+        Config::default().enlarged_size
+    }
+
+    #[test]
+    fn shake_enlarges_then_restores_after_cooldown() {
+        let (backend, log) = MockBackend::new([shake(0, 400), drift(400, 3000)].into_iter().flatten().collect());
+        let (mut controller, _) = controller(backend);
+
+        let states = run(&mut controller, false);
+
+        assert_eq!(transitions(&states), ["idle", "enlarged", "restoring", "idle"]);
+        assert_eq!(*log.sizes.borrow(), [enlarged_size(), ORIGINAL_SIZE]);
+    }
+
+    #[test]
+    fn continuous_shaking_extends_cooldown() {
+        let (backend, log) = MockBackend::new([shake(0, 5000), drift(5000, 8000)].into_iter().flatten().collect());
+        let (mut controller, _) = controller(backend);
+        let cooldown_ms = Config::default().cooldown_ms;
+
+        let states = run(&mut controller, false);
+
+        // Enlarged once, and restored no sooner than a cooldown after the
+        // shaking stopped, although it lasted longer than one
+        assert_eq!(transitions(&states), ["idle", "enlarged", "restoring", "idle"]);
+        assert!(first(&states, "restoring").unwrap() >= 5000 + cooldown_ms);
+        assert_eq!(*log.sizes.borrow(), [enlarged_size(), ORIGINAL_SIZE]);
+    }
+
+    #[test]
+    fn shutdown_restores_enlarged_cursor() {
+        let (backend, log) = MockBackend::new(shake(0, 400));
+        let (mut controller, _) = controller(backend);
+
+        run(&mut controller, false);
+        assert_eq!(controller.state_name(), "enlarged");
+        controller.shutdown();
+
+        assert_eq!(*log.sizes.borrow(), [enlarged_size(), ORIGINAL_SIZE]);
+        assert!(log.disconnected.get());
+    }
+
+    #[test]
+    fn shutdown_when_idle_leaves_cursor_alone() {
+        let (backend, log) = MockBackend::new(drift(0, 1000));
+        let (mut controller, _) = controller(backend);
+
+        run(&mut controller, false);
+        controller.shutdown();
+
+        assert!(log.sizes.borrow().is_empty());
+        assert!(log.disconnected.get());
+    }
+
+    #[test]
+    fn backend_errors_do_not_stall_state_machine() {
+        let (backend, log) = MockBackend::failing([shake(0, 400), drift(400, 3000)].into_iter().flatten().collect());
+        let (mut controller, _) = controller(backend);
+
+        let states = run(&mut controller, false);
+
+        // Failures are logged; the cursor is still asked to restore
+        assert_eq!(transitions(&states), ["idle", "enlarged", "restoring", "idle"]);
+        assert_eq!(*log.sizes.borrow(), [enlarged_size(), ORIGINAL_SIZE]);
+    }
+
+    #[test]
+    fn lost_connection_ends_event_stream() {
+        let (backend, _) = MockBackend::new(vec![motion(0, 0, 0)]);
+        let (mut controller, _) = controller(backend);
+
+        assert!(controller.next_event().is_some());
+        assert!(controller.next_event().is_none());
+    }
+
+    #[test]
+    fn paused_detection_ignores_shakes() {
+        let (backend, log) = MockBackend::new(shake(0, 1000));
+        let (mut controller, _) = controller(backend);
+
+        let states = run(&mut controller, true);
+
+        assert_eq!(transitions(&states), ["idle"]);
+        assert!(log.sizes.borrow().is_empty());
+    }
+
+    #[test]
+    fn motion_while_paused_is_not_idle() {
+        let config = Config { wake_enabled: true, ..Config::default() };
+        let wake_idle_ms = config.wake_idle_ms;
+        let (backend, log) = MockBackend::new(drift(0, 2 * wake_idle_ms + 1000));
+        let mut controller = Controller::new(Box::new(backend), Box::new(MockClock::default()), config, resize);
+
+        // Moving before, during and after a pause longer than wake_idle_ms
+        while let Some(event) = controller.next_event() {
+            let (_, _, timestamp) = controller.position;
+            let paused = (1000..wake_idle_ms + 2000).contains(&timestamp);
+            controller.handle(event, paused);
+            assert_eq!(controller.state_name(), "idle", "woken at t={}", timestamp);
+        }
+        assert!(log.sizes.borrow().is_empty());
+    }
+
+    #[test]
+    fn trigger_between_events_uses_clock() {
+        let events = vec![motion(500, 300, 1000), InputEvent::Interrupted];
+        let (backend, log) = MockBackend::new(events);
+        let (mut controller, clock) = controller(backend);
+        let event = controller.next_event().unwrap();
+        controller.handle(event, false);
+
+        // Woken 500ms later: the trigger is placed at t = 1500
+        clock.advance(Duration::from_millis(500));
+        let event = controller.next_event().unwrap();
+        controller.trigger();
+        controller.handle(event, false);
+        assert_eq!(controller.position.2, 1500);
+        assert_eq!(*log.sizes.borrow(), [enlarged_size()]);
+
+        // The cooldown counts from the trigger, not the last motion
+        let cooldown = Duration::from_millis(Config::default().cooldown_ms as u64);
+        assert_eq!(clock.wake.get(), Some(Duration::from_millis(500) + cooldown));
+    }
+
+    #[test]
+    fn one_shot_trigger_restores_without_motion() {
+        let events = vec![InputEvent::Interrupted, InputEvent::Interrupted, InputEvent::Interrupted];
+        let (backend, log) = MockBackend::new(events);
+        let (mut controller, clock) = controller(backend);
+
+        let event = controller.next_event().unwrap();
+        controller.trigger();
+        controller.handle(event, false);
+        assert_eq!(controller.state_name(), "enlarged");
+
+        // The wakeup at the end of the cooldown begins restoring, and
+        // the one right after restores the cursor
+        clock.fire();
+        let event = controller.next_event().unwrap();
+        controller.handle(event, false);
+        assert_eq!(controller.state_name(), "restoring");
+        assert_eq!(clock.now(), Duration::from_millis(Config::default().cooldown_ms as u64));
+
+        clock.fire();
+        let event = controller.next_event().unwrap();
+        controller.handle(event, false);
+        assert_eq!(controller.state_name(), "idle");
+        assert_eq!(*log.sizes.borrow(), [enlarged_size(), ORIGINAL_SIZE]);
+    }
+
+    #[test]
+    fn cooldown_expires_across_clock_wrap() {
+        let start = u32::MAX - 100;
+        let hotkey = InputEvent::Hotkey { x: 10, y: 10, timestamp: start };
+        let events = vec![hotkey, InputEvent::Interrupted, InputEvent::Interrupted];
+        let (backend, log) = MockBackend::new(events);
+        let (mut controller, clock) = controller(backend);
+        let cooldown_ms = Config::default().cooldown_ms;
+
+        let event = controller.next_event().unwrap();
+        controller.handle(event, false);
+        assert_eq!(controller.state_name(), "enlarged");
+        assert_eq!(clock.wake.get(), Some(Duration::from_millis(cooldown_ms as u64)));
+
+        // Woken after the backend clock wrapped past zero
+        clock.fire();
+        let event = controller.next_event().unwrap();
+        controller.handle(event, false);
+        assert_eq!(controller.position.2, start.wrapping_add(cooldown_ms));
+        assert_eq!(controller.state_name(), "restoring");
+
+        clock.fire();
+        let event = controller.next_event().unwrap();
+        controller.handle(event, false);
+        assert_eq!(controller.state_name(), "idle");
+        assert_eq!(*log.sizes.borrow(), [enlarged_size(), ORIGINAL_SIZE]);
+        assert_eq!(controller.metrics().enlarged_seconds.sum(), cooldown_ms as f64 / 1000.0);
+    }
+
+    #[test]
+    fn shaking_moves_the_wakeup() {
+        let shaking = shake(0, 400);
+        let timestamps: Vec<u64> = shaking.iter().map(|event| match event {
+            InputEvent::Motion(motion) => motion.timestamp as u64,
+            _ => unreachable!(),
+        }).collect();
+        let (backend, _) = MockBackend::new([shaking, vec![InputEvent::Interrupted]].into_iter().flatten().collect());
+        let (mut controller, clock) = controller(backend);
+        let cooldown_ms = Config::default().cooldown_ms as u64;
+
+        // Keep the clock on the motion timestamps
+        let mut started = None;
+        for &timestamp in &timestamps {
+            clock.advance(Duration::from_millis(timestamp) - clock.now());
+            let event = controller.next_event().unwrap();
+            controller.handle(event, false);
+            if started.is_none() && controller.state_name() == "enlarged" {
+                started = Some(timestamp);
+            }
+        }
+        let last = *timestamps.last().unwrap();
+
+        // The wakeup set when the shake started comes too early: the
+        // cooldown runs from the last shaking event, so it is moved there
+        assert_eq!(clock.wake.get(), Some(Duration::from_millis(started.unwrap() + cooldown_ms)));
+        clock.fire();
+        let event = controller.next_event().unwrap();
+        controller.handle(event, false);
+        assert_eq!(controller.state_name(), "enlarged");
+        assert_eq!(clock.wake.get(), Some(Duration::from_millis(last + cooldown_ms)));
+    }
+
+    #[test]
+    fn hotkey_enlarges_while_paused() {
+        let hotkey = InputEvent::Hotkey { x: 10, y: 10, timestamp: 0 };
+        let (backend, log) = MockBackend::new([vec![hotkey], drift(16, 3000)].into_iter().flatten().collect());
+        let (mut controller, _) = controller(backend);
+
+        let states = run(&mut controller, true);
+
+        assert_eq!(transitions(&states), ["enlarged", "restoring", "idle"]);
+        assert_eq!(*log.sizes.borrow(), [enlarged_size(), ORIGINAL_SIZE]);
+    }
+
+    #[test]
+    fn metrics_count_shake_and_enlargement() {
+        let events: Vec<InputEvent> = [shake(0, 400), drift(400, 3000)].into_iter().flatten().collect();
+        let count = events.len() as u64;
+        let (backend, _) = MockBackend::new(events);
+        let (mut controller, _) = controller(backend);
+
+        let states = run(&mut controller, false);
+
+        let metrics = controller.metrics();
+        assert_eq!(metrics.motion_events, count);
+        assert_eq!(metrics.buffer_events.count(), count);
+        assert_eq!(metrics.handle_seconds.count(), count);
+        assert_eq!(metrics.shakes, 1);
+        assert_eq!(metrics.false_starts, 0);
+        assert_eq!(metrics.set_cursor_size_seconds.count(), 2);
+
+        // Enlarged from the shake until the event after the cooldown
+        let enlarged = first(&states, "enlarged").unwrap();
+        let restoring = states.iter().position(|(_, state)| *state == "restoring").unwrap();
+        let restored = states[restoring + 1].0;
+        assert_eq!(metrics.enlarged_seconds.count(), 1);
+        assert_eq!(metrics.enlarged_seconds.sum(), (restored - enlarged) as f64 / 1000.0);
+    }
+
+    #[test]
+    fn weak_shake_counts_as_false_start() {
+        // Two reversals where three are needed, then slow motion
+        let (backend, log) = MockBackend::new([shake(0, 48), drift(48, 1500)].into_iter().flatten().collect());
+        let (mut controller, _) = controller(backend);
+
+        let states = run(&mut controller, false);
+
+        assert_eq!(transitions(&states), ["idle"]);
+        assert!(log.sizes.borrow().is_empty());
+        assert_eq!(controller.metrics().shakes, 0);
+        assert_eq!(controller.metrics().false_starts, 1);
+    }
+}
@@ -0,0 +1,480 @@
+use std::collections::VecDeque;
+
+use crate::adaptive::Adaptive;
+use crate::backend::MotionEvent;
+use crate::config::Config;
+
+/// What put the cursor into the enlarged state. Each trigger has its
+/// own cooldown.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Trigger {
+    /// A shake, or a request to act as if there had been one
+    /// (hotkey, `ctl trigger`). Uses cooldown_ms.
+    Shake,
+
+    /// The first motion after the pointer sat still for wake_idle_ms.
+    /// Uses wake_cooldown_ms.
+    Wake,
+}
+
+/// State Machine pattern: tracks the current cursor state.
+///
+/// Transitions:
+///   Idle → Enlarged        (shake detected, or motion after idle)
+///   Enlarged → Enlarged    (still shaking, reset cooldown)
+///   Enlarged → Restoring   (no shake for the trigger's cooldown)
+///   Restoring → Idle       (cursor restored)
+pub enum CursorState {
+    /// Cursor is at its normal size. Waiting for a shake.
+    Idle,
+
+    /// Cursor has been enlarged. `since` is the timestamp (ms) of
+    /// the last detected shake (or the wake motion), used with the
+    /// trigger's cooldown to know when to start restoring.
+    Enlarged { since: u32, trigger: Trigger },
+
+    /// Cursor is being restored to normal size.
+    Restoring,
+}
+
+impl CursorState {
+    /// Lowercase name of the state, as reported by `ctl status`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            CursorState::Idle => "idle",
+            CursorState::Enlarged { .. } => "enlarged",
+            CursorState::Restoring => "restoring",
+        }
+    }
+}
+
+/// How far (px) the pointer must move back along an axis before that is
+/// a reversal. Hand tremor and pixel rounding wobble the pointer by a few
+/// pixels across any movement; a shake swings it tens of pixels.
+const MIN_STROKE_PX: i32 = 8;
+
+/// Direction of travel along one axis, with hysteresis: the direction
+/// only changes once the pointer has come MIN_STROKE_PX back from the
+/// furthest point it reached.
+struct Stroke {
+    /// +1, -1, or 0 until the pointer has moved MIN_STROKE_PX either way.
+    direction: i32,
+
+    /// Furthest position reached in `direction` (the start position
+    /// while the direction is unknown).
+    extreme: i32,
+}
+
+impl Stroke {
+    fn new(position: i16) -> Self {
+        Self { direction: 0, extreme: position as i32 }
+    }
+
+    /// Follow the pointer to `position`. True if that reversed the
+    /// direction of travel.
+    fn moved_to(&mut self, position: i16) -> bool {
+        let position = position as i32;
+        let travel = position - self.extreme;
+        if travel * self.direction > 0 {
+            // Further the same way
+            self.extreme = position;
+            return false;
+        }
+        if travel.abs() < MIN_STROKE_PX {
+            return false;
+        }
+
+        let reversed = self.direction != 0;
+        self.direction = travel.signum();
+        self.extreme = position;
+        reversed
+    }
+}
+
+/// Reversal and velocity statistics over the events currently in the
+/// detector's time window. These are the two quantities the shake
+/// thresholds are compared against.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MotionStats {
+    /// Direction reversals on the X axis.
+    pub x_reversals: u32,
+
+    /// Direction reversals on the Y axis.
+    pub y_reversals: u32,
+
+    /// Average velocity in pixels per second.
+    pub velocity: f64,
+
+    /// Time between the oldest and newest event in milliseconds.
+    pub time_span_ms: u32,
+}
+
+impl MotionStats {
+    /// Reversals on the axis with more of them (shakes happen on one axis).
+    pub fn reversals(&self) -> u32 {
+        self.x_reversals.max(self.y_reversals)
+    }
+
+    /// True if these statistics meet both shake thresholds:
+    /// reversals >= min_reversals AND velocity >= min_velocity.
+    pub fn is_shake(&self, thresholds: &Thresholds) -> bool {
+        self.time_span_ms > 0
+            && self.reversals() >= thresholds.min_reversals
+            && self.velocity >= thresholds.min_velocity
+    }
+
+    /// How close these statistics are to a shake, relative to the
+    /// thresholds: the smaller of reversals / min_reversals
+    /// and velocity / min_velocity. A score of 1.0 or more is a shake;
+    /// effects use it as an intensity.
+    pub fn score(&self, thresholds: &Thresholds) -> f64 {
+        if self.time_span_ms == 0 {
+            return 0.0;
+        }
+
+        let reversal_ratio = if thresholds.min_reversals == 0 {
+            f64::INFINITY
+        } else {
+            self.reversals() as f64 / thresholds.min_reversals as f64
+        };
+        let velocity_ratio = if thresholds.min_velocity <= 0.0 {
+            f64::INFINITY
+        } else {
+            self.velocity / thresholds.min_velocity
+        };
+
+        reversal_ratio.min(velocity_ratio)
+    }
+}
+
+/// The two values window statistics are compared against. Taken from
+/// the config, or learned from the user's movement in adaptive mode.
+#[derive(Clone, Copy, Debug)]
+pub struct Thresholds {
+    /// Direction reversals needed on one axis.
+    pub min_reversals: u32,
+
+    /// Average velocity needed, in pixels per second.
+    pub min_velocity: f64,
+}
+
+impl Thresholds {
+    /// The configured thresholds.
+    pub fn of(config: &Config) -> Self {
+        Self { min_reversals: config.min_reversals, min_velocity: config.min_velocity }
+    }
+}
+
+/// Analyzes mouse motion events to detect shake gestures.
+///
+/// Stores recent motion events in a ring buffer (VecDeque) and evicts
+/// entries older than the configured time window. On each new event,
+/// it counts direction reversals and calculates average velocity to
+/// determine if the user is shaking the cursor.
+pub struct ShakeDetector {
+    /// Ring Buffer pattern: rolling buffer of recent motion events.
+    /// Old events (outside the time window) are removed from the front.
+    /// New events are pushed to the back. This gives constant memory usage.
+    pub(crate) events: VecDeque<MotionEvent>,
+
+    /// State Machine: current cursor state and transitions.
+    pub(crate) state: CursorState,
+
+    /// Configuration thresholds that control detection sensitivity.
+    pub(crate) config: Config,
+
+    /// Milliseconds without motion before the newest event. Zero until
+    /// a second event arrives.
+    pub(crate) idle_ms: u32,
+
+    /// Statistics of the buffered events, computed once per event by
+    /// record_motion(), as every decision about the event reads them.
+    stats: MotionStats,
+
+    /// Learned baseline, used while adaptive_enabled is set.
+    adaptive: Adaptive,
+}
+
+impl ShakeDetector {
+    /// Create a new detector with an empty event buffer and idle state.
+    /// In adaptive mode it learns from scratch, in memory; see
+    /// set_adaptive() to keep the baseline between runs.
+    pub fn new(config: Config) -> Self {
+        Self {
+            events: VecDeque::new(),
+            state: CursorState::Idle,
+            config,
+            idle_ms: 0,
+            stats: MotionStats::default(),
+            adaptive: Adaptive::new(),
+        }
+    }
+
+    /// Continue from a learned baseline, e.g. one loaded with
+    /// Adaptive::load(). Only used while adaptive_enabled is set.
+    pub fn set_adaptive(&mut self, adaptive: Adaptive) {
+        self.adaptive = adaptive;
+    }
+
+    /// Replace the configuration, saving the adaptive baseline if
+    /// adaptive mode was switched off.
+    pub fn set_config(&mut self, config: Config) {
+        if self.config.adaptive_enabled && !config.adaptive_enabled {
+            self.adaptive.save();
+        }
+        self.config = config;
+    }
+
+    /// Save the adaptive baseline, if adaptive mode is on.
+    pub fn save_state(&self) {
+        if self.config.adaptive_enabled {
+            self.adaptive.save();
+        }
+    }
+
+    /// The learned baseline, if adaptive mode is on.
+    fn adaptive(&self) -> Option<&Adaptive> {
+        self.config.adaptive_enabled.then_some(&self.adaptive)
+    }
+
+    /// The learned baseline, if adaptive mode is on.
+    fn adaptive_mut(&mut self) -> Option<&mut Adaptive> {
+        self.config.adaptive_enabled.then_some(&mut self.adaptive)
+    }
+
+    /// Ring Buffer pattern: record a new motion event and evict stale ones.
+    ///
+    /// 1. Push the new event to the back of the buffer
+    /// 2. Pop events from the front while they are more than time_window
+    ///    older than the new event
+    ///
+    /// Ages are taken with wrapping arithmetic, so the window survives the
+    /// u32 millisecond clock wrapping after ~49 days, and a clock that
+    /// steps back makes the older events look ancient and flushes them.
+    /// This keeps memory bounded: no matter how long the daemon runs,
+    /// the buffer only holds events within the last time_window_ms.
+    pub fn record_motion(&mut self, event: MotionEvent) {
+        // The newest event is never evicted, so this is the previous motion
+        self.idle_ms = self
+            .events
+            .back()
+            .map_or(0, |last| event.timestamp.saturating_sub(last.timestamp));
+
+        self.events.push_back(event);
+
+        while let Some(front) = self.events.front() {
+            if event.timestamp.wrapping_sub(front.timestamp) > self.config.time_window_ms {
+                self.events.pop_front();
+            } else {
+                break;
+            }
+        }
+        self.stats = self.compute_stats();
+
+        // In adaptive mode, idle movement that isn't a shake is the
+        // user's baseline
+        let timestamp = self.events.back().map_or(0, |event| event.timestamp);
+        if let Some(adaptive) = self.adaptive()
+            && matches!(self.state, CursorState::Idle)
+            && adaptive.sample_due(timestamp, self.config.time_window_ms)
+        {
+            let stats = self.stats;
+            let thresholds = self.thresholds();
+            if stats.velocity > 0.0
+                && !stats.is_shake(&thresholds)
+                && let Some(adaptive) = self.adaptive_mut()
+            {
+                adaptive.sample(&stats, timestamp);
+            }
+        }
+    }
+
+    /// Note motion that isn't analyzed, while detection is paused. The
+    /// buffer restarts from it, so the pointer doesn't look idle for the
+    /// length of the pause, and motion from before the pause can't make
+    /// up a shake with motion after it.
+    pub fn ignore_motion(&mut self, event: MotionEvent) {
+        self.events.clear();
+        self.events.push_back(event);
+        self.idle_ms = 0;
+        self.stats = MotionStats::default();
+    }
+
+    /// Reversal and velocity statistics over the buffered events, as of
+    /// the latest record_motion().
+    pub fn stats(&self) -> MotionStats {
+        self.stats
+    }
+
+    /// Compute reversal and velocity statistics over the buffered events.
+    ///
+    /// Walks through consecutive event pairs to:
+    /// 1. Calculate direction (dx, dy) between each pair
+    /// 2. Count direction reversals on X and Y axes independently, each
+    ///    one only once the pointer has come back MIN_STROKE_PX
+    /// 3. Sum the total distance traveled
+    /// 4. Compute average velocity over the time span
+    ///
+    /// Returns all-zero statistics when fewer than 3 events are buffered
+    /// (at least 3 are needed to detect 1 reversal) or when they all share
+    /// the same timestamp.
+    fn compute_stats(&self) -> MotionStats {
+        let mut stats = MotionStats::default();
+
+        if self.events.len() < 3 {
+            return stats;
+        }
+
+        let mut total_distance: f64 = 0.0;
+        let first = &self.events[0];
+        let mut x_stroke = Stroke::new(first.x);
+        let mut y_stroke = Stroke::new(first.y);
+
+        for event_index in 1..self.events.len() {
+            let prev = &self.events[event_index - 1];
+            let curr = &self.events[event_index];
+
+            // Widened: the difference of two i16 coordinates overflows i16
+            let dx = curr.x as i32 - prev.x as i32;
+            let dy = curr.y as i32 - prev.y as i32;
+
+            // Euclidean distance between consecutive events
+            // <Remove by myself the usage of "as">
+            let distance = ((dx as f64).powi(2) + (dy as f64).powi(2)).sqrt();
+            total_distance += distance;
+
+            if x_stroke.moved_to(curr.x) {
+                stats.x_reversals += 1;
+            }
+            if y_stroke.moved_to(curr.y) {
+                stats.y_reversals += 1;
+            }
+        }
+
+        // Time span between oldest and newest event
+        let first_time = self.events.front().unwrap().timestamp;
+        let last_time = self.events.back().unwrap().timestamp;
+        // Wrapping: a clock that wrapped or went backwards must not panic
+        stats.time_span_ms = last_time.wrapping_sub(first_time);
+
+        if stats.time_span_ms == 0 {
+            stats.x_reversals = 0;
+            stats.y_reversals = 0;
+            return stats;
+        }
+
+        // Average velocity in pixels per second
+        stats.velocity = (total_distance / stats.time_span_ms as f64) * 1000.0;
+
+        stats
+    }
+
+    /// How close the buffered motion is to a shake, relative to the
+    /// thresholds in effect; see MotionStats::score().
+    pub fn score(&self) -> f64 {
+        self.stats.score(&self.thresholds())
+    }
+
+    /// Analyze the buffered events to determine if a shake is occurring.
+    ///
+    /// Returns true when: reversals >= min_reversals AND velocity >= min_velocity,
+    /// using the thresholds in effect
+    pub fn is_shaking(&self) -> bool {
+        self.stats.is_shake(&self.thresholds())
+    }
+
+    /// The thresholds in effect: the configured ones, or in adaptive
+    /// mode ones relative to the user's baseline.
+    pub fn thresholds(&self) -> Thresholds {
+        match self.adaptive() {
+            Some(adaptive) => adaptive.thresholds(&self.config),
+            None => Thresholds::of(&self.config),
+        }
+    }
+
+    /// True if the wake trigger is enabled and the newest event is the
+    /// first motion after the pointer sat still for wake_idle_ms.
+    pub fn is_waking(&self) -> bool {
+        self.config.wake_enabled && self.idle_ms >= self.config.wake_idle_ms
+    }
+
+    /// How long the cursor stays enlarged after `trigger` stops firing.
+    pub fn cooldown_ms(&self, trigger: Trigger) -> u32 {
+        match (trigger, self.adaptive()) {
+            (Trigger::Shake, Some(adaptive)) => adaptive.cooldown_ms(self.config.cooldown_ms),
+            (Trigger::Shake, None) => self.config.cooldown_ms,
+            (Trigger::Wake, _) => self.config.wake_cooldown_ms,
+        }
+    }
+
+    /// Learning signal for adaptive mode: a shake started the effects.
+    pub fn shake_started(&mut self, timestamp: u32) {
+        if let Some(adaptive) = self.adaptive_mut() {
+            adaptive.shake_started(timestamp);
+        }
+    }
+
+    /// Learning signal for adaptive mode: the user dismissed the effects.
+    pub fn dismissed(&mut self, timestamp: u32) {
+        if let Some(adaptive) = self.adaptive_mut() {
+            adaptive.dismissed(timestamp);
+        }
+    }
+
+    /// Learning signal for adaptive mode: the cooldown ran out.
+    pub fn restored(&mut self, timestamp: u32) {
+        if let Some(adaptive) = self.adaptive_mut() {
+            adaptive.restored(timestamp);
+        }
+    }
+}
+
+/// Statistics of the detector's window after each of `events`, as a
+/// detector with the given time window sees them. They depend only on
+/// the time window, not on the thresholds, so offline tools compute them
+/// once and compare them against as many thresholds as they like.
+pub fn window_stats(events: &[MotionEvent], time_window_ms: u32) -> Vec<MotionStats> {
+    let mut detector = ShakeDetector::new(Config { time_window_ms, ..Config::default() });
+    events
+        .iter()
+        .map(|event| {
+            detector.record_motion(*event);
+            detector.stats()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn motion(x: i16, timestamp: u32) -> MotionEvent {
+        MotionEvent { x, y: 0, timestamp, device: 0 }
+    }
+
+    #[test]
+    fn window_survives_clock_wrap() {
+        let mut detector = ShakeDetector::new(Config::default());
+        let window = detector.config.time_window_ms;
+        let start = u32::MAX - window / 2;
+        for step in 0..=window / 10 {
+            detector.record_motion(motion(step as i16, start.wrapping_add(step * 10)));
+        }
+
+        assert_eq!(detector.events.len() as u32, window / 10 + 1);
+        assert!(detector.stats().time_span_ms <= window);
+    }
+
+    #[test]
+    fn backwards_step_flushes_buffer() {
+        let mut detector = ShakeDetector::new(Config::default());
+        for step in 0..10 {
+            detector.record_motion(motion(step, 10_000 + step as u32 * 16));
+        }
+
+        detector.record_motion(motion(0, 5_000));
+
+        assert_eq!(detector.events.len(), 1);
+        assert_eq!(detector.stats().time_span_ms, 0);
+    }
+}
@@ -0,0 +1,134 @@
+use crate::backend::DisplayBackend;
+
+/// A visual response that shows the user where the pointer is.
+///
+/// Strategy pattern, like DisplayBackend: the state machine calls these
+/// three methods without knowing whether the effect resizes the cursor or
+/// draws on screen. To add a new effect, implement this trait and return
+/// it from the function the Controller builds its EffectChain with —
+/// detection and the state machine don't change.
+///
+/// The backend is passed in rather than owned, so effects that work
+/// through the display protocol (like resizing) share the daemon's
+/// connection, while overlay effects can ignore it.
+pub trait CursorEffect {
+    /// Short name used in log messages.
+    fn name(&self) -> &'static str;
+
+    /// Begin the effect at pointer position (x, y). Called once when a
+    /// shake is detected.
+    fn start(&mut self, backend: &mut dyn DisplayBackend, x: i16, y: i16) -> Result<(), String>;
+
+    /// Follow the pointer while the effect is active. `intensity` is the
+    /// detector score clamped to 0.0..=1.0 (1.0 while still shaking) and
+    /// `elapsed_ms` is the time since start().
+    fn update(
+        &mut self,
+        backend: &mut dyn DisplayBackend,
+        x: i16,
+        y: i16,
+        intensity: f64,
+        elapsed_ms: u32,
+    ) -> Result<(), String>;
+
+    /// End the effect and undo any change it made.
+    fn stop(&mut self, backend: &mut dyn DisplayBackend) -> Result<(), String>;
+}
+
+/// Enlarges the cursor through the backend, then restores the original size.
+pub struct ResizeEffect {
+    /// Cursor size in pixels while the effect is active.
+    enlarged_size: u32,
+}
+
+impl ResizeEffect {
+    /// Enlarge the cursor to `enlarged_size` pixels.
+    pub fn new(enlarged_size: u32) -> Self {
+        Self { enlarged_size }
+    }
+}
+
+impl CursorEffect for ResizeEffect {
+    fn name(&self) -> &'static str {
+        "enlarge"
+    }
+
+    fn start(&mut self, backend: &mut dyn DisplayBackend, _x: i16, _y: i16) -> Result<(), String> {
+        backend.set_cursor_size(self.enlarged_size)
+    }
+
+    /// Reloading the cursor on every motion event would be expensive and
+    /// flicker, so resizing has nothing to update.
+    fn update(
+        &mut self,
+        _backend: &mut dyn DisplayBackend,
+        _x: i16,
+        _y: i16,
+        _intensity: f64,
+        _elapsed_ms: u32,
+    ) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn stop(&mut self, backend: &mut dyn DisplayBackend) -> Result<(), String> {
+        let original = backend.original_cursor_size();
+        backend.set_cursor_size(original)
+    }
+}
+
+/// Runs several effects together as one.
+///
+/// Effects start in configured order and stop in reverse order. A failing
+/// effect is logged and skipped so the others still run.
+pub struct EffectChain {
+    effects: Vec<Box<dyn CursorEffect>>,
+
+    /// Timestamp (ms) of the start() call, for elapsed time in update().
+    started_at: u32,
+}
+
+impl EffectChain {
+    /// Chain `effects`, in the order they start.
+    pub fn new(effects: Vec<Box<dyn CursorEffect>>) -> Self {
+        Self { effects, started_at: 0 }
+    }
+
+    /// Start every effect at (x, y). `timestamp` is the backend time (ms)
+    /// that update() measures elapsed time from.
+    pub fn start(&mut self, backend: &mut dyn DisplayBackend, x: i16, y: i16, timestamp: u32) {
+        self.started_at = timestamp;
+        for effect in &mut self.effects {
+            if let Err(err) = effect.start(backend, x, y) {
+                log::error!("Failed to start {} effect: {}", effect.name(), err);
+            }
+        }
+    }
+
+    /// Move every effect to (x, y). `intensity` is the detector score,
+    /// clamped to 0.0..=1.0 here.
+    pub fn update(
+        &mut self,
+        backend: &mut dyn DisplayBackend,
+        x: i16,
+        y: i16,
+        intensity: f64,
+        timestamp: u32,
+    ) {
+        let elapsed_ms = timestamp.wrapping_sub(self.started_at);
+        let intensity = intensity.clamp(0.0, 1.0);
+        for effect in &mut self.effects {
+            if let Err(err) = effect.update(backend, x, y, intensity, elapsed_ms) {
+                log::error!("Failed to update {} effect: {}", effect.name(), err);
+            }
+        }
+    }
+
+    /// Stop every effect, last started first.
+    pub fn stop(&mut self, backend: &mut dyn DisplayBackend) {
+        for effect in self.effects.iter_mut().rev() {
+            if let Err(err) = effect.stop(backend) {
+                log::error!("Failed to stop {} effect: {}", effect.name(), err);
+            }
+        }
+    }
+}
@@ -0,0 +1,404 @@
+use std::f64::consts::TAU;
+use std::fmt;
+
+use crate::backend::MotionEvent;
+
+/// Frequency band (Hz) of the jitter. Hand tremor is a smooth wobble
+/// at a few Hz, not a new random offset at every sensor reading.
+const TREMOR_HZ: (f64, f64) = (4.0, 12.0);
+
+/// Sinusoids summed per axis to make the jitter.
+const TREMOR_WAVES: usize = 3;
+
+/// Where generated motion starts: the middle of a 1920x1080 screen.
+const START: (f64, f64) = (960.0, 540.0);
+
+/// Direction of a shake.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Axis {
+    Horizontal,
+    Vertical,
+    Diagonal,
+}
+
+impl Axis {
+    /// Parse an axis by name, as in a gesture spec.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "horizontal" => Ok(Axis::Horizontal),
+            "vertical" => Ok(Axis::Vertical),
+            "diagonal" => Ok(Axis::Diagonal),
+            other => Err(format!("Unknown axis '{}' (expected horizontal, vertical or diagonal)", other)),
+        }
+    }
+
+    /// Name of the axis, as in a gesture spec.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Axis::Horizontal => "horizontal",
+            Axis::Vertical => "vertical",
+            Axis::Diagonal => "diagonal",
+        }
+    }
+
+    /// Unit vector along the axis, in screen coordinates.
+    fn direction(&self) -> (f64, f64) {
+        match self {
+            Axis::Horizontal => (1.0, 0.0),
+            Axis::Vertical => (0.0, 1.0),
+            Axis::Diagonal => (0.5f64.sqrt(), 0.5f64.sqrt()),
+        }
+    }
+}
+
+/// One pointer movement to synthesize. Each starts where the previous
+/// one ended.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Gesture {
+    /// A straight movement of `length` px, `angle` degrees clockwise from
+    /// rightwards, accelerating and decelerating like a hand does
+    /// (minimum-jerk profile).
+    Swipe { length: f64, angle: f64, duration_ms: u32 },
+
+    /// Circling with the given radius (px), one turn per `period_ms`.
+    Circle { radius: f64, period_ms: u32, duration_ms: u32 },
+
+    /// Oscillating along `axis` at `frequency` Hz, `amplitude` px either
+    /// side of the start position.
+    Shake { axis: Axis, frequency: f64, amplitude: f64, duration_ms: u32 },
+
+    /// The pointer at rest: no events.
+    Pause { duration_ms: u32 },
+}
+
+impl Gesture {
+    /// Parse a gesture given as `KIND[:KEY=VALUE,...]`, e.g.
+    /// `shake:axis=vertical,frequency=6,amplitude=80,duration=1500`.
+    /// Keys left out take their defaults.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let (kind, params) = spec.split_once(':').unwrap_or((spec, ""));
+        let mut gesture = match kind {
+            "swipe" => Gesture::Swipe { length: 600.0, angle: 0.0, duration_ms: 300 },
+            "circle" => Gesture::Circle { radius: 100.0, period_ms: 500, duration_ms: 1000 },
+            "shake" => Gesture::Shake { axis: Axis::Horizontal, frequency: 6.0, amplitude: 60.0, duration_ms: 1000 },
+            "pause" => Gesture::Pause { duration_ms: 500 },
+            other => return Err(format!("Unknown gesture '{}' (expected swipe, circle, shake or pause)", other)),
+        };
+
+        for param in params.split(',').filter(|param| !param.is_empty()) {
+            let (key, value) = param.split_once('=')
+                .ok_or_else(|| format!("Invalid gesture parameter '{}' (expected KEY=VALUE)", param))?;
+            let number = || -> Result<f64, String> {
+                value.parse().ok().filter(|number: &f64| number.is_finite() && *number >= 0.0)
+                    .ok_or_else(|| format!("Invalid value '{}' for {}", value, key))
+            };
+            let millis = || -> Result<u32, String> {
+                value.parse().map_err(|_| format!("Invalid value '{}' for {}", value, key))
+            };
+            match (&mut gesture, key) {
+                (Gesture::Swipe { duration_ms, .. }, "duration")
+                | (Gesture::Circle { duration_ms, .. }, "duration")
+                | (Gesture::Shake { duration_ms, .. }, "duration")
+                | (Gesture::Pause { duration_ms }, "duration") => *duration_ms = millis()?,
+                (Gesture::Swipe { length, .. }, "length") => *length = number()?,
+                (Gesture::Swipe { angle, .. }, "angle") => {
+                    *angle = value.parse().map_err(|_| format!("Invalid value '{}' for angle", value))?;
+                }
+                (Gesture::Circle { radius, .. }, "radius") => *radius = number()?,
+                (Gesture::Circle { period_ms, .. }, "period") => *period_ms = millis()?.max(1),
+                (Gesture::Shake { axis, .. }, "axis") => *axis = Axis::parse(value)?,
+                (Gesture::Shake { frequency, .. }, "frequency") => *frequency = number()?,
+                (Gesture::Shake { amplitude, .. }, "amplitude") => *amplitude = number()?,
+                _ => return Err(format!("Unknown parameter '{}' for {}", key, kind)),
+            }
+        }
+        Ok(gesture)
+    }
+
+    /// How long the gesture lasts.
+    pub fn duration_ms(&self) -> u32 {
+        match *self {
+            Gesture::Swipe { duration_ms, .. }
+            | Gesture::Circle { duration_ms, .. }
+            | Gesture::Shake { duration_ms, .. }
+            | Gesture::Pause { duration_ms } => duration_ms,
+        }
+    }
+
+    /// Average speed in px/s, what the detector's velocity threshold is
+    /// compared against.
+    pub fn speed(&self) -> f64 {
+        match *self {
+            Gesture::Swipe { length, duration_ms, .. } => length * 1000.0 / duration_ms.max(1) as f64,
+            Gesture::Circle { radius, period_ms, .. } => TAU * radius * 1000.0 / period_ms as f64,
+            Gesture::Shake { frequency, amplitude, .. } => 4.0 * amplitude * frequency,
+            Gesture::Pause { .. } => 0.0,
+        }
+    }
+
+    /// Position relative to where the gesture started, `elapsed` ms in.
+    fn offset(&self, elapsed: f64) -> (f64, f64) {
+        match *self {
+            Gesture::Swipe { length, angle, duration_ms } => {
+                let progress = (elapsed / duration_ms.max(1) as f64).min(1.0);
+                let eased = progress.powi(3) * (10.0 - 15.0 * progress + 6.0 * progress.powi(2));
+                let angle = angle.to_radians();
+                (length * eased * angle.cos(), length * eased * angle.sin())
+            }
+            Gesture::Circle { radius, period_ms, .. } => {
+                // Centred to the right of the start position
+                let turned = TAU * elapsed / period_ms as f64;
+                (radius * (1.0 - turned.cos()), -radius * turned.sin())
+            }
+            Gesture::Shake { axis, frequency, amplitude, .. } => {
+                let swing = amplitude * (TAU * frequency * elapsed / 1000.0).sin();
+                let (dx, dy) = axis.direction();
+                (swing * dx, swing * dy)
+            }
+            Gesture::Pause { .. } => (0.0, 0.0),
+        }
+    }
+}
+
+impl fmt::Display for Gesture {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Gesture::Swipe { length, angle, duration_ms } => {
+                write!(f, "swipe {:.0}px at {:.0}° over {}ms", length, angle, duration_ms)
+            }
+            Gesture::Circle { radius, period_ms, duration_ms } => {
+                write!(f, "circle r={:.0}px, {}ms per turn, for {}ms", radius, period_ms, duration_ms)
+            }
+            Gesture::Shake { axis, frequency, amplitude, duration_ms } => {
+                write!(f, "{} shake {}Hz ±{:.0}px for {}ms", axis.name(), frequency, amplitude, duration_ms)
+            }
+            Gesture::Pause { duration_ms } => write!(f, "pause {}ms", duration_ms),
+        }
+    }
+}
+
+/// Synthesizes the MotionEvent stream a mouse would produce for a
+/// sequence of gestures.
+///
+/// Positions are sampled at `rate_hz`, jittered by `noise` px (standard
+/// deviation) of tremor, rounded to pixels, and each one is lost with
+/// probability `drop_rate`. Like the display server, it reports nothing
+/// when the rounded position doesn't change. Randomness comes from a seeded
+/// generator, so a seed always gives the same events.
+pub struct Generator {
+    /// Events per second the simulated mouse reports, 125 to 8000 Hz
+    /// for real hardware.
+    pub rate_hz: f64,
+
+    /// Standard deviation of the position jitter, in px.
+    pub noise: f64,
+
+    /// Fraction of events lost on the way, 0 to 1.
+    pub drop_rate: f64,
+
+    rng: fastrand::Rng,
+
+    /// Where the pointer is, without jitter.
+    position: (f64, f64),
+
+    /// Tremor waves per axis: (frequency Hz, phase), each contributing
+    /// an equal share of the jitter.
+    tremor: [[(f64, f64); TREMOR_WAVES]; 2],
+
+    /// Time (ms) the next gesture starts at.
+    time_ms: f64,
+
+    /// Time (ms) of the next sample.
+    next_sample_ms: f64,
+
+    /// Last reported position.
+    reported: Option<(i16, i16)>,
+}
+
+impl Generator {
+    /// A 1000 Hz mouse without noise or drops, at the middle of the
+    /// screen, at time zero.
+    pub fn new(seed: u64) -> Self {
+        let mut rng = fastrand::Rng::with_seed(seed);
+        let tremor = [(); 2].map(|()| {
+            [(); TREMOR_WAVES].map(|()| {
+                let frequency = TREMOR_HZ.0 + (TREMOR_HZ.1 - TREMOR_HZ.0) * rng.f64();
+                (frequency, TAU * rng.f64())
+            })
+        });
+        Self {
+            rate_hz: 1000.0,
+            noise: 0.0,
+            drop_rate: 0.0,
+            rng,
+            position: START,
+            tremor,
+            time_ms: 0.0,
+            next_sample_ms: 0.0,
+            reported: None,
+        }
+    }
+
+    /// Time (ms) the next gesture starts at: the end of the last one.
+    pub fn time_ms(&self) -> u32 {
+        self.time_ms as u32
+    }
+
+    /// The events for one gesture, continuing from the previous one.
+    pub fn generate(&mut self, gesture: &Gesture) -> Vec<MotionEvent> {
+        let start = self.time_ms;
+        let end = start + gesture.duration_ms() as f64;
+        let origin = self.position;
+        let interval = 1000.0 / self.rate_hz.max(1.0);
+
+        let mut events = Vec::new();
+        if matches!(gesture, Gesture::Pause { .. }) {
+            self.next_sample_ms = end;
+        }
+        while self.next_sample_ms < end {
+            let now = self.next_sample_ms;
+            self.next_sample_ms += interval;
+
+            let (dx, dy) = gesture.offset(now - start);
+            self.position = (origin.0 + dx, origin.1 + dy);
+            let (jitter_x, jitter_y) = self.jitter(now);
+
+            let x = (self.position.0 + jitter_x).round().clamp(i16::MIN as f64, i16::MAX as f64) as i16;
+            let y = (self.position.1 + jitter_y).round().clamp(i16::MIN as f64, i16::MAX as f64) as i16;
+            if self.reported == Some((x, y)) || self.rng.f64() < self.drop_rate {
+                continue;
+            }
+            self.reported = Some((x, y));
+            events.push(MotionEvent { x, y, timestamp: now as u32, device: 2 });
+        }
+
+        let (dx, dy) = gesture.offset(end - start);
+        self.position = (origin.0 + dx, origin.1 + dy);
+        self.time_ms = end;
+        events
+    }
+
+    /// Jitter offset at time `now` (ms): the tremor waves, scaled so
+    /// each axis has a standard deviation of `noise`.
+    fn jitter(&self, now: f64) -> (f64, f64) {
+        if self.noise <= 0.0 {
+            return (0.0, 0.0);
+        }
+        // A sinusoid of amplitude a has a variance of a²/2
+        let amplitude = self.noise * (2.0 / TREMOR_WAVES as f64).sqrt();
+        let [x, y] = self.tremor.map(|waves| {
+            waves
+                .iter()
+                .map(|(frequency, phase)| amplitude * (TAU * frequency * now / 1000.0 + phase).sin())
+                .sum()
+        });
+        (x, y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::config::Config;
+    use crate::detector::ShakeDetector;
+
+    /// Event rates of real mice, from a basic USB mouse to a gaming one.
+    const RATES: &[f64] = &[125.0, 250.0, 500.0, 1000.0, 2000.0, 4000.0, 8000.0];
+
+    fn axis() -> impl Strategy<Value = Axis> {
+        prop_oneof![Just(Axis::Horizontal), Just(Axis::Vertical), Just(Axis::Diagonal)]
+    }
+
+    fn generator(seed: u64, rate: usize, noise: f64, drop_rate: f64) -> Generator {
+        let mut generator = Generator::new(seed);
+        generator.rate_hz = RATES[rate];
+        generator.noise = noise;
+        generator.drop_rate = drop_rate;
+        generator
+    }
+
+    /// Run events through a detector with the default thresholds,
+    /// returning whether it saw a shake at any of them.
+    fn detects_shake(events: &[MotionEvent]) -> bool {
+        let mut detector = ShakeDetector::new(Config::default());
+        events.iter().any(|event| {
+            detector.record_motion(*event);
+            detector.is_shaking()
+        })
+    }
+
+    /// Tremor across a fast swipe wobbles the other axis back and forth
+    /// by a pixel or two; those wobbles are not reversals.
+    #[test]
+    fn tremor_across_swipe_is_not_a_shake() {
+        for seed in 0..200 {
+            let mut generator = generator(seed, RATES.len() - 1, 1.0, 0.0);
+            let events = generator.generate(&Gesture::Swipe { length: 1500.0, angle: 0.0, duration_ms: 1000 });
+            assert!(!detects_shake(&events), "swipe with tremor detected as a shake (seed {})", seed);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn shakes_above_threshold_are_detected(
+            seed: u64,
+            rate in 0..RATES.len(),
+            noise in 0.0..1.0,
+            drop_rate in 0.0..0.3,
+            axis in axis(),
+            frequency in 4.0..12.0,
+            amplitude in 35.0..300.0,
+        ) {
+            // At the slowest, 4 * 35px * 4Hz = 560 px/s: above min_velocity
+            let mut generator = generator(seed, rate, noise, drop_rate);
+            let shake = Gesture::Shake { axis, frequency, amplitude, duration_ms: 1000 };
+            let events = generator.generate(&shake);
+            prop_assert!(detects_shake(&events), "{} not detected", shake);
+        }
+
+        #[test]
+        fn straight_motion_never_triggers(
+            seed: u64,
+            rate in 0..RATES.len(),
+            noise in 0.0..1.0,
+            drop_rate in 0.0..0.3,
+            length in 0.0..3000.0,
+            angle in 0.0..360.0,
+            duration_ms in 50u32..2000,
+        ) {
+            let mut generator = generator(seed, rate, noise, drop_rate);
+            let swipe = Gesture::Swipe { length, angle, duration_ms };
+            let events = generator.generate(&swipe);
+            prop_assert!(!detects_shake(&events), "{} detected as a shake", swipe);
+        }
+
+        #[test]
+        fn slow_motion_never_triggers(
+            seed: u64,
+            rate in 0..RATES.len(),
+            noise in 0.0..1.0,
+            drop_rate in 0.0..0.3,
+            axis in axis(),
+            frequency in 1.0..12.0,
+            radius in 5.0..200.0,
+            speed in 0.0f64..0.5,
+        ) {
+            // Shaking and circling, but at most half of min_velocity: pixel
+            // steps make diagonal motion measure up to √2 times faster
+            let speed = speed * Config::default().min_velocity;
+            let amplitude = speed / (4.0 * frequency);
+            let period_ms = (TAU * radius * 1000.0 / speed.max(1.0)) as u32;
+
+            let mut generator = generator(seed, rate, noise, drop_rate);
+            for gesture in [
+                Gesture::Shake { axis, frequency, amplitude, duration_ms: 1500 },
+                Gesture::Circle { radius, period_ms: period_ms.max(1), duration_ms: 1500 },
+            ] {
+                let events = generator.generate(&gesture);
+                prop_assert!(!detects_shake(&events), "{} detected as a shake", gesture);
+            }
+        }
+    }
+}
@@ -0,0 +1,69 @@
+//! Shake detection and the cursor state machine of shake-cursor, without
+//! any display server code.
+//!
+//! The daemon is one user of this crate: it adds X11 and Wayland
+//! backends, screen overlays and the control front ends. Other programs
+//! (a kiosk shell, a compositor, an accessibility tool) can embed the same
+//! detection with their own source of pointer motion:
+//!
+//! - [`ShakeDetector`] turns a stream of [`MotionEvent`]s into a shake
+//!   score and a yes/no decision. This is all that is needed to react to
+//!   shakes in your own way.
+//! - [`Controller`] adds the state machine around it: it pulls events from
+//!   a [`DisplayBackend`], starts an [`EffectChain`](effect::EffectChain)
+//!   on a shake and restores the cursor after the cooldown.
+//! - [`Config`] holds the thresholds and effect settings, and reads the
+//!   daemon's config file format.
+//!
+//! ```
+//! use shake_cursor_core::{Config, MotionEvent, ShakeDetector};
+//!
+//! let mut detector = ShakeDetector::new(Config::default());
+//! for step in 0..40u32 {
+//!     let x = if step % 2 == 0 { 400 } else { 500 };
+//!     detector.record_motion(MotionEvent { x, y: 300, timestamp: step * 16, device: 0 });
+//! }
+//! assert!(detector.is_shaking());
+//! ```
+//!
+//! Timestamps are milliseconds on any monotonic clock, as long as one
+//! source uses the same clock throughout. Adaptive mode
+//! (`adaptive_enabled`) learns in memory. To keep its baseline between
+//! runs, load it from a file of your own with
+//! [`Adaptive::load`](adaptive::Adaptive::load) and hand it to the
+//! detector or Controller; nothing is read or written otherwise.
+//!
+//! The public modules are the stable API: while the crate is at 0.x, a
+//! breaking change to them comes with a new minor version. The
+//! `gesture` module, a generator of synthetic pointer motion for tests
+//! and simulations, is only built with the `gesture` feature.
+
+/// The learned baseline of adaptive mode.
+pub mod adaptive;
+/// Motion events and the DisplayBackend trait pointer sources implement.
+pub mod backend;
+/// Configuration and the config file format.
+pub mod config;
+/// The cursor state machine.
+pub mod controller;
+/// Shake detection over a window of motion events.
+pub mod detector;
+/// The locate effects started on a shake.
+pub mod effect;
+/// Synthetic pointer gestures, for testing detection. Needs the
+/// `gesture` feature.
+#[cfg(feature = "gesture")]
+pub mod gesture;
+/// Counters and histograms of what the Controller did.
+pub mod metrics;
+#[cfg(test)]
+mod mock_backend;
+#[cfg(test)]
+mod temp_dir;
+/// Recording motion and detection decisions as JSON Lines.
+pub mod trace;
+
+pub use backend::{DisplayBackend, InputEvent, MotionEvent, Waker};
+pub use config::Config;
+pub use controller::{Clock, Controller, SystemClock};
+pub use detector::{CursorState, ShakeDetector, Trigger};
@@ -0,0 +1,242 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::backend::{DisplayBackend, InputEvent, Waker};
+use crate::controller::Clock;
+
+/// Bucket upper bounds for how long the cursor stays enlarged (s).
+const ENLARGED_BUCKETS: &[f64] = &[0.5, 1.0, 2.0, 3.0, 5.0, 10.0, 30.0, 60.0];
+
+/// Bucket upper bounds for set_cursor_size() latency (s). Reloading a
+/// theme cursor takes a few milliseconds, scaling one somewhat longer.
+const SET_CURSOR_SIZE_BUCKETS: &[f64] = &[0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25];
+
+/// Bucket upper bounds for the events in the detector's buffer. A
+/// 500ms window holds 60 events at 125Hz and 4000 at 8000Hz.
+const BUFFER_BUCKETS: &[f64] = &[8.0, 16.0, 32.0, 64.0, 128.0, 256.0, 512.0, 1024.0, 2048.0, 4096.0];
+
+/// Bucket upper bounds for how long the Controller takes to handle one
+/// event (s). Usually microseconds; a cursor change adds its latency.
+const HANDLE_BUCKETS: &[f64] = &[0.00001, 0.000025, 0.00005, 0.0001, 0.00025, 0.0005, 0.001, 0.005, 0.025, 0.1];
+
+/// Distribution of observed values over fixed buckets, like a
+/// Prometheus histogram.
+#[derive(Clone, Debug)]
+pub struct Histogram {
+    /// Upper bounds of the buckets, ascending. Values above the last go
+    /// only into the implicit +Inf bucket.
+    bounds: &'static [f64],
+
+    /// Observations per bucket (not cumulative), with one extra for +Inf.
+    counts: Vec<u64>,
+
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    /// An empty histogram with the given bucket upper bounds.
+    pub fn new(bounds: &'static [f64]) -> Self {
+        Self { bounds, counts: vec![0; bounds.len() + 1], sum: 0.0, count: 0 }
+    }
+
+    /// Record one value.
+    pub fn observe(&mut self, value: f64) {
+        let bucket = self.bounds.iter().position(|&bound| value <= bound).unwrap_or(self.bounds.len());
+        self.counts[bucket] += 1;
+        self.sum += value;
+        self.count += 1;
+    }
+
+    /// Number of values recorded.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Sum of the values recorded.
+    pub fn sum(&self) -> f64 {
+        self.sum
+    }
+
+    /// (upper bound, values at or below it) for every bucket, ending with
+    /// +Inf, which holds count(). Cumulative, as Prometheus reports them.
+    pub fn buckets(&self) -> Vec<(f64, u64)> {
+        let bounds = self.bounds.iter().copied().chain([f64::INFINITY]);
+        bounds
+            .zip(&self.counts)
+            .scan(0, |total, (bound, count)| {
+                *total += count;
+                Some((bound, *total))
+            })
+            .collect()
+    }
+}
+
+/// What the Controller has done since it started, for monitoring.
+///
+/// Counters only ever grow. Times are in seconds, as Prometheus expects.
+#[derive(Clone, Debug)]
+pub struct Metrics {
+    /// Motion events handled, including those ignored while paused.
+    pub motion_events: u64,
+
+    /// Shakes that started the locate effects. Hotkey presses, wakes
+    /// and `ctl trigger` are not shakes.
+    pub shakes: u64,
+
+    /// Motion that got halfway to a shake (score 0.5) and then died down
+    /// without becoming one: shakes that were too weak for the
+    /// thresholds, or normal movement that came close to passing them.
+    pub false_starts: u64,
+
+    /// How long the locate effects ran each time, start to restore.
+    pub enlarged_seconds: Histogram,
+
+    /// How long each set_cursor_size() call to the backend took.
+    pub set_cursor_size_seconds: Histogram,
+
+    /// Events in the detector's buffer after each motion event.
+    pub buffer_events: Histogram,
+
+    /// How long each Controller::handle() call took.
+    pub handle_seconds: Histogram,
+}
+
+/// Metrics the Controller updates in place and other threads read, so
+/// the event loop never has to copy them.
+pub type SharedMetrics = Arc<Mutex<Metrics>>;
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            motion_events: 0,
+            shakes: 0,
+            false_starts: 0,
+            enlarged_seconds: Histogram::new(ENLARGED_BUCKETS),
+            set_cursor_size_seconds: Histogram::new(SET_CURSOR_SIZE_BUCKETS),
+            buffer_events: Histogram::new(BUFFER_BUCKETS),
+            handle_seconds: Histogram::new(HANDLE_BUCKETS),
+        }
+    }
+}
+
+impl Metrics {
+    /// The metrics in the Prometheus text exposition format, named
+    /// `shake_cursor_*`.
+    pub fn to_prometheus(&self) -> String {
+        let mut text = String::new();
+        let counters = [
+            ("motion_events_total", "Motion events handled.", self.motion_events),
+            ("shakes_total", "Shakes that started the locate effects.", self.shakes),
+            ("false_starts_total", "Motion that came halfway to a shake without becoming one.", self.false_starts),
+        ];
+        for (name, help, value) in counters {
+            let _ = writeln!(text, "# HELP shake_cursor_{} {}", name, help);
+            let _ = writeln!(text, "# TYPE shake_cursor_{} counter", name);
+            let _ = writeln!(text, "shake_cursor_{} {}", name, value);
+        }
+
+        let histograms = [
+            ("enlarged_seconds", "How long the locate effects ran.", &self.enlarged_seconds),
+            ("set_cursor_size_seconds", "Latency of changing the cursor size.", &self.set_cursor_size_seconds),
+            ("buffer_events", "Events in the detector's window.", &self.buffer_events),
+            ("handle_seconds", "Time taken to handle one event.", &self.handle_seconds),
+        ];
+        for (name, help, histogram) in histograms {
+            let _ = writeln!(text, "# HELP shake_cursor_{} {}", name, help);
+            let _ = writeln!(text, "# TYPE shake_cursor_{} histogram", name);
+            for (bound, count) in histogram.buckets() {
+                let bound = if bound.is_infinite() { "+Inf".to_string() } else { bound.to_string() };
+                let _ = writeln!(text, "shake_cursor_{}_bucket{{le=\"{}\"}} {}", name, bound, count);
+            }
+            let _ = writeln!(text, "shake_cursor_{}_sum {}", name, histogram.sum());
+            let _ = writeln!(text, "shake_cursor_{}_count {}", name, histogram.count());
+        }
+        text
+    }
+
+    /// Write the metrics to `path` for node_exporter's textfile
+    /// collector. Written to a temporary file first and renamed, so the
+    /// collector never reads half a file.
+    pub fn write_textfile(&self, path: &Path) -> Result<(), String> {
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        fs::write(&temporary, self.to_prometheus())
+            .map_err(|err| format!("Failed to write {}: {}", Path::new(&temporary).display(), err))?;
+        fs::rename(&temporary, path).map_err(|err| format!("Failed to replace {}: {}", path.display(), err))
+    }
+}
+
+/// Decorator around the Controller's backend, handed to the effects so
+/// set_cursor_size() is timed whichever effect calls it.
+pub(crate) struct TimedBackend<'a> {
+    pub backend: &'a mut dyn DisplayBackend,
+    pub clock: &'a dyn Clock,
+    pub metrics: &'a Mutex<Metrics>,
+}
+
+impl DisplayBackend for TimedBackend<'_> {
+    fn connect(&mut self) -> Result<(), String> {
+        self.backend.connect()
+    }
+
+    fn next_event(&mut self) -> Option<InputEvent> {
+        self.backend.next_event()
+    }
+
+    fn waker(&self) -> Waker {
+        self.backend.waker()
+    }
+
+    fn set_cursor_size(&mut self, size: u32) -> Result<(), String> {
+        let started = self.clock.now();
+        let result = self.backend.set_cursor_size(size);
+        let latency = self.clock.now().saturating_sub(started);
+        self.metrics.lock().unwrap().set_cursor_size_seconds.observe(latency.as_secs_f64());
+        result
+    }
+
+    fn original_cursor_size(&self) -> u32 {
+        self.backend.original_cursor_size()
+    }
+
+    fn disconnect(&mut self) {
+        self.backend.disconnect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histogram_buckets_are_cumulative() {
+        let mut histogram = Histogram::new(&[1.0, 2.0]);
+        for value in [0.5, 1.0, 1.5, 7.0] {
+            histogram.observe(value);
+        }
+        assert_eq!(histogram.buckets(), vec![(1.0, 2), (2.0, 3), (f64::INFINITY, 4)]);
+        assert_eq!(histogram.count(), 4);
+        assert_eq!(histogram.sum(), 10.0);
+    }
+
+    #[test]
+    fn prometheus_text_has_every_metric() {
+        let mut metrics = Metrics { motion_events: 42, shakes: 2, ..Metrics::default() };
+        metrics.enlarged_seconds.observe(2.5);
+
+        let text = metrics.to_prometheus();
+        assert!(text.contains("# TYPE shake_cursor_motion_events_total counter\nshake_cursor_motion_events_total 42\n"));
+        assert!(text.contains("shake_cursor_shakes_total 2\n"));
+        assert!(text.contains("shake_cursor_false_starts_total 0\n"));
+        assert!(text.contains("shake_cursor_enlarged_seconds_bucket{le=\"2\"} 0\n"));
+        assert!(text.contains("shake_cursor_enlarged_seconds_bucket{le=\"3\"} 1\n"));
+        assert!(text.contains("shake_cursor_enlarged_seconds_bucket{le=\"+Inf\"} 1\n"));
+        assert!(text.contains("shake_cursor_enlarged_seconds_sum 2.5\n"));
+        assert!(text.contains("shake_cursor_set_cursor_size_seconds_count 0\n"));
+        assert!(text.contains("# TYPE shake_cursor_buffer_events histogram\n"));
+        assert!(text.contains("shake_cursor_handle_seconds_count 0\n"));
+    }
+}
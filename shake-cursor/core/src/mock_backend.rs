@@ -0,0 +1,135 @@
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::backend::{DisplayBackend, InputEvent, MotionEvent, Waker};
+use crate::controller::Clock;
+
+/// Cursor size the mock reports as the user's own.
+pub const ORIGINAL_SIZE: u32 = 24;
+
+/// What a MockBackend was asked to do, shared with the test that
+/// handed the backend to a Controller.
+#[derive(Clone, Default)]
+pub struct MockLog {
+    /// Every set_cursor_size() call, in order.
+    pub sizes: Rc<RefCell<Vec<u32>>>,
+
+    /// True once disconnect() was called.
+    pub disconnected: Rc<Cell<bool>>,
+}
+
+/// In-memory backend for tests: yields scripted events, then reports a
+/// lost connection, and records cursor changes instead of making them.
+pub struct MockBackend {
+    events: VecDeque<InputEvent>,
+    log: MockLog,
+
+    /// Make set_cursor_size() fail, after recording the call.
+    fail: bool,
+}
+
+impl MockBackend {
+    pub fn new(events: Vec<InputEvent>) -> (Self, MockLog) {
+        let log = MockLog::default();
+        let backend = Self { events: events.into(), log: log.clone(), fail: false };
+        (backend, log)
+    }
+
+    /// A backend whose every cursor change fails.
+    pub fn failing(events: Vec<InputEvent>) -> (Self, MockLog) {
+        let (mut backend, log) = Self::new(events);
+        backend.fail = true;
+        (backend, log)
+    }
+}
+
+impl DisplayBackend for MockBackend {
+    fn connect(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn next_event(&mut self) -> Option<InputEvent> {
+        self.events.pop_front()
+    }
+
+    fn waker(&self) -> Waker {
+        Arc::new(|| {})
+    }
+
+    fn set_cursor_size(&mut self, size: u32) -> Result<(), String> {
+        self.log.sizes.borrow_mut().push(size);
+        if self.fail {
+            Err("mock failure".to_string())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn original_cursor_size(&self) -> u32 {
+        ORIGINAL_SIZE
+    }
+
+    fn disconnect(&mut self) {
+        self.log.disconnected.set(true);
+    }
+}
+
+/// A clock that only moves when told to. Wakeups are recorded, not made.
+#[derive(Clone, Default)]
+pub struct MockClock {
+    now: Rc<Cell<Duration>>,
+
+    /// Deadline of the latest wake_at().
+    pub wake: Rc<Cell<Option<Duration>>>,
+}
+
+impl MockClock {
+    pub fn advance(&self, by: Duration) {
+        self.now.set(self.now.get() + by);
+    }
+
+    /// Move the clock to the requested wakeup, as if it had fired.
+    pub fn fire(&self) {
+        let deadline = self.wake.take().expect("no wakeup requested");
+        self.now.set(self.now.get().max(deadline));
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Duration {
+        self.now.get()
+    }
+
+    fn wake_at(&self, deadline: Duration, _waker: Waker) {
+        self.wake.set(Some(deadline));
+    }
+}
+
+pub fn motion(x: i16, y: i16, timestamp: u32) -> InputEvent {
+    InputEvent::Motion(MotionEvent { x, y, timestamp, device: 0 })
+}
+
+/// A horizontal shake from `start` to `end` (ms): one event every 16ms,
+/// swinging 50px either side of x = 500, reversing on every event.
+pub fn shake(start: u32, end: u32) -> Vec<InputEvent> {
+    (start..end)
+        .step_by(16)
+        .enumerate()
+        .map(|(index, timestamp)| {
+            let x = if index % 2 == 0 { 450 } else { 550 };
+            motion(x, 300, timestamp)
+        })
+        .collect()
+}
+
+/// Slow straight motion from `start` to `end` (ms), one pixel per event.
+pub fn drift(start: u32, end: u32) -> Vec<InputEvent> {
+    (start..end)
+        .step_by(16)
+        .enumerate()
+        .map(|(index, timestamp)| motion(500 + index as i16, 300, timestamp))
+        .collect()
+}
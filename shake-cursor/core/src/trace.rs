@@ -0,0 +1,254 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Lines, Write};
+use std::path::Path;
+
+use serde_json::{json, Map, Value};
+
+use crate::backend::MotionEvent;
+use crate::config::Config;
+
+/// Version written to the header line; bumped on incompatible changes.
+const TRACE_VERSION: u64 = 1;
+
+/// Writes a motion trace: JSON Lines, one object per line.
+///
+/// The first line is a header with the configuration the detector ran
+/// with. Every motion event follows as one line with the detector's
+/// decision for it:
+///
+/// ```text
+/// {"config":{"cooldown_ms":"2000",...},"type":"header","version":1}
+/// {"device":11,"score":0.42,"shaking":false,"state":"idle","t":5120331,"type":"motion","x":812,"y":440}
+/// ```
+///
+/// Traces may also contain label lines marking intervals of deliberate
+/// shaking, `{"type":"label","start":5120200,"end":5121400}`.
+pub struct TraceWriter {
+    out: BufWriter<File>,
+}
+
+impl TraceWriter {
+    /// Create (or truncate) the trace file and write its header.
+    pub fn create(path: &Path, config: &Config) -> Result<Self, String> {
+        let file = File::create(path)
+            .map_err(|err| format!("Failed to create {}: {}", path.display(), err))?;
+        let mut writer = Self { out: BufWriter::new(file) };
+
+        let config: Map<String, Value> = config
+            .entries()
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), Value::String(value)))
+            .collect();
+        writer.write_line(&json!({ "type": "header", "version": TRACE_VERSION, "config": config }));
+        Ok(writer)
+    }
+
+    /// Record one motion event and what the detector made of it.
+    /// `state` is the CursorState after the event was processed.
+    pub fn write_motion(&mut self, event: &MotionEvent, score: f64, shaking: bool, state: &str) {
+        self.write_line(&json!({
+            "type": "motion",
+            "x": event.x,
+            "y": event.y,
+            "t": event.timestamp,
+            "device": event.device,
+            "score": (score * 1000.0).round() / 1000.0,
+            "shaking": shaking,
+            "state": state,
+        }));
+    }
+
+    /// Mark `start` to `end` (backend timestamps) as deliberate shaking.
+    pub fn write_label(&mut self, start: u32, end: u32) {
+        self.write_line(&json!({ "type": "label", "start": start, "end": end }));
+    }
+
+    fn write_line(&mut self, line: &Value) {
+        if let Err(err) = writeln!(self.out, "{}", line) {
+            log::warn!("Failed to write trace: {}", err);
+        }
+    }
+
+    /// Write out buffered lines; called at shutdown.
+    pub fn flush(&mut self) {
+        if let Err(err) = self.out.flush() {
+            log::warn!("Failed to write trace: {}", err);
+        }
+    }
+}
+
+/// One line of a trace.
+pub enum TraceRecord {
+    /// The header: configuration as (key, value) pairs for Config::set().
+    Header { config: Vec<(String, String)> },
+
+    /// A motion event.
+    Motion { event: MotionEvent },
+
+    /// A labelled interval: the user was deliberately shaking from
+    /// `start` to `end` (backend timestamps, ms). Added by hand or by
+    /// `calibrate`, and used by `analyze` to score detections.
+    Label { start: u32, end: u32 },
+}
+
+/// Reads a trace line by line, so long recordings don't have to fit in
+/// memory. Lines of unknown type are skipped, so newer traces with extra
+/// records stay readable.
+pub struct TraceReader {
+    path: String,
+    lines: Lines<BufReader<File>>,
+    line_number: usize,
+}
+
+impl TraceReader {
+    /// Open the trace at `path` for reading.
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let file = File::open(path)
+            .map_err(|err| format!("Failed to open {}: {}", path.display(), err))?;
+        Ok(Self {
+            path: path.display().to_string(),
+            lines: BufReader::new(file).lines(),
+            line_number: 0,
+        })
+    }
+
+    fn parse(&self, line: &str) -> Result<Option<TraceRecord>, String> {
+        let error = |what: &str| format!("{}:{}: {}", self.path, self.line_number, what);
+        let value: Value = serde_json::from_str(line).map_err(|err| error(&err.to_string()))?;
+
+        match value.get("type").and_then(Value::as_str) {
+            Some("header") => {
+                let config = value
+                    .get("config")
+                    .and_then(Value::as_object)
+                    .map(|config| {
+                        config
+                            .iter()
+                            .filter_map(|(key, value)| Some((key.clone(), value.as_str()?.to_string())))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                Ok(Some(TraceRecord::Header { config }))
+            }
+            Some("motion") => {
+                let number = |key: &str| value.get(key).and_then(Value::as_i64);
+                let (Some(x), Some(y), Some(t)) = (number("x"), number("y"), number("t")) else {
+                    return Err(error("motion needs x, y and t"));
+                };
+                // Out-of-range values would wrap into a different motion
+                let field = |key: &str, value: i64| error(&format!("motion {} = {} is out of range", key, value));
+                let event = MotionEvent {
+                    x: i16::try_from(x).map_err(|_| field("x", x))?,
+                    y: i16::try_from(y).map_err(|_| field("y", y))?,
+                    timestamp: u32::try_from(t).map_err(|_| field("t", t))?,
+                    device: match number("device") {
+                        Some(device) => u16::try_from(device).map_err(|_| field("device", device))?,
+                        None => 0,
+                    },
+                };
+                Ok(Some(TraceRecord::Motion { event }))
+            }
+            Some("label") => {
+                let number = |key: &str| value.get(key).and_then(Value::as_u64);
+                match (number("start"), number("end")) {
+                    (Some(start), Some(end)) if start <= end => {
+                        match (u32::try_from(start), u32::try_from(end)) {
+                            (Ok(start), Ok(end)) => Ok(Some(TraceRecord::Label { start, end })),
+                            _ => Err(error("label start and end are out of range")),
+                        }
+                    }
+                    _ => Err(error("label needs start <= end")),
+                }
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+impl Iterator for TraceReader {
+    type Item = Result<TraceRecord, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let line = match self.lines.next()? {
+                Ok(line) => line,
+                Err(err) => return Some(Err(format!("Failed to read {}: {}", self.path, err))),
+            };
+            self.line_number += 1;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match self.parse(&line) {
+                Ok(Some(record)) => return Some(Ok(record)),
+                Ok(None) => continue,
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp_dir::TempDir;
+    use std::fs;
+
+    fn read(path: &Path) -> Result<Vec<TraceRecord>, String> {
+        TraceReader::open(path)?.collect()
+    }
+
+    #[test]
+    fn reader_reads_what_writer_wrote() {
+        let dir = TempDir::new("trace-round-trip");
+        let trace = dir.0.join("trace.jsonl");
+        let config = Config { min_reversals: 5, cooldown_ms: 1500, ..Config::default() };
+        let event = MotionEvent { x: -12, y: 840, timestamp: 5120331, device: 11 };
+        let mut writer = TraceWriter::create(&trace, &config).unwrap();
+        writer.write_motion(&event, 0.42, false, "idle");
+        writer.write_label(5120200, 5121400);
+        writer.flush();
+        drop(writer);
+        // A record type from a newer version, between two known ones
+        let text = fs::read_to_string(&trace).unwrap();
+        let mut lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 3);
+        lines.insert(2, r#"{"type":"marker","t":1}"#);
+        lines.insert(3, "");
+        fs::write(&trace, lines.join("\n")).unwrap();
+
+        let records = read(&trace).unwrap();
+        assert_eq!(records.len(), 3);
+        let TraceRecord::Header { config: recorded } = &records[0] else { panic!("no header") };
+        let mut read = Config::default();
+        for (key, value) in recorded {
+            read.set(key, value).unwrap();
+        }
+        assert_eq!(read.entries(), config.entries());
+        let TraceRecord::Motion { event: read } = records[1] else { panic!("no motion") };
+        assert_eq!((read.x, read.y, read.timestamp, read.device), (-12, 840, 5120331, 11));
+        assert!(matches!(records[2], TraceRecord::Label { start: 5120200, end: 5121400 }));
+    }
+
+    #[test]
+    fn out_of_range_motion_is_rejected() {
+        let dir = TempDir::new("trace-range");
+        let trace = dir.0.join("trace.jsonl");
+        for line in [
+            r#"{"type":"motion","x":40000,"y":0,"t":0}"#,
+            r#"{"type":"motion","x":0,"y":-40000,"t":0}"#,
+            r#"{"type":"motion","x":0,"y":0,"t":-1}"#,
+            r#"{"type":"motion","x":0,"y":0,"t":4294967296}"#,
+            r#"{"type":"motion","x":0,"y":0,"t":0,"device":65536}"#,
+            r#"{"type":"label","start":0,"end":4294967296}"#,
+        ] {
+            fs::write(&trace, line).unwrap();
+            let err = read(&trace).err().unwrap_or_else(|| panic!("accepted {}", line));
+            assert!(err.contains(":1:"), "{}", err);
+        }
+
+        fs::write(&trace, r#"{"type":"motion","x":-32768,"y":32767,"t":4294967295,"device":65535}"#).unwrap();
+        let records = read(&trace).unwrap();
+        let [TraceRecord::Motion { event }] = records[..] else { panic!("no motion") };
+        assert_eq!((event.x, event.y, event.timestamp, event.device), (i16::MIN, i16::MAX, u32::MAX, u16::MAX));
+    }
+}
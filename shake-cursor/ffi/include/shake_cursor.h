@@ -0,0 +1,106 @@
+/* shake-cursor C API. Generated with cbindgen from ffi/src/lib.rs; do not edit. */
+
+#ifndef SHAKE_CURSOR_H
+#define SHAKE_CURSOR_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/*
+ * A shake detector. Opaque: only handled through pointers.
+ */
+typedef struct ShakeDetector ShakeDetector;
+
+/*
+ * Detection thresholds, as in the daemon's config file, and with the
+ * same ranges.
+ */
+typedef struct ShakeConfig {
+  /*
+   * How far back (ms) motion is considered, 10 to 10000.
+   */
+  uint32_t time_window_ms;
+  /*
+   * Direction reversals needed on one axis within the window, 1 to 100.
+   */
+  uint32_t min_reversals;
+  /*
+   * Average velocity needed within the window, in pixels per second,
+   * 0 to 1000000.
+   */
+  double min_velocity;
+} ShakeConfig;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/*
+ * The default thresholds, to start from when changing only some.
+ */
+struct ShakeConfig shake_config_default(void);
+
+/*
+ * Create a detector with the given thresholds, or the defaults if
+ * `config` is NULL. Free it with shake_detector_free().
+ *
+ * Returns NULL if a threshold is out of range (see ShakeConfig) or NaN.
+ *
+ * # Safety
+ *
+ * `config` must be NULL or point to a valid ShakeConfig.
+ */
+struct ShakeDetector *shake_detector_new(const struct ShakeConfig *config);
+
+/*
+ * Record that the pointer moved to (x, y) at `timestamp_ms`, on any
+ * millisecond clock that is the same for every call.
+ *
+ * # Safety
+ *
+ * `detector` must be NULL (ignored) or a handle from
+ * shake_detector_new() that has not been freed.
+ */
+void shake_detector_record_motion(struct ShakeDetector *detector,
+                                  int16_t x,
+                                  int16_t y,
+                                  uint32_t timestamp_ms);
+
+/*
+ * How close the recorded motion is to a shake: 1.0 or more is a shake.
+ * 0.0 for a NULL handle.
+ *
+ * # Safety
+ *
+ * `detector` must be NULL or a handle from shake_detector_new() that
+ * has not been freed.
+ */
+double shake_detector_score(const struct ShakeDetector *detector);
+
+/*
+ * True if the recorded motion is a shake. False for a NULL handle.
+ *
+ * # Safety
+ *
+ * `detector` must be NULL or a handle from shake_detector_new() that
+ * has not been freed.
+ */
+bool shake_detector_is_shaking(const struct ShakeDetector *detector);
+
+/*
+ * Free a detector. NULL is ignored.
+ *
+ * # Safety
+ *
+ * `detector` must be NULL or a handle from shake_detector_new() that
+ * has not been freed; it must not be used afterwards.
+ */
+void shake_detector_free(struct ShakeDetector *detector);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* SHAKE_CURSOR_H */
@@ -0,0 +1,135 @@
+//! C ABI for shake-cursor's shake detector, for compositors and input
+//! tools written in C or C++.
+//!
+//! The detector is behind an opaque handle: create it with
+//! shake_detector_new(), feed it pointer motion with
+//! shake_detector_record_motion(), ask shake_detector_is_shaking() (or
+//! shake_detector_score() for how close the motion is) after each event,
+//! and release it with shake_detector_free(). Declarations are in
+//! include/shake_cursor.h; regenerate it with cbindgen after changing
+//! this file.
+//!
+//! A handle is not thread-safe: use each one from one thread at a time.
+//! Adaptive mode is not available here, since it keeps its state in the
+//! daemon's files.
+
+use shake_cursor_core::config::{MIN_REVERSALS, MIN_VELOCITY, TIME_WINDOW_MS};
+use shake_cursor_core::detector;
+use shake_cursor_core::{Config, MotionEvent};
+
+/// Detection thresholds, as in the daemon's config file, and with the
+/// same ranges.
+#[repr(C)]
+pub struct ShakeConfig {
+    /// How far back (ms) motion is considered, 10 to 10000.
+    pub time_window_ms: u32,
+
+    /// Direction reversals needed on one axis within the window, 1 to 100.
+    pub min_reversals: u32,
+
+    /// Average velocity needed within the window, in pixels per second,
+    /// 0 to 1000000.
+    pub min_velocity: f64,
+}
+
+/// A shake detector. Opaque: only handled through pointers.
+pub struct ShakeDetector {
+    detector: detector::ShakeDetector,
+}
+
+/// The default thresholds, to start from when changing only some.
+#[unsafe(no_mangle)]
+pub extern "C" fn shake_config_default() -> ShakeConfig {
+    let config = Config::default();
+    ShakeConfig {
+        time_window_ms: config.time_window_ms,
+        min_reversals: config.min_reversals,
+        min_velocity: config.min_velocity,
+    }
+}
+
+/// Create a detector with the given thresholds, or the defaults if
+/// `config` is NULL. Free it with shake_detector_free().
+///
+/// Returns NULL if a threshold is out of range (see ShakeConfig) or NaN.
+///
+/// # Safety
+///
+/// `config` must be NULL or point to a valid ShakeConfig.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn shake_detector_new(config: *const ShakeConfig) -> *mut ShakeDetector {
+    let mut detector_config = Config { adaptive_enabled: false, ..Config::default() };
+    // SAFETY: the caller passes NULL or a valid ShakeConfig
+    if let Some(config) = unsafe { config.as_ref() } {
+        if !TIME_WINDOW_MS.contains(&config.time_window_ms)
+            || !MIN_REVERSALS.contains(&config.min_reversals)
+            || !MIN_VELOCITY.contains(&config.min_velocity)
+        {
+            return std::ptr::null_mut();
+        }
+        detector_config.time_window_ms = config.time_window_ms;
+        detector_config.min_reversals = config.min_reversals;
+        detector_config.min_velocity = config.min_velocity;
+    }
+    let detector = detector::ShakeDetector::new(detector_config);
+    Box::into_raw(Box::new(ShakeDetector { detector }))
+}
+
+/// Record that the pointer moved to (x, y) at `timestamp_ms`, on any
+/// millisecond clock that is the same for every call.
+///
+/// # Safety
+///
+/// `detector` must be NULL (ignored) or a handle from
+/// shake_detector_new() that has not been freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn shake_detector_record_motion(
+    detector: *mut ShakeDetector,
+    x: i16,
+    y: i16,
+    timestamp_ms: u32,
+) {
+    // SAFETY: the caller passes NULL or a live handle
+    if let Some(handle) = unsafe { detector.as_mut() } {
+        handle.detector.record_motion(MotionEvent { x, y, timestamp: timestamp_ms, device: 0 });
+    }
+}
+
+/// How close the recorded motion is to a shake: 1.0 or more is a shake.
+/// 0.0 for a NULL handle.
+///
+/// # Safety
+///
+/// `detector` must be NULL or a handle from shake_detector_new() that
+/// has not been freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn shake_detector_score(detector: *const ShakeDetector) -> f64 {
+    // SAFETY: the caller passes NULL or a live handle
+    unsafe { detector.as_ref() }.map_or(0.0, |handle| handle.detector.score())
+}
+
+/// True if the recorded motion is a shake. False for a NULL handle.
+///
+/// # Safety
+///
+/// `detector` must be NULL or a handle from shake_detector_new() that
+/// has not been freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn shake_detector_is_shaking(detector: *const ShakeDetector) -> bool {
+    // SAFETY: the caller passes NULL or a live handle
+    unsafe { detector.as_ref() }.is_some_and(|handle| handle.detector.is_shaking())
+}
+
+/// Free a detector. NULL is ignored.
+///
+/// # Safety
+///
+/// `detector` must be NULL or a handle from shake_detector_new() that
+/// has not been freed; it must not be used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn shake_detector_free(detector: *mut ShakeDetector) {
+    if !detector.is_null() {
+        // SAFETY: the handle came from Box::into_raw() in shake_detector_new()
+        drop(unsafe { Box::from_raw(detector) });
+    }
+}
@@ -0,0 +1,118 @@
+/* Exercises the C API the way an embedding program would. Built and run
+ * by tests/c_api.rs; prints the failed check and exits 1 on failure. */
+
+#include <math.h>
+#include <stdio.h>
+
+#include "shake_cursor.h"
+
+#define CHECK(condition)                                                  \
+    do {                                                                  \
+        if (!(condition)) {                                               \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__,       \
+                    __LINE__, #condition);                                \
+            return 1;                                                     \
+        }                                                                 \
+    } while (0)
+
+/* Side to side, 100 px every 16 ms: well above the default thresholds. */
+static void shake(ShakeDetector *detector, uint32_t start_ms, int events)
+{
+    for (int i = 0; i < events; i++) {
+        int16_t x = i % 2 ? 400 : 500;
+        shake_detector_record_motion(detector, x, 300, start_ms + i * 16);
+    }
+}
+
+static int test_defaults(void)
+{
+    ShakeConfig config = shake_config_default();
+    CHECK(config.time_window_ms == 500);
+    CHECK(config.min_reversals == 3);
+    CHECK(config.min_velocity == 500.0);
+    return 0;
+}
+
+static int test_shake_is_detected(void)
+{
+    ShakeDetector *detector = shake_detector_new(NULL);
+    CHECK(detector != NULL);
+    CHECK(!shake_detector_is_shaking(detector));
+    CHECK(shake_detector_score(detector) == 0.0);
+
+    shake(detector, 0, 20);
+    CHECK(shake_detector_is_shaking(detector));
+    CHECK(shake_detector_score(detector) >= 1.0);
+
+    /* A second later, slow straight motion has replaced the shake */
+    for (int i = 0; i < 40; i++) {
+        shake_detector_record_motion(detector, 500 + i, 300, 1000 + i * 16);
+    }
+    CHECK(!shake_detector_is_shaking(detector));
+    CHECK(shake_detector_score(detector) < 1.0);
+
+    shake_detector_free(detector);
+    return 0;
+}
+
+static int test_config_is_applied(void)
+{
+    ShakeConfig config = shake_config_default();
+    config.min_reversals = 100;
+    ShakeDetector *strict = shake_detector_new(&config);
+    CHECK(strict != NULL);
+
+    shake(strict, 0, 20);
+    CHECK(!shake_detector_is_shaking(strict));
+    CHECK(shake_detector_score(strict) > 0.0);
+
+    shake_detector_free(strict);
+    return 0;
+}
+
+static int test_invalid_config_is_rejected(void)
+{
+    ShakeConfig config = shake_config_default();
+    config.time_window_ms = UINT32_MAX;
+    CHECK(shake_detector_new(&config) == NULL);
+
+    config = shake_config_default();
+    config.min_reversals = 0;
+    CHECK(shake_detector_new(&config) == NULL);
+
+    config = shake_config_default();
+    config.min_velocity = NAN;
+    CHECK(shake_detector_new(&config) == NULL);
+
+    config = shake_config_default();
+    config.min_velocity = -1.0;
+    CHECK(shake_detector_new(&config) == NULL);
+
+    /* The ends of the ranges are valid */
+    config.time_window_ms = 10;
+    config.min_reversals = 100;
+    config.min_velocity = 1000000.0;
+    ShakeDetector *detector = shake_detector_new(&config);
+    CHECK(detector != NULL);
+    shake_detector_free(detector);
+    return 0;
+}
+
+static int test_null_handle(void)
+{
+    shake_detector_record_motion(NULL, 0, 0, 0);
+    CHECK(shake_detector_score(NULL) == 0.0);
+    CHECK(!shake_detector_is_shaking(NULL));
+    shake_detector_free(NULL);
+    return 0;
+}
+
+int main(void)
+{
+    if (test_defaults() || test_shake_is_detected() || test_config_is_applied() || test_invalid_config_is_rejected()
+        || test_null_handle()) {
+        return 1;
+    }
+    printf("c_api: all checks passed\n");
+    return 0;
+}
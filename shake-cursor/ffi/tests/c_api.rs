@@ -0,0 +1,72 @@
+//! Builds tests/c_api.c against include/shake_cursor.h and the cdylib,
+//! as a C (and C++) program embedding the detector would be, and runs it.
+//!
+//! Skipped when no C compiler is installed. $CC and $CXX choose the
+//! compilers, like they do for build scripts.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Where cargo put libshake_cursor.so: next to this test's executable.
+fn library_dir() -> PathBuf {
+    let exe = env::current_exe().unwrap();
+    let dir = exe.parent().unwrap().to_path_buf();
+    assert!(dir.join("libshake_cursor.so").exists(), "libshake_cursor.so not found in {}", dir.display());
+    dir
+}
+
+/// Compile tests/c_api.c with `compiler` (plus `flags`) into the temp
+/// directory as `name`, and run it. False if the compiler isn't installed.
+fn build_and_run(compiler: &str, flags: &[&str], name: &str) -> bool {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let library_dir = library_dir();
+    let output = env::temp_dir().join(format!("shake-cursor-{}-{}", name, std::process::id()));
+
+    let status = match Command::new(compiler)
+        .args(flags)
+        .arg("-Wall")
+        .arg("-Wextra")
+        .arg("-Werror")
+        .arg("-I")
+        .arg(manifest_dir.join("include"))
+        .arg(manifest_dir.join("tests/c_api.c"))
+        .arg("-o")
+        .arg(&output)
+        .arg("-L")
+        .arg(&library_dir)
+        .arg(format!("-Wl,-rpath,{}", library_dir.display()))
+        .arg("-lshake_cursor")
+        .status()
+    {
+        Ok(status) => status,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return false,
+        Err(err) => panic!("Failed to run {}: {}", compiler, err),
+    };
+    assert!(status.success(), "{} failed to build c_api.c", compiler);
+
+    let run = Command::new(&output).output();
+    let _ = fs::remove_file(&output);
+    let run = run.unwrap();
+    eprint!("{}", String::from_utf8_lossy(&run.stderr));
+    assert!(run.status.success(), "c_api failed ({})", run.status);
+    true
+}
+
+#[test]
+fn c_program_detects_shakes() {
+    let compiler = env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    if !build_and_run(&compiler, &["-std=c99"], "c-api") {
+        eprintln!("{} not found, skipping C API test", compiler);
+    }
+}
+
+#[test]
+fn header_works_from_cpp() {
+    let compiler = env::var("CXX").unwrap_or_else(|_| "c++".to_string());
+    if !build_and_run(&compiler, &["-x", "c++", "-std=c++11"], "cpp-api") {
+        eprintln!("{} not found, skipping C++ API test", compiler);
+    }
+}
@@ -0,0 +1,86 @@
+#![no_main]
+
+use libfuzzer_sys::arbitrary::{self, Arbitrary};
+use libfuzzer_sys::fuzz_target;
+
+use shake_cursor_core::gesture::{Axis, Generator, Gesture};
+use shake_cursor_core::{Config, MotionEvent, ShakeDetector};
+
+/// Motion to feed the detector: raw events anywhere on the i16 plane,
+/// with timestamps that may jump or go backwards, mixed with gestures
+/// from the generator.
+#[derive(Arbitrary, Debug)]
+enum Step {
+    Event { x: i16, y: i16, dt: i16 },
+    Swipe { length: u16, angle: u16, duration_ms: u16 },
+    Circle { radius: u16, period_ms: u16, duration_ms: u16 },
+    Shake { axis: u8, frequency: u8, amplitude: u16, duration_ms: u16 },
+}
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    time_window_ms: u16,
+    min_reversals: u8,
+    min_velocity: u16,
+    seed: u64,
+    rate_hz: u16,
+    noise: u8,
+    steps: Vec<Step>,
+}
+
+/// Limit on the time window and gesture durations (ms). Stats are
+/// recomputed over the whole window on every event, so an 8000 Hz mouse
+/// with minute-long windows makes inputs too slow to fuzz.
+const MAX_MS: u16 = 1000;
+
+fuzz_target!(|input: Input| {
+    let config = Config {
+        time_window_ms: (input.time_window_ms % MAX_MS) as u32,
+        min_reversals: input.min_reversals as u32,
+        min_velocity: input.min_velocity as f64,
+        ..Config::default()
+    };
+    let time_window_ms = config.time_window_ms;
+    let mut detector = ShakeDetector::new(config);
+
+    let mut generator = Generator::new(input.seed);
+    generator.rate_hz = (input.rate_hz as f64).clamp(1.0, 8000.0);
+    generator.noise = input.noise as f64 / 16.0;
+
+    let mut timestamp: u32 = 0;
+    for step in input.steps {
+        let events = match step {
+            Step::Event { x, y, dt } => {
+                timestamp = timestamp.wrapping_add_signed(dt as i32);
+                vec![MotionEvent { x, y, timestamp, device: 0 }]
+            }
+            Step::Swipe { length, angle, duration_ms } => generator.generate(&Gesture::Swipe {
+                length: length as f64,
+                angle: angle as f64,
+                duration_ms: (duration_ms % MAX_MS) as u32,
+            }),
+            Step::Circle { radius, period_ms, duration_ms } => generator.generate(&Gesture::Circle {
+                radius: radius as f64,
+                period_ms: period_ms.max(1) as u32,
+                duration_ms: (duration_ms % MAX_MS) as u32,
+            }),
+            Step::Shake { axis, frequency, amplitude, duration_ms } => generator.generate(&Gesture::Shake {
+                axis: [Axis::Horizontal, Axis::Vertical, Axis::Diagonal][axis as usize % 3],
+                frequency: frequency as f64,
+                amplitude: amplitude as f64,
+                duration_ms: (duration_ms % MAX_MS) as u32,
+            }),
+        };
+
+        for event in events {
+            detector.record_motion(event);
+            let stats = detector.stats();
+            assert!(stats.time_span_ms <= time_window_ms, "buffer outgrew the window: {:?}", stats);
+            let score = detector.score();
+            assert!(score >= 0.0, "score {} for {:?}", score, detector.stats());
+            if detector.is_shaking() {
+                assert!(score >= 1.0, "shaking with score {} for {:?}", score, detector.stats());
+            }
+        }
+    }
+});
@@ -0,0 +1,507 @@
+use std::path::{Path, PathBuf};
+
+use shake_cursor_core::backend::MotionEvent;
+use shake_cursor_core::config::{Config, LocateEffect};
+use shake_cursor_core::controller::{Controller, SystemClock};
+use shake_cursor_core::detector::{self, Thresholds};
+use shake_cursor_core::trace::{TraceReader, TraceRecord};
+
+use crate::effect;
+use crate::script_backend::ScriptBackend;
+
+/// Grid searched by `--sweep` when no values are given for a parameter.
+const SWEEP_TIME_WINDOW_MS: &[u32] = &[250, 300, 400, 500, 600, 800, 1000];
+const SWEEP_MIN_REVERSALS: &[u32] = &[2, 3, 4, 5, 6, 8];
+const SWEEP_MIN_VELOCITY: &[f64] = &[250.0, 500.0, 750.0, 1000.0, 1500.0, 2000.0, 3000.0];
+
+/// How many of the best sweep results to print.
+const SWEEP_TOP: usize = 10;
+
+pub const USAGE: &str = "\
+Usage: shake-cursor analyze [OPTIONS] <TRACE>
+
+Runs a recorded trace through the shake detector and reports the shakes
+it detects. Label lines in the trace mark deliberate shakes; with them,
+detections are scored by precision, recall and F1.
+
+Options:
+  --config <PATH>    Analyze with this config file instead of the one
+                     recorded in the trace (repeat to compare several,
+                     except with --sweep)
+  --set <KEY=VALUE>  Override one parameter in every config analyzed
+  --windows          Print the reversals and velocity of every window
+  --sweep            Grid-search time_window_ms, min_reversals and
+                     min_velocity for the best F1 (needs labels)
+  --time-window <LIST>
+  --reversals <LIST>
+  --velocity <LIST>  Comma-separated values to sweep instead of the
+                     built-in grid";
+
+/// Parsed `analyze` arguments.
+struct Options {
+    trace: PathBuf,
+    configs: Vec<PathBuf>,
+    overrides: Vec<(String, String)>,
+    windows: bool,
+    sweep: bool,
+    time_window_ms: Vec<u32>,
+    min_reversals: Vec<u32>,
+    min_velocity: Vec<f64>,
+}
+
+/// Everything analyze needs from a trace, read into memory because each
+/// configuration makes its own pass over the events.
+struct Trace {
+    /// The configuration the trace was recorded with.
+    config: Config,
+    events: Vec<MotionEvent>,
+    labels: Vec<(u32, u32)>,
+}
+
+/// Detections compared against the labelled intervals.
+#[derive(Clone, Copy)]
+struct Score {
+    detections: usize,
+    /// Detections inside a labelled interval.
+    correct: usize,
+    labels: usize,
+    /// Labelled intervals with at least one detection.
+    found: usize,
+}
+
+impl Score {
+    /// Score shake onsets (timestamps) against the labelled intervals.
+    fn new(onsets: &[u32], labels: &[(u32, u32)]) -> Self {
+        let inside = |timestamp: u32, (start, end): (u32, u32)| (start..=end).contains(&timestamp);
+        Self {
+            detections: onsets.len(),
+            correct: onsets
+                .iter()
+                .filter(|&&timestamp| labels.iter().any(|&label| inside(timestamp, label)))
+                .count(),
+            labels: labels.len(),
+            found: labels
+                .iter()
+                .filter(|&&label| onsets.iter().any(|&timestamp| inside(timestamp, label)))
+                .count(),
+        }
+    }
+
+    fn precision(&self) -> f64 {
+        ratio(self.correct, self.detections)
+    }
+
+    fn recall(&self) -> f64 {
+        ratio(self.found, self.labels)
+    }
+
+    fn f1(&self) -> f64 {
+        let (precision, recall) = (self.precision(), self.recall());
+        if precision + recall == 0.0 {
+            0.0
+        } else {
+            2.0 * precision * recall / (precision + recall)
+        }
+    }
+}
+
+fn ratio(part: usize, whole: usize) -> f64 {
+    if whole == 0 {
+        0.0
+    } else {
+        part as f64 / whole as f64
+    }
+}
+
+/// Run `shake-cursor analyze` with the arguments after the subcommand
+/// name. Returns the process exit code.
+pub fn run(args: &[String]) -> i32 {
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", USAGE);
+        return 0;
+    }
+
+    let options = match parse(args) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, USAGE);
+            return 2;
+        }
+    };
+
+    match analyze(&options) {
+        Ok(()) => 0,
+        Err(err) => {
+            eprintln!("{}", err);
+            1
+        }
+    }
+}
+
+fn parse(args: &[String]) -> Result<Options, String> {
+    /// Values to sweep `key` over, each checked like `--set` checks it.
+    fn list<T: std::str::FromStr>(option: &str, key: &str, value: Option<&String>) -> Result<Vec<T>, String> {
+        let value = value.ok_or_else(|| format!("{} requires a value", option))?;
+        value
+            .split(',')
+            .map(|item| {
+                Config::default().set(key, item.trim())?;
+                item.trim().parse().map_err(|_| format!("Invalid value '{}' for {}", item, option))
+            })
+            .collect()
+    }
+
+    let mut trace = None;
+    let mut options = Options {
+        trace: PathBuf::new(),
+        configs: Vec::new(),
+        overrides: Vec::new(),
+        windows: false,
+        sweep: false,
+        time_window_ms: SWEEP_TIME_WINDOW_MS.to_vec(),
+        min_reversals: SWEEP_MIN_REVERSALS.to_vec(),
+        min_velocity: SWEEP_MIN_VELOCITY.to_vec(),
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--config" => {
+                let path = args.next()
+                    .ok_or_else(|| "--config requires a value".to_string())?;
+                options.configs.push(PathBuf::from(path));
+            }
+            "--set" => {
+                let setting = args.next()
+                    .ok_or_else(|| "--set requires a value".to_string())?;
+                let (key, value) = setting.split_once('=')
+                    .ok_or_else(|| format!("Invalid setting '{}' (expected KEY=VALUE)", setting))?;
+                // Validate now rather than once per config
+                Config::default().set(key.trim(), value.trim())?;
+                options.overrides.push((key.trim().to_string(), value.trim().to_string()));
+            }
+            "--windows" => options.windows = true,
+            "--sweep" => options.sweep = true,
+            "--time-window" => options.time_window_ms = list(arg, "time_window_ms", args.next())?,
+            "--reversals" => options.min_reversals = list(arg, "min_reversals", args.next())?,
+            "--velocity" => options.min_velocity = list(arg, "min_velocity", args.next())?,
+            other if other.starts_with('-') => return Err(format!("Unknown argument '{}'", other)),
+            path if trace.is_none() => trace = Some(PathBuf::from(path)),
+            other => return Err(format!("Unexpected argument '{}'", other)),
+        }
+    }
+
+    options.trace = trace.ok_or_else(|| "analyze requires a trace file".to_string())?;
+    if options.sweep && options.configs.len() > 1 {
+        return Err("--sweep takes at most one --config, which supplies the parameters not swept".to_string());
+    }
+    Ok(options)
+}
+
+fn analyze(options: &Options) -> Result<(), String> {
+    let trace = read_trace(&options.trace)?;
+    let (Some(first), Some(last)) = (trace.events.first(), trace.events.last()) else {
+        return Err(format!("{} has no motion events", options.trace.display()));
+    };
+    println!(
+        "{}: {} events over {:.1}s, {} labelled shakes",
+        options.trace.display(),
+        trace.events.len(),
+        last.timestamp.saturating_sub(first.timestamp) as f64 / 1000.0,
+        trace.labels.len()
+    );
+
+    // The configurations to compare, each with the overrides applied
+    let mut configs: Vec<(String, Config)> = Vec::new();
+    if options.configs.is_empty() {
+        configs.push(("recorded config".to_string(), trace.config.clone()));
+    }
+    for path in &options.configs {
+        configs.push((path.display().to_string(), Config::load(path)?));
+    }
+    for (_, config) in &mut configs {
+        for (key, value) in &options.overrides {
+            config.set(key, value)?;
+        }
+    }
+
+    if options.sweep {
+        if trace.labels.is_empty() {
+            return Err("--sweep needs labelled shakes in the trace to score against".to_string());
+        }
+        // Parameters other than the swept ones come from the first config
+        sweep(&trace, &configs[0].1, options);
+        return Ok(());
+    }
+
+    for (name, config) in &configs {
+        println!();
+        println!(
+            "{}: time_window_ms = {}, min_reversals = {}, min_velocity = {}",
+            name, config.time_window_ms, config.min_reversals, config.min_velocity
+        );
+
+        let onsets = detect(config, &trace.events);
+
+        let stats = detector::window_stats(&trace.events, config.time_window_ms);
+        if options.windows {
+            for (event, stats) in trace.events.iter().zip(&stats) {
+                println!(
+                    "  t={} x={} y={}  reversals {} (x {}, y {})  {:.0} px/s over {}ms{}",
+                    event.timestamp, event.x, event.y,
+                    stats.reversals(), stats.x_reversals, stats.y_reversals,
+                    stats.velocity, stats.time_span_ms,
+                    if stats.is_shake(&Thresholds::of(config)) { "  shaking" } else { "" }
+                );
+            }
+        }
+
+        for &index in &onsets {
+            let (event, stats) = (&trace.events[index], &stats[index]);
+            println!(
+                "  shake at t={} (x={}, y={}): {} reversals, {:.0} px/s",
+                event.timestamp, event.x, event.y, stats.reversals(), stats.velocity
+            );
+        }
+        println!("  {} shakes detected", onsets.len());
+
+        if !trace.labels.is_empty() {
+            let score = Score::new(&timestamps(&trace.events, &onsets), &trace.labels);
+            println!(
+                "  precision {:.2} ({}/{}), recall {:.2} ({}/{}), F1 {:.2}",
+                score.precision(), score.correct, score.detections,
+                score.recall(), score.found, score.labels,
+                score.f1()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Read the whole trace. The recorded configuration starts from the
+/// defaults, so traces from older versions still load; keys this
+/// version doesn't know are skipped.
+fn read_trace(path: &Path) -> Result<Trace, String> {
+    let mut trace = Trace { config: Config::default(), events: Vec::new(), labels: Vec::new() };
+    for record in TraceReader::open(path)? {
+        match record? {
+            TraceRecord::Header { config } => {
+                for (key, value) in config {
+                    if let Err(err) = trace.config.set(&key, &value) {
+                        eprintln!("Ignoring recorded {} = {}: {}", key, value, err);
+                    }
+                }
+            }
+            TraceRecord::Motion { event } => trace.events.push(event),
+            TraceRecord::Label { start, end } => trace.labels.push((start, end)),
+        }
+    }
+    Ok(trace)
+}
+
+/// Grid-search the shake thresholds and print the configurations with
+/// the best F1. `base` supplies every other parameter, e.g. cooldown_ms.
+fn sweep(trace: &Trace, base: &Config, options: &Options) {
+    let results = sweep_results(trace, base, options);
+
+    println!();
+    println!("Swept {} configurations:", results.len());
+    println!("  time_window_ms  min_reversals  min_velocity  precision  recall     F1");
+    for (config, score) in results.iter().take(SWEEP_TOP) {
+        println!(
+            "  {:>14}  {:>13}  {:>12}  {:>9.2}  {:>6.2}  {:>5.2}",
+            config.time_window_ms, config.min_reversals, config.min_velocity,
+            score.precision(), score.recall(), score.f1()
+        );
+    }
+
+    if let Some((best, _)) = results.first() {
+        println!();
+        println!("Best configuration:");
+        println!("time_window_ms = {}", best.time_window_ms);
+        println!("min_reversals = {}", best.min_reversals);
+        println!("min_velocity = {}", best.min_velocity);
+    }
+}
+
+/// Score every combination of the swept parameters, best first.
+fn sweep_results(trace: &Trace, base: &Config, options: &Options) -> Vec<(Config, Score)> {
+    let mut results = Vec::new();
+    for &time_window_ms in &options.time_window_ms {
+        for &min_reversals in &options.min_reversals {
+            for &min_velocity in &options.min_velocity {
+                let config = Config { time_window_ms, min_reversals, min_velocity, ..base.clone() };
+                let onsets = detect(&config, &trace.events);
+                let score = Score::new(&timestamps(&trace.events, &onsets), &trace.labels);
+                results.push((config, score));
+            }
+        }
+    }
+    rank(&mut results);
+    results
+}
+
+/// Sort by F1, best first; among equals, prefer precision, since a false
+/// enlargement is more disruptive than a shake that needs repeating.
+fn rank(results: &mut [(Config, Score)]) {
+    results.sort_by(|(_, a), (_, b)| {
+        b.f1().total_cmp(&a.f1()).then(b.precision().total_cmp(&a.precision()))
+    });
+}
+
+/// Indices of the events at which a shake starts the locate effects.
+///
+/// The events go through the daemon's Controller, so a shake is reported
+/// where the daemon would report it and continued shaking only extends
+/// the cooldown. Only the enlarge effect runs, and wake triggers and
+/// adaptive mode are off, so the configured thresholds alone decide.
+fn detect(config: &Config, events: &[MotionEvent]) -> Vec<usize> {
+    let config = Config {
+        effects: vec![LocateEffect::Enlarge],
+        wake_enabled: false,
+        adaptive_enabled: false,
+        ..config.clone()
+    };
+    let mut controller = Controller::new(
+        Box::new(ScriptBackend::new(events.iter().copied())),
+        Box::new(SystemClock::start()),
+        config,
+        effect::build_chain,
+    );
+    let mut onsets = Vec::new();
+    for index in 0..events.len() {
+        // The backend yields these same events, in order
+        let Some(event) = controller.next_event() else { break };
+        if controller.handle(event, false).is_some() {
+            onsets.push(index);
+        }
+    }
+    controller.shutdown();
+    onsets
+}
+
+fn timestamps(events: &[MotionEvent], indices: &[usize]) -> Vec<u32> {
+    indices.iter().map(|&index| events[index].timestamp).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shake_cursor_core::gesture::{Generator, Gesture};
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
+    }
+
+    fn score(detections: usize, correct: usize, labels: usize, found: usize) -> Score {
+        Score { detections, correct, labels, found }
+    }
+
+    #[test]
+    fn score_counts_onsets_inside_labels() {
+        let labels = [(100, 200), (400, 600), (1000, 1100)];
+        // Two onsets in the second label, one outside every label
+        let score = Score::new(&[150, 450, 550, 900], &labels);
+        assert_eq!((score.detections, score.correct, score.labels, score.found), (4, 3, 3, 2));
+        assert_eq!(score.precision(), 0.75);
+        assert_eq!(score.recall(), 2.0 / 3.0);
+        assert!((score.f1() - 2.0 * 0.75 * (2.0 / 3.0) / (0.75 + 2.0 / 3.0)).abs() < 1e-12);
+
+        // Label bounds are inclusive
+        assert_eq!(Score::new(&[100, 200], &[(100, 200)]).precision(), 1.0);
+    }
+
+    #[test]
+    fn score_without_detections_or_labels_is_zero() {
+        let none = Score::new(&[], &[(100, 200)]);
+        assert_eq!((none.precision(), none.recall(), none.f1()), (0.0, 0.0, 0.0));
+        let unlabelled = Score::new(&[150], &[]);
+        assert_eq!((unlabelled.precision(), unlabelled.recall(), unlabelled.f1()), (0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn parse_reads_options() {
+        let options = parse(&args(
+            "--config a.conf trace.jsonl --set min_reversals=5 --windows --time-window 300,400 --velocity 250.5",
+        ))
+        .unwrap();
+        assert_eq!(options.trace, PathBuf::from("trace.jsonl"));
+        assert_eq!(options.configs, [PathBuf::from("a.conf")]);
+        assert_eq!(options.overrides, [("min_reversals".to_string(), "5".to_string())]);
+        assert!(options.windows && !options.sweep);
+        assert_eq!(options.time_window_ms, [300, 400]);
+        assert_eq!(options.min_reversals, SWEEP_MIN_REVERSALS);
+        assert_eq!(options.min_velocity, [250.5]);
+    }
+
+    #[test]
+    fn parse_rejects_bad_arguments() {
+        for line in [
+            "",
+            "--windows",
+            "trace.jsonl other.jsonl",
+            "trace.jsonl --frobnicate",
+            "trace.jsonl --config",
+            "trace.jsonl --set min_reversals",
+            "trace.jsonl --set min_reversals=many",
+            "trace.jsonl --reversals 3,x",
+            "trace.jsonl --sweep --config a.conf --config b.conf",
+        ] {
+            assert!(parse(&args(line)).is_err(), "accepted '{}'", line);
+        }
+        assert!(parse(&args("trace.jsonl --sweep --config a.conf")).is_ok());
+    }
+
+    #[test]
+    fn parse_rejects_swept_values_out_of_range() {
+        for (line, error) in [
+            ("trace.jsonl --reversals 3,0", "Invalid value '0' for min_reversals (expected 1 to 100)"),
+            ("trace.jsonl --velocity nan", "Invalid value 'nan' for min_velocity (expected 0 to 1000000)"),
+            ("trace.jsonl --time-window 0", "Invalid value '0' for time_window_ms (expected 10 to 10000)"),
+        ] {
+            assert_eq!(parse(&args(line)).err().as_deref(), Some(error), "{}", line);
+        }
+    }
+
+    #[test]
+    fn detect_reports_each_shake_once() {
+        let mut generator = Generator::new(1);
+        let mut events = Vec::new();
+        for spec in ["swipe", "shake", "pause:duration=3000", "swipe", "shake"] {
+            events.extend(generator.generate(&Gesture::parse(spec).unwrap()));
+        }
+        assert_eq!(detect(&Config::default(), &events).len(), 2);
+    }
+
+    #[test]
+    fn sweep_ranks_by_f1_then_precision() {
+        let mut generator = Generator::new(1);
+        let mut trace = Trace { config: Config::default(), events: Vec::new(), labels: Vec::new() };
+        for spec in ["swipe", "shake", "pause:duration=3000", "circle", "shake"] {
+            let gesture = Gesture::parse(spec).unwrap();
+            let start = generator.time_ms();
+            trace.events.extend(generator.generate(&gesture));
+            if matches!(gesture, Gesture::Shake { .. }) {
+                trace.labels.push((start, generator.time_ms()));
+            }
+        }
+        let mut options = parse(&args("trace.jsonl --sweep --time-window 500 --velocity 500")).unwrap();
+        options.min_reversals = vec![50, 3];
+
+        let results = sweep_results(&trace, &trace.config, &options);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].0.min_reversals, 3);
+        assert_eq!(results[0].1.f1(), 1.0);
+        assert_eq!(results[1].1.detections, 0);
+
+        // Equal F1: the more precise result comes first
+        let mut results = vec![
+            (Config { min_reversals: 1, ..Config::default() }, score(2, 1, 1, 1)),
+            (Config { min_reversals: 2, ..Config::default() }, score(1, 1, 2, 1)),
+            (Config { min_reversals: 3, ..Config::default() }, score(1, 1, 1, 1)),
+        ];
+        rank(&mut results);
+        let order: Vec<u32> = results.iter().map(|(config, _)| config.min_reversals).collect();
+        assert_eq!(order, [3, 2, 1]);
+    }
+}
@@ -0,0 +1,528 @@
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::*;
+use x11rb::rust_connection::RustConnection;
+use x11rb::wrapper::ConnectionExt as _;
+
+use shake_cursor_core::backend::{DisplayBackend, InputEvent, MotionEvent};
+use shake_cursor_core::config::{self, Config, LocateEffect};
+use shake_cursor_core::controller::{Controller, SystemClock};
+use shake_cursor_core::detector::{self, MotionStats, Thresholds};
+use shake_cursor_core::trace::TraceWriter;
+
+use crate::effect;
+use crate::notify;
+use crate::registry;
+use crate::script_backend::ScriptBackend;
+
+/// How long to record normal pointer movement.
+const NORMAL_DURATION: Duration = Duration::from_secs(20);
+
+/// Number of deliberate shakes to record.
+const SHAKES: usize = 5;
+
+/// Time given for each shake, and the pause before it.
+const SHAKE_DURATION: Duration = Duration::from_secs(3);
+const REST_DURATION: Duration = Duration::from_secs(2);
+
+/// Countdown before recording starts, to read the instructions.
+const COUNTDOWN_DURATION: Duration = Duration::from_secs(5);
+
+/// How often the prompt is redrawn (the countdown is in whole seconds).
+const REDRAW_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Fewer usable shakes than this and the recording is rejected.
+const MIN_SHAKES: usize = 3;
+
+/// Fraction of normal-movement windows allowed above a threshold when
+/// choosing it: the 99th percentile, so a rare flick doesn't set it.
+const NORMAL_PERCENTILE: f64 = 0.99;
+
+const WINDOW_WIDTH: u16 = 460;
+const WINDOW_HEIGHT: u16 = 120;
+
+pub const USAGE: &str = "\
+Usage: shake-cursor calibrate [OPTIONS]
+
+Records 20 seconds of normal pointer movement and five deliberate shakes,
+then writes shake thresholds that separate the two to the config file.
+Needs an X11 session.
+
+Options:
+  --output <PATH>    Config file to write (default: $XDG_CONFIG_HOME/shake-cursor/config)
+  --trace <PATH>     Also save the recording as a labelled trace for `analyze`
+  --dry-run          Print the thresholds without writing them";
+
+/// Parsed `calibrate` arguments.
+struct Options {
+    output: Option<PathBuf>,
+    trace: Option<PathBuf>,
+    dry_run: bool,
+}
+
+/// What the wizard is asking the user to do.
+enum Step {
+    Countdown,
+    Normal,
+    Rest(usize),
+    Shake(usize),
+}
+
+impl Step {
+    fn instructions(&self) -> [String; 2] {
+        match self {
+            Step::Countdown => [
+                "First, use the pointer as you normally would.".to_string(),
+                "Then shake it five times when asked.".to_string(),
+            ],
+            Step::Normal => [
+                "Move the pointer as you normally would.".to_string(),
+                "Don't shake it yet.".to_string(),
+            ],
+            Step::Rest(number) => [
+                format!("Get ready for shake {} of {}.", number, SHAKES),
+                "Keep the pointer still.".to_string(),
+            ],
+            Step::Shake(number) => [
+                format!("Shake the pointer now! ({} of {})", number, SHAKES),
+                "Shake it the way you would to find it.".to_string(),
+            ],
+        }
+    }
+}
+
+/// The recording: every motion event, and which of them belong to the
+/// normal-movement step and to each shake.
+struct Recording {
+    events: Vec<MotionEvent>,
+    normal: Range<usize>,
+    shakes: Vec<Range<usize>>,
+}
+
+/// The window telling the user what to do. Drawn with core X fonts on a
+/// separate connection, so the backend's event handling is untouched.
+struct PromptWindow {
+    conn: RustConnection,
+    window: Window,
+    gc: Gcontext,
+}
+
+impl PromptWindow {
+    fn open() -> Result<Self, String> {
+        let (conn, screen_num) = RustConnection::connect(None)
+            .map_err(|err| format!("Failed to connect to X server: {}", err))?;
+        let screen = &conn.setup().roots[screen_num];
+        let (root, white, black) = (screen.root, screen.white_pixel, screen.black_pixel);
+        let x = (screen.width_in_pixels.saturating_sub(WINDOW_WIDTH) / 2) as i16;
+        let y = (screen.height_in_pixels.saturating_sub(WINDOW_HEIGHT) / 2) as i16;
+
+        let window = conn.generate_id()
+            .map_err(|err| format!("Failed to allocate window ID: {}", err))?;
+        create_window(
+            &conn,
+            x11rb::COPY_DEPTH_FROM_PARENT,
+            window,
+            root,
+            x,
+            y,
+            WINDOW_WIDTH,
+            WINDOW_HEIGHT,
+            0,
+            WindowClass::INPUT_OUTPUT,
+            x11rb::COPY_FROM_PARENT,
+            &CreateWindowAux::new().background_pixel(white),
+        )
+        .map_err(|err| format!("Failed to create calibration window: {}", err))?;
+        conn.change_property8(
+            PropMode::REPLACE,
+            window,
+            AtomEnum::WM_NAME,
+            AtomEnum::STRING,
+            b"shake-cursor calibration",
+        )
+        .map_err(|err| format!("Failed to set window title: {}", err))?;
+
+        let font = conn.generate_id()
+            .map_err(|err| format!("Failed to allocate font ID: {}", err))?;
+        conn.open_font(font, b"fixed")
+            .map_err(|err| format!("Failed to open font: {}", err))?;
+        let gc = conn.generate_id()
+            .map_err(|err| format!("Failed to allocate GC ID: {}", err))?;
+        create_gc(&conn, gc, window, &CreateGCAux::new().foreground(black).background(white).font(font))
+            .map_err(|err| format!("Failed to create GC: {}", err))?;
+        conn.close_font(font)
+            .map_err(|err| format!("Failed to close font: {}", err))?;
+
+        conn.map_window(window)
+            .map_err(|err| format!("Failed to map calibration window: {}", err))?;
+        conn.flush()
+            .map_err(|err| format!("Failed to flush X connection: {}", err))?;
+        Ok(Self { conn, window, gc })
+    }
+
+    /// Replace the window's text. Called on every redraw, which also
+    /// covers Expose, so events on this connection are just discarded.
+    fn show(&self, lines: &[String]) {
+        while let Ok(Some(_)) = self.conn.poll_for_event() {}
+
+        let _ = self.conn.clear_area(false, self.window, 0, 0, 0, 0);
+        for (index, line) in lines.iter().enumerate() {
+            let y = 30 + 24 * index as i16;
+            let _ = self.conn.image_text8(self.window, self.gc, 20, y, line.as_bytes());
+        }
+        let _ = self.conn.flush();
+    }
+}
+
+impl Drop for PromptWindow {
+    fn drop(&mut self) {
+        let _ = self.conn.destroy_window(self.window);
+        let _ = self.conn.flush();
+    }
+}
+
+/// Run `shake-cursor calibrate` with the arguments after the subcommand
+/// name. Returns the process exit code.
+pub fn run(args: &[String]) -> i32 {
+    let options = match parse(args) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return 0;
+        }
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, USAGE);
+            return 2;
+        }
+    };
+
+    match calibrate(&options) {
+        Ok(()) => 0,
+        Err(err) => {
+            eprintln!("Calibration failed: {}", err);
+            1
+        }
+    }
+}
+
+/// Parse the arguments; None if help was asked for.
+fn parse(args: &[String]) -> Result<Option<Options>, String> {
+    let mut options = Options { output: None, trace: None, dry_run: false };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--output" => {
+                let path = args.next()
+                    .ok_or_else(|| "--output requires a value".to_string())?;
+                options.output = Some(PathBuf::from(path));
+            }
+            "--trace" => {
+                let path = args.next()
+                    .ok_or_else(|| "--trace requires a value".to_string())?;
+                options.trace = Some(PathBuf::from(path));
+            }
+            "--dry-run" => options.dry_run = true,
+            "-h" | "--help" => return Ok(None),
+            other => return Err(format!("Unknown argument '{}'", other)),
+        }
+    }
+    Ok(Some(options))
+}
+
+fn calibrate(options: &Options) -> Result<(), String> {
+    let output = match &options.output {
+        Some(path) => path.clone(),
+        None => Config::default_path().ok_or_else(|| "Cannot find the config directory".to_string())?,
+    };
+
+    // Start from the existing config, so its time window is the one the
+    // statistics are computed over and the thresholds are written for
+    let base = if output.exists() { Config::load(&output)? } else { Config::default() };
+
+    let recording = record(&Config { hotkey_enabled: false, ..base.clone() })?;
+
+    let normal = detector::window_stats(&recording.events[recording.normal.clone()], base.time_window_ms);
+    let normal: Vec<MotionStats> = normal.into_iter().filter(|stats| stats.time_span_ms > 0).collect();
+    if normal.is_empty() {
+        return Err("No pointer movement was recorded in the first step".to_string());
+    }
+
+    // Each shake is represented by its window with the most reversals
+    let shakes: Vec<MotionStats> = recording
+        .shakes
+        .iter()
+        .filter_map(|range| {
+            detector::window_stats(&recording.events[range.clone()], base.time_window_ms)
+                .into_iter()
+                .filter(|stats| stats.reversals() > 0)
+                .max_by(|a, b| a.reversals().cmp(&b.reversals()).then(a.velocity.total_cmp(&b.velocity)))
+        })
+        .collect();
+    if shakes.len() < MIN_SHAKES {
+        return Err(format!(
+            "Only {} of {} shakes were recorded, at least {} are needed",
+            shakes.len(),
+            SHAKES,
+            MIN_SHAKES
+        ));
+    }
+
+    let normal_reversals: Vec<f64> = normal.iter().map(|stats| stats.reversals() as f64).collect();
+    let normal_velocity: Vec<f64> = normal.iter().map(|stats| stats.velocity).collect();
+    let shake_reversals: Vec<f64> = shakes.iter().map(|stats| stats.reversals() as f64).collect();
+    let shake_velocity: Vec<f64> = shakes.iter().map(|stats| stats.velocity).collect();
+
+    println!("Window statistics over {}ms:", base.time_window_ms);
+    println!("                      p50     p90     p99     max");
+    print_distribution("normal reversals", &normal_reversals);
+    print_distribution("normal velocity", &normal_velocity);
+    println!("                      min  median     max");
+    print_range("shake reversals", &shake_reversals);
+    print_range("shake velocity", &shake_velocity);
+    println!();
+
+    if overlaps(&normal_reversals, &shake_reversals) {
+        println!("Warning: your shakes reverse direction no more often than your normal movement.");
+    }
+    if overlaps(&normal_velocity, &shake_velocity) {
+        println!("Warning: your shakes are no faster than your normal movement.");
+    }
+    let (min_reversals, min_velocity) = derive_thresholds(&normal, &shakes);
+
+    let config = Config { min_reversals, min_velocity, ..base };
+    let thresholds = Thresholds::of(&config);
+    let false_starts = normal.iter().filter(|stats| stats.is_shake(&thresholds)).count();
+    let detected = shakes.iter().filter(|stats| stats.is_shake(&thresholds)).count();
+    println!("min_reversals = {}", config.min_reversals);
+    println!("min_velocity = {}", config.min_velocity);
+    println!(
+        "With these, {} of {} shakes are detected and {:.1}% of normal movement windows look like a shake.",
+        detected,
+        shakes.len(),
+        100.0 * false_starts as f64 / normal.len() as f64
+    );
+
+    if let Some(path) = &options.trace {
+        write_trace(path, &config, &recording)?;
+        println!("Saved the recording to {}", path.display());
+    }
+
+    if !options.dry_run {
+        config::update_file(
+            &output,
+            &[
+                ("min_reversals", config.min_reversals.to_string()),
+                ("min_velocity", config.min_velocity.to_string()),
+            ],
+        )?;
+        println!("Wrote {}. Run `shake-cursor ctl reload` to apply it.", output.display());
+    }
+    Ok(())
+}
+
+/// Thresholds that separate normal movement from the shakes, from their
+/// window statistics (neither may be empty): (min_reversals, min_velocity).
+///
+/// Each threshold lies between the normal movement's 99th percentile and
+/// the weakest shake. Reversals are counts, so halfway between; velocities
+/// span orders of magnitude, so the geometric mean. Where the two overlap,
+/// the threshold is set just below the weakest shake, so every recorded
+/// shake is still detected.
+fn derive_thresholds(normal: &[MotionStats], shakes: &[MotionStats]) -> (u32, f64) {
+    let reversals = |stats: &[MotionStats]| -> Vec<f64> {
+        stats.iter().map(|stats| stats.reversals() as f64).collect()
+    };
+    let velocity = |stats: &[MotionStats]| -> Vec<f64> { stats.iter().map(|stats| stats.velocity).collect() };
+
+    let (normal_reversals, shake_reversals) = (reversals(normal), reversals(shakes));
+    let normal_max = percentile(&normal_reversals, NORMAL_PERCENTILE) as u32;
+    let shake_min = percentile(&shake_reversals, 0.0) as u32;
+    let min_reversals = if overlaps(&normal_reversals, &shake_reversals) {
+        shake_min.max(2)
+    } else {
+        ((normal_max + 1 + shake_min) / 2).max(2)
+    };
+
+    let (normal_velocity, shake_velocity) = (velocity(normal), velocity(shakes));
+    let normal_max = percentile(&normal_velocity, NORMAL_PERCENTILE);
+    let shake_min = percentile(&shake_velocity, 0.0);
+    let min_velocity = if overlaps(&normal_velocity, &shake_velocity) {
+        (shake_min * 0.9 / 10.0).round() * 10.0
+    } else {
+        ((normal_max.max(1.0) * shake_min).sqrt() / 10.0).round() * 10.0
+    };
+
+    (min_reversals, min_velocity)
+}
+
+/// True if the weakest shake is no stronger than the normal movement's
+/// 99th percentile.
+fn overlaps(normal: &[f64], shakes: &[f64]) -> bool {
+    percentile(shakes, 0.0) <= percentile(normal, NORMAL_PERCENTILE)
+}
+
+/// Walk the user through the steps, collecting motion from the X11
+/// backend until each step's time is up.
+fn record(config: &Config) -> Result<Recording, String> {
+    let mut backend = registry::select(Some("x11"), config)?;
+    let prompt = PromptWindow::open();
+    let result = prompt.and_then(|prompt| {
+        // Wake next_event() regularly so the countdown keeps running
+        // while the pointer is still
+        notify::start_timer(REDRAW_INTERVAL, backend.waker());
+
+        let mut steps = vec![(Step::Countdown, COUNTDOWN_DURATION), (Step::Normal, NORMAL_DURATION)];
+        for number in 1..=SHAKES {
+            steps.push((Step::Rest(number), REST_DURATION));
+            steps.push((Step::Shake(number), SHAKE_DURATION));
+        }
+
+        let mut recording = Recording { events: Vec::new(), normal: 0..0, shakes: Vec::new() };
+        for (step, duration) in steps {
+            let start = recording.events.len();
+            record_step(backend.as_mut(), &prompt, &step, duration, &mut recording.events)?;
+            match step {
+                Step::Normal => recording.normal = start..recording.events.len(),
+                Step::Shake(_) => recording.shakes.push(start..recording.events.len()),
+                Step::Countdown | Step::Rest(_) => {}
+            }
+        }
+        prompt.show(&["Done.".to_string()]);
+        Ok(recording)
+    });
+    backend.disconnect();
+    result
+}
+
+fn record_step(
+    backend: &mut dyn DisplayBackend,
+    prompt: &PromptWindow,
+    step: &Step,
+    duration: Duration,
+    events: &mut Vec<MotionEvent>,
+) -> Result<(), String> {
+    let deadline = Instant::now() + duration;
+    loop {
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Ok(());
+        }
+        let [first, second] = step.instructions();
+        prompt.show(&[first, second, format!("{}s", left.as_secs() + 1)]);
+
+        match backend.next_event() {
+            Some(InputEvent::Motion(event)) => events.push(event),
+            Some(InputEvent::Hotkey { .. } | InputEvent::Interrupted) => {}
+            None => return Err("Display server connection lost".to_string()),
+        }
+    }
+}
+
+/// Save the recording as a trace, with each shake step labelled and the
+/// decisions of the calibrated config, so `analyze` can check it. The
+/// events run through a Controller, so the recorded states are the ones
+/// the daemon would have gone through.
+fn write_trace(path: &Path, config: &Config, recording: &Recording) -> Result<(), String> {
+    // Only the enlarge effect runs without a display, and adaptive mode
+    // would learn from the recording
+    let config = Config { effects: vec![LocateEffect::Enlarge], adaptive_enabled: false, ..config.clone() };
+    let mut writer = TraceWriter::create(path, &config)?;
+    for range in recording.shakes.iter().filter(|range| !range.is_empty()) {
+        writer.write_label(recording.events[range.start].timestamp, recording.events[range.end - 1].timestamp);
+    }
+
+    let mut controller = Controller::new(
+        Box::new(ScriptBackend::new(recording.events.iter().copied())),
+        Box::new(SystemClock::start()),
+        config,
+        effect::build_chain,
+    );
+    controller.record_to(writer);
+    while let Some(event) = controller.next_event() {
+        controller.handle(event, false);
+    }
+    // Flushes the trace
+    controller.shutdown();
+    Ok(())
+}
+
+/// The value below which `fraction` of the samples lie.
+fn percentile(samples: &[f64], fraction: f64) -> f64 {
+    let mut sorted = samples.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let index = ((sorted.len() - 1) as f64 * fraction).round() as usize;
+    sorted[index]
+}
+
+fn print_distribution(name: &str, samples: &[f64]) {
+    println!(
+        "  {:<18}{:>7.0} {:>7.0} {:>7.0} {:>7.0}",
+        name,
+        percentile(samples, 0.5),
+        percentile(samples, 0.9),
+        percentile(samples, NORMAL_PERCENTILE),
+        percentile(samples, 1.0)
+    );
+}
+
+fn print_range(name: &str, samples: &[f64]) {
+    println!(
+        "  {:<18}{:>7.0} {:>7.0} {:>7.0}",
+        name,
+        percentile(samples, 0.0),
+        percentile(samples, 0.5),
+        percentile(samples, 1.0)
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(reversals: u32, velocity: f64) -> MotionStats {
+        MotionStats { x_reversals: reversals, y_reversals: 0, velocity, time_span_ms: 500 }
+    }
+
+    #[test]
+    fn percentile_picks_the_nearest_rank() {
+        let samples = [5.0, 1.0, 3.0, 2.0, 4.0];
+        assert_eq!(percentile(&samples, 0.0), 1.0);
+        assert_eq!(percentile(&samples, 0.5), 3.0);
+        assert_eq!(percentile(&samples, 0.6), 3.0);
+        assert_eq!(percentile(&samples, 0.9), 5.0);
+        assert_eq!(percentile(&samples, 1.0), 5.0);
+        assert_eq!(percentile(&[7.0], NORMAL_PERCENTILE), 7.0);
+    }
+
+    #[test]
+    fn separable_recordings_get_thresholds_between_them() {
+        let normal: Vec<MotionStats> = (0..200).map(|index| stats(index % 2, 100.0 + (index % 4) as f64 * 100.0)).collect();
+        let shakes = [stats(6, 1600.0), stats(8, 2500.0), stats(7, 2000.0)];
+
+        // Halfway between 1 and 6 reversals, the geometric mean of 400 and 1600 px/s
+        let (min_reversals, min_velocity) = derive_thresholds(&normal, &shakes);
+        assert_eq!((min_reversals, min_velocity), (4, 800.0));
+
+        let config = Config { min_reversals, min_velocity, ..Config::default() };
+        let thresholds = Thresholds::of(&config);
+        assert!(shakes.iter().all(|stats| stats.is_shake(&thresholds)));
+        assert!(!normal.iter().any(|stats| stats.is_shake(&thresholds)));
+    }
+
+    #[test]
+    fn overlapping_recordings_keep_every_shake() {
+        let normal: Vec<MotionStats> = (0..200).map(|index| stats(index % 6, 500.0 + (index % 6) as f64 * 500.0)).collect();
+        let shakes = [stats(3, 1000.0), stats(9, 4000.0)];
+        assert!(overlaps(&[5.0, 1.0], &[3.0, 9.0]));
+
+        // Just below the weakest shake
+        let (min_reversals, min_velocity) = derive_thresholds(&normal, &shakes);
+        assert_eq!((min_reversals, min_velocity), (3, 900.0));
+
+        // Never below two reversals, a single back-and-forth
+        assert_eq!(derive_thresholds(&normal, &[stats(1, 1000.0)]).0, 2);
+    }
+}
@@ -0,0 +1,352 @@
+use std::path::PathBuf;
+
+use shake_cursor_core::config::{CustomCursor, Hotkey, LocateEffect};
+
+/// Slowest and fastest `--replay-speed` factors, besides 0. Dividing the
+/// recorded gaps by a factor much closer to 0 overflows a Duration.
+const MIN_REPLAY_SPEED: f64 = 0.001;
+const MAX_REPLAY_SPEED: f64 = 1_000_000.0;
+
+/// What the binary was asked to do.
+pub enum Command {
+    /// Run the shake detection daemon (the default).
+    Daemon,
+
+    /// Print what each backend probe found and exit.
+    ListBackends,
+
+    /// Send one request to a running daemon's control socket and exit.
+    /// Holds the ctl arguments, e.g. ["set", "min_reversals", "4"].
+    Ctl(Vec<String>),
+
+    /// Run a recorded trace through the detector offline and exit.
+    /// Holds the analyze arguments, parsed by the analyze module.
+    Analyze(Vec<String>),
+
+    /// Record normal movement and shakes, and write thresholds that
+    /// separate them. Holds the calibrate arguments.
+    Calibrate(Vec<String>),
+
+    /// Run synthetic gestures through the detector and exit. Holds the
+    /// simulate arguments.
+    Simulate(Vec<String>),
+
+    /// Print usage and exit.
+    Help,
+}
+
+/// Which configuration a `--replay` runs with (`--replay-config`).
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ReplayConfig {
+    /// The configuration in the trace header, so the replay decides as
+    /// the recording did.
+    Recorded,
+
+    /// The config file and command line, to try other settings.
+    Current,
+}
+
+/// Parsed command-line arguments.
+pub struct Args {
+    /// The selected command.
+    pub command: Command,
+
+    /// Config file from `--config`, instead of the default location.
+    pub config_path: Option<PathBuf>,
+
+    /// Backend name from `--backend`, overriding auto-selection.
+    pub backend: Option<String>,
+
+    /// Locate effect chain from `--effect`, overriding the config default.
+    pub effects: Option<Vec<LocateEffect>>,
+
+    /// Custom enlarged cursor from `--cursor-image` and `--cursor-hotspot`.
+    pub enlarged_cursor: Option<CustomCursor>,
+
+    /// Locate hotkey from `--hotkey`, which also enables it.
+    pub hotkey: Option<Hotkey>,
+
+    /// Start with shake detection paused (`--paused`).
+    pub paused: bool,
+
+    /// Take over from a daemon already running on this display (`--replace`).
+    pub replace: bool,
+
+    /// Trace file to write every motion event and decision to (`--record`).
+    pub record: Option<PathBuf>,
+
+    /// Trace file to play back instead of a display server (`--replay`).
+    pub replay: Option<PathBuf>,
+
+    /// Playback speed factor for `--replay` (`--replay-speed`).
+    pub replay_speed: f64,
+
+    /// Configuration for `--replay` (`--replay-config`).
+    pub replay_config: ReplayConfig,
+}
+
+pub const USAGE: &str = "\
+Usage: shake-cursor [OPTIONS]
+       shake-cursor ctl [--replay] <COMMAND>
+       shake-cursor analyze [OPTIONS] <TRACE>
+       shake-cursor calibrate [OPTIONS]
+       shake-cursor simulate [OPTIONS] <GESTURE>...
+
+Options:
+  --config <PATH>    Config file (default: $XDG_CONFIG_HOME/shake-cursor/config)
+  --backend <NAME>   Use the named display backend instead of auto-selecting
+  --effect <LIST>    Comma-separated locate effects to chain: enlarge, ring,
+                     crosshair, spotlight (default: enlarge)
+  --cursor-image <PATH>
+                     PNG or Xcursor file to show as the enlarged cursor (X11)
+  --cursor-hotspot <X,Y>
+                     Hotspot of --cursor-image in image pixels (default: 0,0)
+  --hotkey <COMBO>   Show the locate effects on a key combination, e.g.
+                     ctrl (tap Ctrl alone) or super+l (X11)
+  --paused           Start with shake detection paused
+  --replace          Take over from a daemon already running on this display
+  --record <PATH>    Write every motion event and detector decision to a
+                     JSON Lines trace file
+  --replay <PATH>    Play back a recorded trace instead of a display server
+  --replay-speed <FACTOR>
+                     Replay speed: 1 is real time, 0 as fast as possible,
+                     otherwise 0.001 to 1000000 (default: 1)
+  --replay-config <recorded|current>
+                     Replay with the config recorded in the trace, or with
+                     the config file and options (default: recorded)
+  --list-backends    Show what each backend probe found and exit
+  -h, --help         Show this help and exit
+
+Signals:
+  SIGHUP                  Re-read the config file
+  SIGUSR1                 Toggle pause
+  SIGUSR2                 Show the locate effects once, for cooldown_ms
+  SIGQUIT                 Print metrics to stderr, instead of dumping core
+
+Control commands (sent to the running daemon, or with --replay to the
+running --replay):
+  ctl status              Show cursor state and whether detection is paused
+  ctl pause | resume      Pause or resume shake detection
+  ctl trigger             Show the locate effects now, as if shaken
+  ctl dismiss             End the locate effects now and restore the cursor
+  ctl reload              Re-read the config file
+  ctl get-config          Print the active configuration
+  ctl metrics             Print event, shake and latency metrics
+  ctl set <KEY> <VALUE>   Change one configuration value
+
+Run `shake-cursor analyze --help`, `shake-cursor calibrate --help` or
+`shake-cursor simulate --help` for their options.";
+
+/// Parse command-line arguments (without the program name).
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Args, String> {
+    let mut parsed = Args {
+        command: Command::Daemon,
+        config_path: None,
+        backend: None,
+        effects: None,
+        enlarged_cursor: None,
+        hotkey: None,
+        paused: false,
+        replace: false,
+        record: None,
+        replay: None,
+        replay_speed: 1.0,
+        replay_config: ReplayConfig::Recorded,
+    };
+    let mut hotspot = None;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "ctl" => {
+                let rest: Vec<String> = args.by_ref().collect();
+                if rest.is_empty() {
+                    return Err("ctl requires a command".to_string());
+                }
+                parsed.command = Command::Ctl(rest);
+            }
+            "analyze" => parsed.command = Command::Analyze(args.by_ref().collect()),
+            "calibrate" => parsed.command = Command::Calibrate(args.by_ref().collect()),
+            "simulate" => parsed.command = Command::Simulate(args.by_ref().collect()),
+            "--config" => {
+                let path = args.next()
+                    .ok_or_else(|| "--config requires a value".to_string())?;
+                parsed.config_path = Some(PathBuf::from(path));
+            }
+            "--backend" => {
+                let name = args.next()
+                    .ok_or_else(|| "--backend requires a value".to_string())?;
+                parsed.backend = Some(name);
+            }
+            "--effect" => {
+                let name = args.next()
+                    .ok_or_else(|| "--effect requires a value".to_string())?;
+                parsed.effects = Some(LocateEffect::parse_list(&name)?);
+            }
+            "--cursor-image" => {
+                let path = args.next()
+                    .ok_or_else(|| "--cursor-image requires a value".to_string())?;
+                parsed.enlarged_cursor = Some(CustomCursor {
+                    path: PathBuf::from(path),
+                    hotspot: None,
+                });
+            }
+            "--cursor-hotspot" => {
+                let value = args.next()
+                    .ok_or_else(|| "--cursor-hotspot requires a value".to_string())?;
+                hotspot = Some(parse_hotspot(&value)?);
+            }
+            "--hotkey" => {
+                let combo = args.next()
+                    .ok_or_else(|| "--hotkey requires a value".to_string())?;
+                parsed.hotkey = Some(Hotkey::parse(&combo)?);
+            }
+            "--paused" => parsed.paused = true,
+            "--replace" => parsed.replace = true,
+            "--record" => {
+                let path = args.next()
+                    .ok_or_else(|| "--record requires a value".to_string())?;
+                parsed.record = Some(PathBuf::from(path));
+            }
+            "--replay" => {
+                let path = args.next()
+                    .ok_or_else(|| "--replay requires a value".to_string())?;
+                parsed.replay = Some(PathBuf::from(path));
+            }
+            "--replay-speed" => {
+                let value = args.next()
+                    .ok_or_else(|| "--replay-speed requires a value".to_string())?;
+                parsed.replay_speed = value.parse().ok()
+                    .filter(|speed: &f64| *speed == 0.0 || (MIN_REPLAY_SPEED..=MAX_REPLAY_SPEED).contains(speed))
+                    .ok_or_else(|| format!(
+                        "Invalid replay speed '{}' (expected 0, or {} to {})",
+                        value, MIN_REPLAY_SPEED, MAX_REPLAY_SPEED
+                    ))?;
+            }
+            "--replay-config" => {
+                let value = args.next()
+                    .ok_or_else(|| "--replay-config requires a value".to_string())?;
+                parsed.replay_config = match value.as_str() {
+                    "recorded" => ReplayConfig::Recorded,
+                    "current" => ReplayConfig::Current,
+                    _ => return Err(format!("Invalid replay config '{}' (expected recorded or current)", value)),
+                };
+            }
+            "--list-backends" => parsed.command = Command::ListBackends,
+            "-h" | "--help" => parsed.command = Command::Help,
+            other => {
+                if let Some(name) = other.strip_prefix("--backend=") {
+                    parsed.backend = Some(name.to_string());
+                } else if let Some(name) = other.strip_prefix("--effect=") {
+                    parsed.effects = Some(LocateEffect::parse_list(name)?);
+                } else {
+                    return Err(format!("Unknown argument '{}'", other));
+                }
+            }
+        }
+    }
+
+    if parsed.replay.is_some() && parsed.backend.is_some() {
+        return Err("--replay and --backend cannot be combined".to_string());
+    }
+
+    match (&mut parsed.enlarged_cursor, hotspot) {
+        (Some(cursor), hotspot) => cursor.hotspot = hotspot,
+        (None, Some(_)) => return Err("--cursor-hotspot requires --cursor-image".to_string()),
+        (None, None) => {}
+    }
+
+    Ok(parsed)
+}
+
+/// Parse a hotspot given as "X,Y".
+fn parse_hotspot(value: &str) -> Result<(u32, u32), String> {
+    let (x, y) = value.split_once(',')
+        .ok_or_else(|| format!("Invalid hotspot '{}' (expected X,Y)", value))?;
+    let x = x.trim().parse().map_err(|_| format!("Invalid hotspot X '{}'", x))?;
+    let y = y.trim().parse().map_err(|_| format!("Invalid hotspot Y '{}'", y))?;
+    Ok((x, y))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_args(args: &[&str]) -> Result<Args, String> {
+        parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn no_arguments_run_the_daemon_with_defaults() {
+        let args = parse_args(&[]).unwrap();
+        assert!(matches!(args.command, Command::Daemon));
+        assert!(args.config_path.is_none() && args.backend.is_none() && args.effects.is_none());
+        assert!(!args.paused && !args.replace);
+        assert_eq!(args.replay_speed, 1.0);
+        assert_eq!(args.replay_config, ReplayConfig::Recorded);
+    }
+
+    #[test]
+    fn options_are_parsed() {
+        let args = parse_args(&[
+            "--effect", "ring,spotlight", "--paused", "--replace",
+            "--config", "/tmp/config", "--replay", "trace.jsonl", "--replay-speed", "0",
+            "--replay-config", "current",
+        ])
+        .unwrap();
+        assert_eq!(args.effects, Some(vec![LocateEffect::Ring, LocateEffect::Spotlight]));
+        assert!(args.paused && args.replace);
+        assert_eq!(args.config_path, Some(PathBuf::from("/tmp/config")));
+        assert_eq!(args.replay, Some(PathBuf::from("trace.jsonl")));
+        assert_eq!(args.replay_speed, 0.0);
+        assert_eq!(args.replay_config, ReplayConfig::Current);
+
+        for backend in [&["--backend", "wayland"][..], &["--backend=wayland"]] {
+            assert_eq!(parse_args(backend).unwrap().backend.as_deref(), Some("wayland"));
+        }
+    }
+
+    #[test]
+    fn subcommands_take_the_remaining_arguments() {
+        let args = parse_args(&["--config", "c", "ctl", "set", "min_reversals", "4"]).unwrap();
+        let Command::Ctl(rest) = args.command else { panic!("not ctl") };
+        assert_eq!(rest, ["set", "min_reversals", "4"]);
+        assert_eq!(args.config_path, Some(PathBuf::from("c")));
+
+        let args = parse_args(&["analyze", "--sweep", "trace.jsonl"]).unwrap();
+        assert!(matches!(args.command, Command::Analyze(rest) if rest == ["--sweep", "trace.jsonl"]));
+        assert!(matches!(parse_args(&["--list-backends"]).unwrap().command, Command::ListBackends));
+        assert!(matches!(parse_args(&["-h"]).unwrap().command, Command::Help));
+    }
+
+    #[test]
+    fn invalid_arguments_are_rejected() {
+        for (args, expected) in [
+            (&["ctl"][..], "ctl requires a command"),
+            (&["--backend"], "--backend requires a value"),
+            (&["--frobnicate"], "Unknown argument '--frobnicate'"),
+            (&["--replay-speed", "-1"], "Invalid replay speed '-1' (expected 0, or 0.001 to 1000000)"),
+            (&["--replay-speed", "1e-20"], "Invalid replay speed '1e-20' (expected 0, or 0.001 to 1000000)"),
+            (&["--replay-speed", "NaN"], "Invalid replay speed 'NaN' (expected 0, or 0.001 to 1000000)"),
+            (&["--replay-speed", "inf"], "Invalid replay speed 'inf' (expected 0, or 0.001 to 1000000)"),
+            (&["--replay-config", "both"], "Invalid replay config 'both' (expected recorded or current)"),
+            (&["--replay", "t", "--backend", "x11"], "--replay and --backend cannot be combined"),
+            (&["--cursor-hotspot", "1,2"], "--cursor-hotspot requires --cursor-image"),
+        ] {
+            assert_eq!(parse_args(args).err().as_deref(), Some(expected), "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn hotspot_is_parsed_and_follows_the_image() {
+        assert_eq!(parse_hotspot("3,17"), Ok((3, 17)));
+        assert_eq!(parse_hotspot(" 3 , 17 "), Ok((3, 17)));
+        assert_eq!(parse_hotspot("3"), Err("Invalid hotspot '3' (expected X,Y)".to_string()));
+        assert_eq!(parse_hotspot("-1,2"), Err("Invalid hotspot X '-1'".to_string()));
+        assert_eq!(parse_hotspot("1,y"), Err("Invalid hotspot Y 'y'".to_string()));
+
+        let args = parse_args(&["--cursor-hotspot", "4,5", "--cursor-image", "big.png"]).unwrap();
+        let cursor = args.enlarged_cursor.unwrap();
+        assert_eq!((cursor.path, cursor.hotspot), (PathBuf::from("big.png"), Some((4, 5))));
+    }
+}
@@ -0,0 +1,510 @@
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use serde_json::{json, Map, Value};
+
+use shake_cursor_core::backend::Waker;
+use shake_cursor_core::config::Config;
+use shake_cursor_core::metrics::{Histogram, Metrics, SharedMetrics};
+
+use crate::instance::runtime_path;
+
+/// Config keys the display backend only reads when it connects: the
+/// hotkey grab and the custom cursor image. A running daemon can't change
+/// them, so `set` rejects them and a reload keeps their running values.
+const RESTART_KEYS: &[&str] = &["hotkey_enabled", "hotkey", "enlarged_cursor", "enlarged_cursor_hotspot"];
+
+/// Work the event loop has to do on behalf of a control client, because
+/// it needs the backend or the detector, which only the loop owns.
+pub enum ControlAction {
+    /// Show the locate effects now, as if a shake had been detected.
+    Trigger,
+
+    /// Re-read the config file and apply it.
+    Reload,
+
+    /// `config` in the shared state was changed with `set`; apply it.
+    ApplyConfig,
+
+    /// Detection was paused; stop any running effect.
+    Pause,
+
+    /// End the running effect now and restore the cursor.
+    Dismiss,
+
+    /// A termination signal arrived; restore the cursor and exit.
+    Shutdown,
+}
+
+/// State shared between the event loop and the control front ends
+/// (control socket, D-Bus, signals).
+///
+/// Queries (status, get-config, metrics) are answered straight from here so they
+/// never wait on the event loop. Commands that need the loop queue a
+/// ControlAction and wake the loop, which drains the queue right away.
+pub struct ControlState {
+    /// True while shake detection is paused.
+    pub paused: bool,
+
+    /// Name of the current CursorState, kept up to date by the event loop.
+    pub cursor_state: &'static str,
+
+    /// The configuration the daemon is running with.
+    pub config: Config,
+
+    /// The controller's metrics, which it updates in place.
+    pub metrics: SharedMetrics,
+
+    /// Actions waiting for the event loop, oldest first.
+    pub pending: Vec<ControlAction>,
+
+    /// Wakes the event loop from the backend's next_event().
+    pub waker: Waker,
+}
+
+impl ControlState {
+    /// Pause shake detection, ending any running effect.
+    pub fn pause(&mut self) {
+        if !self.paused {
+            self.paused = true;
+            self.pending.push(ControlAction::Pause);
+            (self.waker)();
+        }
+    }
+
+    pub fn resume(&mut self) {
+        if self.paused {
+            self.paused = false;
+            (self.waker)();
+        }
+    }
+
+    pub fn trigger(&mut self) {
+        self.pending.push(ControlAction::Trigger);
+        (self.waker)();
+    }
+
+    pub fn dismiss(&mut self) {
+        self.pending.push(ControlAction::Dismiss);
+        (self.waker)();
+    }
+
+    pub fn shutdown(&mut self) {
+        self.pending.push(ControlAction::Shutdown);
+        (self.waker)();
+    }
+
+    pub fn reload(&mut self) {
+        self.pending.push(ControlAction::Reload);
+        (self.waker)();
+    }
+
+    /// Change one config parameter, as Config::set() does, and queue it
+    /// for the event loop. A bad value, or a change to a key that needs a
+    /// restart, leaves the config untouched.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let mut config = self.config.clone();
+        config.set(key, value)?;
+        if let Some(key) = restart_changes(&self.config, &config).first() {
+            return Err(format!("{} can only be changed by restarting the daemon", key));
+        }
+        self.config = config;
+        self.pending.push(ControlAction::ApplyConfig);
+        (self.waker)();
+        Ok(())
+    }
+}
+
+pub type SharedControl = Arc<Mutex<ControlState>>;
+
+/// Keep the running values of the keys only a restart applies, warning
+/// about each one a reloaded `config` would have changed.
+pub fn keep_restart_keys(running: &Config, config: &mut Config) {
+    for key in restart_changes(running, config) {
+        log::warn!("{} changed in the config file; restart the daemon to apply it.", key);
+    }
+    config.hotkey_enabled = running.hotkey_enabled;
+    config.hotkey = running.hotkey.clone();
+    config.enlarged_cursor = running.enlarged_cursor.clone();
+}
+
+/// The RESTART_KEYS whose values differ between the two configs.
+fn restart_changes(running: &Config, config: &Config) -> Vec<&'static str> {
+    running
+        .entries()
+        .into_iter()
+        .zip(config.entries())
+        .filter(|((key, old), (_, new))| RESTART_KEYS.contains(key) && old != new)
+        .map(|((key, _), _)| key)
+        .collect()
+}
+
+/// Path of the control socket: `$XDG_RUNTIME_DIR/shake-cursor-<display>.sock`,
+/// one per display like the instance lock. `ctl` finds the daemon of the
+/// display in its own DISPLAY or WAYLAND_DISPLAY.
+///
+/// A `--replay` run has no display, and must not take the socket of the
+/// daemon that has one: it listens on `shake-cursor-replay.sock`, which
+/// `ctl --replay` talks to.
+pub fn socket_path(replay: bool) -> Result<PathBuf, String> {
+    let path = runtime_path("sock")?;
+    Ok(if replay { path.with_file_name("shake-cursor-replay.sock") } else { path })
+}
+
+/// Bind the control socket and serve clients on a background thread.
+///
+/// A leftover socket from a crashed daemon is removed; one that still
+/// accepts connections belongs to a running daemon and is left alone.
+pub fn start_server(state: SharedControl, replay: bool) -> Result<PathBuf, String> {
+    let path = socket_path(replay)?;
+
+    if path.exists() {
+        if UnixStream::connect(&path).is_ok() {
+            return Err(format!("{} is in use by another daemon", path.display()));
+        }
+        fs::remove_file(&path)
+            .map_err(|err| format!("Failed to remove stale {}: {}", path.display(), err))?;
+    }
+
+    let listener = UnixListener::bind(&path)
+        .map_err(|err| format!("Failed to bind {}: {}", path.display(), err))?;
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let state = Arc::clone(&state);
+                    thread::spawn(move || serve_client(stream, state));
+                }
+                Err(err) => log::warn!("Control socket accept failed: {}", err),
+            }
+        }
+    });
+
+    Ok(path)
+}
+
+/// Remove the control socket on shutdown.
+pub fn remove_socket(path: &Path) {
+    let _ = fs::remove_file(path);
+}
+
+/// Handle one client: each line is a JSON request, each reply one JSON line.
+fn serve_client(stream: UnixStream, state: SharedControl) {
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else {
+            return;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let reply = match serde_json::from_str::<Value>(&line) {
+            Ok(request) => handle_request(&request, &state),
+            Err(err) => error_reply(&format!("Invalid JSON: {}", err)),
+        };
+
+        if writeln!(writer, "{}", reply).is_err() {
+            return;
+        }
+    }
+}
+
+/// Execute one request against the shared state.
+///
+/// Request:  {"command": "set", "key": "min_reversals", "value": "4"}
+/// Reply:    {"ok": true, ...} or {"ok": false, "error": "..."}
+fn handle_request(request: &Value, state: &SharedControl) -> Value {
+    let Some(command) = request.get("command").and_then(Value::as_str) else {
+        return error_reply("Missing \"command\"");
+    };
+
+    let mut state = state.lock().unwrap();
+    match command {
+        "status" => json!({
+            "ok": true,
+            "state": state.cursor_state,
+            "paused": state.paused,
+        }),
+        "pause" => {
+            state.pause();
+            json!({ "ok": true })
+        }
+        "resume" => {
+            state.resume();
+            json!({ "ok": true })
+        }
+        "trigger" => {
+            state.trigger();
+            json!({ "ok": true })
+        }
+        "dismiss" => {
+            state.dismiss();
+            json!({ "ok": true })
+        }
+        "reload" => {
+            state.reload();
+            json!({ "ok": true })
+        }
+        "get-config" => {
+            let config: Map<String, Value> = state
+                .config
+                .entries()
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), Value::String(value)))
+                .collect();
+            json!({ "ok": true, "config": config })
+        }
+        "metrics" => json!({ "ok": true, "metrics": metrics_json(&state.metrics.lock().unwrap()) }),
+        "set" => {
+            let key = request.get("key").and_then(Value::as_str);
+            let value = request.get("value").and_then(Value::as_str);
+            let (Some(key), Some(value)) = (key, value) else {
+                return error_reply("set requires \"key\" and \"value\"");
+            };
+
+            match state.set(key, value) {
+                Ok(()) => json!({ "ok": true }),
+                Err(err) => error_reply(&err),
+            }
+        }
+        other => error_reply(&format!("Unknown command '{}'", other)),
+    }
+}
+
+/// Counters as numbers, histograms as their count, sum and cumulative
+/// [upper bound, count] buckets (the +Inf bucket is the count).
+fn metrics_json(metrics: &Metrics) -> Value {
+    fn histogram(histogram: &Histogram) -> Value {
+        let buckets: Vec<Value> = histogram
+            .buckets()
+            .into_iter()
+            .filter(|(bound, _)| bound.is_finite())
+            .map(|(bound, count)| json!([bound, count]))
+            .collect();
+        json!({ "count": histogram.count(), "sum": histogram.sum(), "buckets": buckets })
+    }
+
+    json!({
+        "motion_events": metrics.motion_events,
+        "shakes": metrics.shakes,
+        "false_starts": metrics.false_starts,
+        "enlarged_seconds": histogram(&metrics.enlarged_seconds),
+        "set_cursor_size_seconds": histogram(&metrics.set_cursor_size_seconds),
+        "buffer_events": histogram(&metrics.buffer_events),
+        "handle_seconds": histogram(&metrics.handle_seconds),
+    })
+}
+
+fn error_reply(message: &str) -> Value {
+    json!({ "ok": false, "error": message })
+}
+
+/// `shake-cursor ctl ...`: send one request to the running daemon, or
+/// with a leading `--replay` to the running replay, and print its JSON
+/// reply. Returns the process exit code.
+pub fn run_client(args: &[String]) -> i32 {
+    let (replay, args) = match args {
+        [first, rest @ ..] if first == "--replay" => (true, rest),
+        _ => (false, args),
+    };
+    let request = match build_request(args) {
+        Ok(request) => request,
+        Err(err) => {
+            eprintln!("{}", err);
+            return 2;
+        }
+    };
+
+    match send_request(&request, replay) {
+        Ok(reply) => {
+            println!("{}", reply);
+            if reply.get("ok").and_then(Value::as_bool) == Some(true) {
+                0
+            } else {
+                1
+            }
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            1
+        }
+    }
+}
+
+/// Turn `ctl` arguments into a JSON request.
+fn build_request(args: &[String]) -> Result<Value, String> {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match args.as_slice() {
+        ["set", key, value] => Ok(json!({ "command": "set", "key": key, "value": value })),
+        ["set", ..] => Err("Usage: shake-cursor ctl set <KEY> <VALUE>".to_string()),
+        [command @ ("status" | "pause" | "resume" | "trigger" | "dismiss" | "reload" | "get-config" | "metrics")] => {
+            Ok(json!({ "command": command }))
+        }
+        [command, ..] => Err(format!("Unknown ctl command '{}'", command)),
+        [] => Err("ctl requires a command".to_string()),
+    }
+}
+
+fn send_request(request: &Value, replay: bool) -> Result<Value, String> {
+    let path = socket_path(replay)?;
+    let mut stream = UnixStream::connect(&path)
+        .map_err(|err| format!("Cannot reach daemon at {}: {}", path.display(), err))?;
+
+    writeln!(stream, "{}", request)
+        .map_err(|err| format!("Failed to send request: {}", err))?;
+
+    let mut line = String::new();
+    BufReader::new(stream)
+        .read_line(&mut line)
+        .map_err(|err| format!("Failed to read reply: {}", err))?;
+
+    serde_json::from_str(&line).map_err(|err| format!("Invalid reply from daemon: {}", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Shared state whose waker counts its calls.
+    fn state() -> (SharedControl, Arc<AtomicUsize>) {
+        let wakeups = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&wakeups);
+        let state = Arc::new(Mutex::new(ControlState {
+            paused: false,
+            cursor_state: "idle",
+            config: Config::default(),
+            metrics: SharedMetrics::default(),
+            pending: Vec::new(),
+            waker: Arc::new(move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+            }),
+        }));
+        (state, wakeups)
+    }
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn build_request_maps_ctl_arguments() {
+        assert_eq!(build_request(&args("status")).unwrap(), json!({ "command": "status" }));
+        assert_eq!(build_request(&args("get-config")).unwrap(), json!({ "command": "get-config" }));
+        assert_eq!(
+            build_request(&args("set min_reversals 4")).unwrap(),
+            json!({ "command": "set", "key": "min_reversals", "value": "4" })
+        );
+        for line in ["", "set min_reversals", "set a b c", "frobnicate", "status now"] {
+            assert!(build_request(&args(line)).is_err(), "accepted '{}'", line);
+        }
+    }
+
+    #[test]
+    fn commands_queue_actions_and_wake_the_loop() {
+        let (state, wakeups) = state();
+        for command in ["trigger", "dismiss", "reload", "pause", "pause", "resume"] {
+            let reply = handle_request(&json!({ "command": command }), &state);
+            assert_eq!(reply, json!({ "ok": true }), "{}", command);
+        }
+
+        let state = state.lock().unwrap();
+        let pending: Vec<&str> = state
+            .pending
+            .iter()
+            .map(|action| match action {
+                ControlAction::Trigger => "trigger",
+                ControlAction::Dismiss => "dismiss",
+                ControlAction::Reload => "reload",
+                ControlAction::Pause => "pause",
+                ControlAction::ApplyConfig => "apply",
+                ControlAction::Shutdown => "shutdown",
+            })
+            .collect();
+        // Pausing twice queues one Pause
+        assert_eq!(pending, ["trigger", "dismiss", "reload", "pause"]);
+        assert!(!state.paused);
+        assert_eq!(wakeups.load(Ordering::SeqCst), 5);
+    }
+
+    #[test]
+    fn queries_read_the_shared_state() {
+        let (state, wakeups) = state();
+        state.lock().unwrap().paused = true;
+        let reply = handle_request(&json!({ "command": "status" }), &state);
+        assert_eq!(reply, json!({ "ok": true, "state": "idle", "paused": true }));
+
+        let reply = handle_request(&json!({ "command": "get-config" }), &state);
+        assert_eq!(reply["config"]["min_reversals"], "3");
+        let reply = handle_request(&json!({ "command": "metrics" }), &state);
+        assert_eq!(reply["metrics"]["shakes"], 0);
+        assert_eq!(wakeups.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn set_applies_valid_values_only() {
+        let (state, wakeups) = state();
+        let set = |key: &str, value: &str| handle_request(&json!({ "command": "set", "key": key, "value": value }), &state);
+
+        assert_eq!(set("min_reversals", "4"), json!({ "ok": true }));
+        assert_eq!(set("cooldown_ms", "0")["ok"], false);
+        assert_eq!(set("min_velocity", "NaN")["ok"], false);
+        assert_eq!(set("colour", "red")["ok"], false);
+        assert_eq!(handle_request(&json!({ "command": "set", "key": "min_reversals" }), &state)["ok"], false);
+
+        let state = state.lock().unwrap();
+        assert_eq!(state.config.min_reversals, 4);
+        assert_eq!(state.config.cooldown_ms, Config::default().cooldown_ms);
+        assert!(matches!(state.pending[..], [ControlAction::ApplyConfig]));
+        assert_eq!(wakeups.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn keys_needing_a_restart_are_rejected() {
+        let (state, wakeups) = state();
+        let set = |key: &str, value: &str| handle_request(&json!({ "command": "set", "key": key, "value": value }), &state);
+
+        for (key, value) in [("hotkey_enabled", "true"), ("hotkey", "super+l"), ("enlarged_cursor", "/tmp/arrow.png")] {
+            let reply = set(key, value);
+            assert_eq!(reply["ok"], false, "{} = {}", key, value);
+            assert!(reply["error"].as_str().unwrap().contains("restarting"), "{}", reply);
+        }
+        // Setting the running value changes nothing, so it is fine
+        assert_eq!(set("hotkey", "ctrl"), json!({ "ok": true }));
+
+        let state = state.lock().unwrap();
+        assert_eq!(state.config.entries(), Config::default().entries());
+        assert_eq!(wakeups.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn reload_keeps_keys_needing_a_restart() {
+        let running = Config::default();
+        let mut reloaded = Config::default();
+        reloaded.set("hotkey_enabled", "true").unwrap();
+        reloaded.set("enlarged_cursor", "/tmp/arrow.png").unwrap();
+        reloaded.set("min_reversals", "5").unwrap();
+        assert_eq!(restart_changes(&running, &reloaded), ["enlarged_cursor", "hotkey_enabled"]);
+
+        keep_restart_keys(&running, &mut reloaded);
+        assert!(restart_changes(&running, &reloaded).is_empty());
+        assert_eq!(reloaded.min_reversals, 5);
+    }
+
+    #[test]
+    fn malformed_requests_are_rejected() {
+        let (state, _) = state();
+        assert_eq!(handle_request(&json!({}), &state)["ok"], false);
+        assert_eq!(handle_request(&json!({ "command": 3 }), &state)["ok"], false);
+        assert_eq!(handle_request(&json!({ "command": "frobnicate" }), &state)["ok"], false);
+    }
+}
@@ -0,0 +1,430 @@
+use std::fs::{self, File};
+use std::path::Path;
+
+use x11rb::protocol::xproto::ImageOrder;
+
+/// One cursor image (or one frame of an animated cursor) held in memory.
+///
+/// Pixels use the Xcursor/Render convention: premultiplied ARGB packed
+/// into a u32 per pixel, row-major. This is what the Render extension's
+/// CreateCursor expects, so images go to the X server unchanged.
+#[derive(Clone)]
+pub struct CursorImage {
+    /// The size this image was designed for, e.g. 24 for a 24px cursor.
+    /// For Xcursor files this is the image's nominal size; for PNGs it is
+    /// the larger of width and height.
+    pub nominal_size: u32,
+
+    pub width: u32,
+    pub height: u32,
+
+    /// Hotspot (the pixel that is the pointer's actual position).
+    pub xhot: u32,
+    pub yhot: u32,
+
+    /// How long to show this frame before the next, in milliseconds.
+    /// Zero for static cursors.
+    pub delay_ms: u32,
+
+    /// Premultiplied ARGB pixels, width × height of them.
+    pub pixels: Vec<u32>,
+}
+
+impl CursorImage {
+    /// Resample the image to `target_size` (relative to its nominal size),
+    /// scaling the hotspot with it.
+    ///
+    /// Upscaling uses bilinear interpolation and downscaling averages
+    /// every source pixel under each destination pixel, so edges stay
+    /// smooth rather than blocky or aliased. Interpolation happens on
+    /// premultiplied values, which keeps transparent edges from bleeding
+    /// dark fringes.
+    pub fn scaled_to(&self, target_size: u32) -> CursorImage {
+        if target_size == self.nominal_size || self.nominal_size == 0 {
+            return self.clone();
+        }
+
+        let factor = target_size as f64 / self.nominal_size as f64;
+        let width = ((self.width as f64 * factor).round() as u32).max(1);
+        let height = ((self.height as f64 * factor).round() as u32).max(1);
+        let mut pixels = Vec::with_capacity((width * height) as usize);
+
+        for row in 0..height {
+            for col in 0..width {
+                let channels = if factor >= 1.0 {
+                    self.sample_bilinear((col as f64 + 0.5) / factor - 0.5, (row as f64 + 0.5) / factor - 0.5)
+                } else {
+                    self.sample_box(col as f64 / factor, row as f64 / factor, 1.0 / factor)
+                };
+                pixels.push(pack(channels));
+            }
+        }
+
+        CursorImage {
+            nominal_size: target_size,
+            width,
+            height,
+            xhot: ((self.xhot as f64 * factor).round() as u32).min(width - 1),
+            yhot: ((self.yhot as f64 * factor).round() as u32).min(height - 1),
+            delay_ms: self.delay_ms,
+            pixels,
+        }
+    }
+
+    /// Channels (a, r, g, b) of the source pixel, clamped to the edges.
+    fn channels_at(&self, x: i64, y: i64) -> [f64; 4] {
+        let x = x.clamp(0, self.width as i64 - 1) as usize;
+        let y = y.clamp(0, self.height as i64 - 1) as usize;
+        unpack(self.pixels[y * self.width as usize + x])
+    }
+
+    fn sample_bilinear(&self, x: f64, y: f64) -> [f64; 4] {
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+
+        let top_left = self.channels_at(x0, y0);
+        let top_right = self.channels_at(x0 + 1, y0);
+        let bottom_left = self.channels_at(x0, y0 + 1);
+        let bottom_right = self.channels_at(x0 + 1, y0 + 1);
+
+        let mut out = [0.0; 4];
+        for channel in 0..4 {
+            let top = top_left[channel] * (1.0 - fx) + top_right[channel] * fx;
+            let bottom = bottom_left[channel] * (1.0 - fx) + bottom_right[channel] * fx;
+            out[channel] = top * (1.0 - fy) + bottom * fy;
+        }
+        out
+    }
+
+    /// Average the source pixels covered by the square [x, x+span) × [y, y+span).
+    fn sample_box(&self, x: f64, y: f64, span: f64) -> [f64; 4] {
+        let (x_start, x_end) = (x.floor() as i64, ((x + span).ceil() as i64).max(x.floor() as i64 + 1));
+        let (y_start, y_end) = (y.floor() as i64, ((y + span).ceil() as i64).max(y.floor() as i64 + 1));
+
+        let mut sum = [0.0; 4];
+        let mut count = 0.0;
+        for sy in y_start..y_end {
+            for sx in x_start..x_end {
+                let channels = self.channels_at(sx, sy);
+                for channel in 0..4 {
+                    sum[channel] += channels[channel];
+                }
+                count += 1.0;
+            }
+        }
+        sum.map(|value| value / count)
+    }
+}
+
+fn unpack(pixel: u32) -> [f64; 4] {
+    [
+        (pixel >> 24) as f64,
+        ((pixel >> 16) & 0xff) as f64,
+        ((pixel >> 8) & 0xff) as f64,
+        (pixel & 0xff) as f64,
+    ]
+}
+
+fn pack(channels: [f64; 4]) -> u32 {
+    let [a, r, g, b] = channels.map(|value| value.round().clamp(0.0, 255.0) as u32);
+    // Premultiplied colour can never exceed alpha
+    (a << 24) | (r.min(a) << 16) | (g.min(a) << 8) | b.min(a)
+}
+
+/// Load every image from an Xcursor file (all nominal sizes, all frames).
+pub fn load_xcursor(path: &Path) -> Result<Vec<CursorImage>, String> {
+    let content = fs::read(path)
+        .map_err(|err| format!("Failed to read {}: {}", path.display(), err))?;
+    let images = xcursor::parser::parse_xcursor(&content)
+        .ok_or_else(|| format!("{} is not a valid Xcursor file", path.display()))?;
+
+    Ok(images
+        .into_iter()
+        .map(|image| CursorImage {
+            nominal_size: image.size,
+            width: image.width,
+            height: image.height,
+            xhot: image.xhot,
+            yhot: image.yhot,
+            delay_ms: image.delay,
+            // Xcursor stores each pixel as a little-endian u32 in file order
+            pixels: image
+                .pixels_rgba
+                .chunks_exact(4)
+                .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+                .collect(),
+        })
+        .collect())
+}
+
+/// Load a PNG as a single static cursor image with the given hotspot.
+pub fn load_png(path: &Path, hotspot: (u32, u32)) -> Result<CursorImage, String> {
+    let file = File::open(path)
+        .map_err(|err| format!("Failed to open {}: {}", path.display(), err))?;
+
+    let mut decoder = png::Decoder::new(file);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()
+        .map_err(|err| format!("Failed to decode {}: {}", path.display(), err))?;
+    let mut buf = vec![0u8; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf)
+        .map_err(|err| format!("Failed to decode {}: {}", path.display(), err))?;
+
+    let samples = info.color_type.samples();
+    let pixels: Vec<u32> = buf[..info.buffer_size()]
+        .chunks_exact(samples)
+        .map(|px| {
+            let (r, g, b, a) = match px.len() {
+                1 => (px[0], px[0], px[0], 255),
+                2 => (px[0], px[0], px[0], px[1]),
+                3 => (px[0], px[1], px[2], 255),
+                _ => (px[0], px[1], px[2], px[3]),
+            };
+            let premultiply = |channel: u8| (channel as u32 * a as u32 + 127) / 255;
+            (a as u32) << 24 | premultiply(r) << 16 | premultiply(g) << 8 | premultiply(b)
+        })
+        .collect();
+
+    if hotspot.0 >= info.width || hotspot.1 >= info.height {
+        return Err(format!(
+            "Hotspot {},{} lies outside the {}x{} image",
+            hotspot.0, hotspot.1, info.width, info.height
+        ));
+    }
+
+    Ok(CursorImage {
+        nominal_size: info.width.max(info.height),
+        width: info.width,
+        height: info.height,
+        xhot: hotspot.0,
+        yhot: hotspot.1,
+        delay_ms: 0,
+        pixels,
+    })
+}
+
+/// Load a PNG or Xcursor file, recognised by its magic bytes.
+/// A given hotspot overrides the one stored in an Xcursor file.
+pub fn load_file(path: &Path, hotspot: Option<(u32, u32)>) -> Result<Vec<CursorImage>, String> {
+    let content = fs::read(path)
+        .map_err(|err| format!("Failed to read {}: {}", path.display(), err))?;
+
+    if content.starts_with(b"Xcur") {
+        let mut images = load_xcursor(path)?;
+        if let Some((xhot, yhot)) = hotspot {
+            for image in &mut images {
+                image.xhot = xhot.min(image.width.saturating_sub(1));
+                image.yhot = yhot.min(image.height.saturating_sub(1));
+            }
+        }
+        Ok(images)
+    } else if content.starts_with(b"\x89PNG") {
+        Ok(vec![load_png(path, hotspot.unwrap_or((0, 0)))?])
+    } else {
+        Err(format!("{} is neither a PNG nor an Xcursor file", path.display()))
+    }
+}
+
+/// Pick the frames to show at `size` and scale them to it.
+///
+/// Uses the nominal size closest to the target from above, or the largest
+/// available if every image is smaller, so detail is only ever lost by
+/// downscaling. All images of that nominal size are kept in file order,
+/// which are the frames of an animated cursor. Returns the frames and
+/// whether any scaling was needed.
+pub fn frames_at_size(images: &[CursorImage], size: u32) -> (Vec<CursorImage>, bool) {
+    let Some(largest) = images.iter().map(|image| image.nominal_size).max() else {
+        return (Vec::new(), false);
+    };
+    let source_size = images
+        .iter()
+        .map(|image| image.nominal_size)
+        .filter(|&nominal| nominal >= size)
+        .min()
+        .unwrap_or(largest);
+
+    let frames = images
+        .iter()
+        .filter(|image| image.nominal_size == source_size)
+        .map(|image| image.scaled_to(size))
+        .collect();
+    (frames, source_size != size)
+}
+
+/// Pick the frames libXcursor itself would show for `size`: all images of
+/// the nominal size closest to it, unscaled. Used when restoring, so the
+/// user gets back exactly the cursor their toolkit loaded.
+pub fn nearest_frames(images: &[CursorImage], size: u32) -> Vec<CursorImage> {
+    let Some(nearest) = images
+        .iter()
+        .map(|image| image.nominal_size)
+        .min_by_key(|&nominal| nominal.abs_diff(size))
+    else {
+        return Vec::new();
+    };
+
+    images
+        .iter()
+        .filter(|image| image.nominal_size == nearest)
+        .cloned()
+        .collect()
+}
+
+/// Pixels as Z-pixmap image data in the byte order the server asked
+/// for at connection setup. Most servers are little-endian, but a server
+/// on a big-endian machine may ask for MSB first.
+pub fn image_data(pixels: &[u32], order: ImageOrder) -> Vec<u8> {
+    if order == ImageOrder::MSB_FIRST {
+        pixels.iter().flat_map(|pixel| pixel.to_be_bytes()).collect()
+    } else {
+        pixels.iter().flat_map(|pixel| pixel.to_le_bytes()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::TempDir;
+    use std::path::PathBuf;
+
+    /// A square image whose pixels all equal `pixel`.
+    fn image(nominal_size: u32, delay_ms: u32, pixel: u32) -> CursorImage {
+        CursorImage {
+            nominal_size,
+            width: nominal_size,
+            height: nominal_size,
+            xhot: nominal_size / 4,
+            yhot: nominal_size / 2,
+            delay_ms,
+            pixels: vec![pixel; (nominal_size * nominal_size) as usize],
+        }
+    }
+
+    /// Write a 4x2 RGBA PNG into `dir`, opaque red on the left and
+    /// transparent white on the right.
+    fn png(dir: &TempDir) -> PathBuf {
+        let path = dir.0.join("cursor.png");
+        let mut encoder = png::Encoder::new(File::create(&path).unwrap(), 4, 2);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().unwrap();
+        let row = [[255, 0, 0, 255], [255, 0, 0, 255], [255, 255, 255, 0], [255, 255, 255, 0]];
+        writer.write_image_data(&[row, row].concat().concat()).unwrap();
+        path
+    }
+
+    #[test]
+    fn scaling_resizes_the_image_and_its_hotspot() {
+        let source = image(24, 50, 0x80402010);
+
+        let up = source.scaled_to(48);
+        assert_eq!((up.nominal_size, up.width, up.height), (48, 48, 48));
+        assert_eq!((up.xhot, up.yhot, up.delay_ms), (12, 24, 50));
+        assert!(up.pixels.iter().all(|&pixel| pixel == 0x80402010));
+
+        let down = source.scaled_to(12);
+        assert_eq!((down.width, down.height, down.xhot, down.yhot), (12, 12, 3, 6));
+        assert!(down.pixels.iter().all(|&pixel| pixel == 0x80402010));
+
+        assert_eq!(source.scaled_to(24).pixels, source.pixels);
+    }
+
+    #[test]
+    fn scaling_blends_premultiplied_pixels() {
+        // Opaque white next to fully transparent
+        let source = CursorImage {
+            nominal_size: 2,
+            width: 2,
+            height: 1,
+            xhot: 1,
+            yhot: 0,
+            delay_ms: 0,
+            pixels: vec![0xffffffff, 0x00000000],
+        };
+        let down = source.scaled_to(1);
+        assert_eq!((down.width, down.height, down.xhot), (1, 1, 0));
+        // Half-transparent white, not a dark fringe
+        assert_eq!(down.pixels, [0x80808080]);
+    }
+
+    #[test]
+    fn frames_come_from_the_nearest_size_above() {
+        let images = [
+            image(24, 10, 0xff000001),
+            image(24, 20, 0xff000002),
+            image(48, 10, 0xff000003),
+            image(48, 20, 0xff000004),
+            image(64, 0, 0xff000005),
+        ];
+
+        let (frames, scaled) = frames_at_size(&images, 40);
+        assert!(scaled);
+        assert_eq!(frames.iter().map(|frame| (frame.nominal_size, frame.delay_ms)).collect::<Vec<_>>(), [(40, 10), (40, 20)]);
+        assert_eq!(frames[1].pixels[0], 0xff000004);
+
+        let (frames, scaled) = frames_at_size(&images, 48);
+        assert!(!scaled);
+        assert_eq!(frames.len(), 2);
+
+        // Nothing is big enough, so the largest is upscaled
+        let (frames, scaled) = frames_at_size(&images, 96);
+        assert!(scaled);
+        assert_eq!((frames.len(), frames[0].width, frames[0].pixels[0]), (1, 96, 0xff000005));
+
+        assert!(frames_at_size(&[], 24).0.is_empty());
+    }
+
+    #[test]
+    fn nearest_frames_are_unscaled() {
+        let images = [image(24, 10, 1), image(24, 20, 2), image(48, 0, 3)];
+
+        let frames = nearest_frames(&images, 32);
+        assert_eq!(frames.iter().map(|frame| (frame.width, frame.delay_ms)).collect::<Vec<_>>(), [(24, 10), (24, 20)]);
+        // Nearer to 48 than 24, and below it
+        let frames = nearest_frames(&images, 40);
+        assert_eq!((frames.len(), frames[0].width), (1, 48));
+
+        assert!(nearest_frames(&[], 24).is_empty());
+    }
+
+    #[test]
+    fn png_is_premultiplied_and_checks_its_hotspot() {
+        let dir = TempDir::new("cursor-png");
+        let file = png(&dir);
+
+        let image = load_png(&file, (3, 1)).unwrap();
+        assert_eq!((image.nominal_size, image.width, image.height, image.xhot, image.yhot), (4, 4, 2, 3, 1));
+        assert_eq!(image.pixels[..4], [0xffff0000, 0xffff0000, 0, 0]);
+
+        for hotspot in [(4, 0), (0, 2)] {
+            let err = load_png(&file, hotspot).err().unwrap();
+            assert_eq!(err, format!("Hotspot {},{} lies outside the 4x2 image", hotspot.0, hotspot.1));
+        }
+    }
+
+    #[test]
+    fn files_are_recognised_by_their_magic() {
+        let dir = TempDir::new("cursor-magic");
+        let file = png(&dir);
+        let images = load_file(&file, None).unwrap();
+        assert_eq!((images.len(), images[0].xhot, images[0].yhot), (1, 0, 0));
+
+        fs::write(&file, "not a cursor").unwrap();
+        let err = load_file(&file, None).err().unwrap();
+        assert!(err.ends_with("is neither a PNG nor an Xcursor file"), "{}", err);
+    }
+
+    #[test]
+    fn image_data_follows_the_server_byte_order() {
+        let pixels = [0xff112233, 0x80402010];
+        assert_eq!(
+            image_data(&pixels, ImageOrder::LSB_FIRST),
+            [0x33, 0x22, 0x11, 0xff, 0x10, 0x20, 0x40, 0x80]
+        );
+        assert_eq!(
+            image_data(&pixels, ImageOrder::MSB_FIRST),
+            [0xff, 0x11, 0x22, 0x33, 0x80, 0x40, 0x20, 0x10]
+        );
+    }
+}
@@ -0,0 +1,533 @@
+use zbus::blocking::connection;
+use zbus::fdo;
+use zbus::interface;
+use zbus::object_server::SignalEmitter;
+
+use shake_cursor_core::config::Config;
+
+use crate::control::SharedControl;
+
+/// Well-known bus name the daemon claims on the session bus.
+const BUS_NAME: &str = "org.shakecursor.Daemon";
+
+/// Object path the Daemon interface is served at.
+const OBJECT_PATH: &str = "/org/shakecursor/Daemon";
+
+/// The `org.shakecursor.Daemon` interface.
+///
+/// A second front end to the same ControlState the control socket uses:
+/// methods queue the same actions, and every Config parameter is a
+/// read/write property validated by Config::set(). Desktop environments
+/// and scripts can use this instead of the socket.
+struct DaemonInterface {
+    state: SharedControl,
+}
+
+impl DaemonInterface {
+    fn config(&self) -> Config {
+        self.state.lock().unwrap().config.clone()
+    }
+
+    /// Set a parameter from its textual form, like `ctl set` does.
+    fn set(&self, key: &str, value: &str) -> fdo::Result<()> {
+        self.state
+            .lock()
+            .unwrap()
+            .set(key, value)
+            .map_err(fdo::Error::InvalidArgs)
+    }
+}
+
+#[interface(name = "org.shakecursor.Daemon")]
+impl DaemonInterface {
+    /// Pause shake detection, ending any running effect.
+    fn pause(&self) {
+        self.state.lock().unwrap().pause();
+    }
+
+    /// Resume shake detection.
+    fn resume(&self) {
+        self.state.lock().unwrap().resume();
+    }
+
+    /// Show the locate effects now, as if a shake had been detected.
+    fn trigger(&self) {
+        self.state.lock().unwrap().trigger();
+    }
+
+    /// End the locate effects now and restore the cursor.
+    fn dismiss(&self) {
+        self.state.lock().unwrap().dismiss();
+    }
+
+    /// Re-read the config file and apply it.
+    fn reload(&self) {
+        self.state.lock().unwrap().reload();
+    }
+
+    /// Emitted when a shake starts the locate effects, with the pointer
+    /// position at that moment.
+    #[zbus(signal)]
+    async fn shake_detected(emitter: &SignalEmitter<'_>, x: i16, y: i16) -> zbus::Result<()>;
+
+    /// Name of the current CursorState: "idle", "enlarged" or "restoring".
+    #[zbus(property)]
+    fn cursor_state(&self) -> String {
+        self.state.lock().unwrap().cursor_state.to_string()
+    }
+
+    /// True while shake detection is paused.
+    #[zbus(property)]
+    fn paused(&self) -> bool {
+        self.state.lock().unwrap().paused
+    }
+
+    #[zbus(property)]
+    fn time_window_ms(&self) -> u32 {
+        self.config().time_window_ms
+    }
+
+    #[zbus(property)]
+    fn set_time_window_ms(&mut self, value: u32) -> fdo::Result<()> {
+        self.set("time_window_ms", &value.to_string())
+    }
+
+    #[zbus(property)]
+    fn min_reversals(&self) -> u32 {
+        self.config().min_reversals
+    }
+
+    #[zbus(property)]
+    fn set_min_reversals(&mut self, value: u32) -> fdo::Result<()> {
+        self.set("min_reversals", &value.to_string())
+    }
+
+    #[zbus(property)]
+    fn min_velocity(&self) -> f64 {
+        self.config().min_velocity
+    }
+
+    #[zbus(property)]
+    fn set_min_velocity(&mut self, value: f64) -> fdo::Result<()> {
+        self.set("min_velocity", &value.to_string())
+    }
+
+    #[zbus(property)]
+    fn cooldown_ms(&self) -> u32 {
+        self.config().cooldown_ms
+    }
+
+    #[zbus(property)]
+    fn set_cooldown_ms(&mut self, value: u32) -> fdo::Result<()> {
+        self.set("cooldown_ms", &value.to_string())
+    }
+
+    #[zbus(property)]
+    fn enlarged_size(&self) -> u32 {
+        self.config().enlarged_size
+    }
+
+    #[zbus(property)]
+    fn set_enlarged_size(&mut self, value: u32) -> fdo::Result<()> {
+        self.set("enlarged_size", &value.to_string())
+    }
+
+    /// Path of the custom enlarged cursor, empty if not set. Read when
+    /// the daemon starts, so setting a different value fails.
+    #[zbus(property)]
+    fn enlarged_cursor(&self) -> String {
+        self.config()
+            .enlarged_cursor
+            .map(|cursor| cursor.path.display().to_string())
+            .unwrap_or_default()
+    }
+
+    #[zbus(property)]
+    fn set_enlarged_cursor(&mut self, value: String) -> fdo::Result<()> {
+        self.set("enlarged_cursor", &value)
+    }
+
+    /// Hotspot of the custom cursor as "X,Y", empty if not set. Like
+    /// EnlargedCursor, it can't be changed while the daemon runs.
+    #[zbus(property)]
+    fn enlarged_cursor_hotspot(&self) -> String {
+        self.config()
+            .enlarged_cursor
+            .and_then(|cursor| cursor.hotspot)
+            .map(|(x, y)| format!("{},{}", x, y))
+            .unwrap_or_default()
+    }
+
+    #[zbus(property)]
+    fn set_enlarged_cursor_hotspot(&mut self, value: String) -> fdo::Result<()> {
+        self.set("enlarged_cursor_hotspot", &value)
+    }
+
+    /// Names of the locate effects in the chain, in order.
+    #[zbus(property)]
+    fn effects(&self) -> Vec<String> {
+        self.config().effects.iter().map(|effect| effect.name().to_string()).collect()
+    }
+
+    #[zbus(property)]
+    fn set_effects(&mut self, value: Vec<String>) -> fdo::Result<()> {
+        self.set("effects", &value.join(","))
+    }
+
+    #[zbus(property)]
+    fn ring_diameter(&self) -> u32 {
+        self.config().ring_diameter
+    }
+
+    #[zbus(property)]
+    fn set_ring_diameter(&mut self, value: u32) -> fdo::Result<()> {
+        self.set("ring_diameter", &value.to_string())
+    }
+
+    #[zbus(property)]
+    fn spotlight_radius(&self) -> u32 {
+        self.config().spotlight_radius
+    }
+
+    #[zbus(property)]
+    fn set_spotlight_radius(&mut self, value: u32) -> fdo::Result<()> {
+        self.set("spotlight_radius", &value.to_string())
+    }
+
+    /// Whether the locate hotkey is grabbed. The grab is made when the
+    /// daemon starts, so setting a different value fails.
+    #[zbus(property)]
+    fn hotkey_enabled(&self) -> bool {
+        self.config().hotkey_enabled
+    }
+
+    #[zbus(property)]
+    fn set_hotkey_enabled(&mut self, value: bool) -> fdo::Result<()> {
+        self.set("hotkey_enabled", &value.to_string())
+    }
+
+    /// Locate hotkey combination, e.g. "ctrl" or "super+l". Like
+    /// HotkeyEnabled, it can't be changed while the daemon runs.
+    #[zbus(property)]
+    fn hotkey(&self) -> String {
+        self.config().hotkey.name()
+    }
+
+    #[zbus(property)]
+    fn set_hotkey(&mut self, value: String) -> fdo::Result<()> {
+        self.set("hotkey", &value)
+    }
+
+    #[zbus(property)]
+    fn wake_enabled(&self) -> bool {
+        self.config().wake_enabled
+    }
+
+    #[zbus(property)]
+    fn set_wake_enabled(&mut self, value: bool) -> fdo::Result<()> {
+        self.set("wake_enabled", &value.to_string())
+    }
+
+    #[zbus(property)]
+    fn wake_idle_ms(&self) -> u32 {
+        self.config().wake_idle_ms
+    }
+
+    #[zbus(property)]
+    fn set_wake_idle_ms(&mut self, value: u32) -> fdo::Result<()> {
+        self.set("wake_idle_ms", &value.to_string())
+    }
+
+    #[zbus(property)]
+    fn wake_cooldown_ms(&self) -> u32 {
+        self.config().wake_cooldown_ms
+    }
+
+    #[zbus(property)]
+    fn set_wake_cooldown_ms(&mut self, value: u32) -> fdo::Result<()> {
+        self.set("wake_cooldown_ms", &value.to_string())
+    }
+
+    #[zbus(property)]
+    fn adaptive_enabled(&self) -> bool {
+        self.config().adaptive_enabled
+    }
+
+    #[zbus(property)]
+    fn set_adaptive_enabled(&mut self, value: bool) -> fdo::Result<()> {
+        self.set("adaptive_enabled", &value.to_string())
+    }
+
+    /// Path of the Prometheus textfile, empty if not written.
+    #[zbus(property)]
+    fn metrics_textfile(&self) -> String {
+        self.config()
+            .metrics_textfile
+            .map(|path| path.display().to_string())
+            .unwrap_or_default()
+    }
+
+    #[zbus(property)]
+    fn set_metrics_textfile(&mut self, value: String) -> fdo::Result<()> {
+        self.set("metrics_textfile", &value)
+    }
+}
+
+/// The daemon's session bus connection, serving DaemonInterface.
+///
+/// zbus dispatches incoming calls on its own thread, so the event loop
+/// only touches this to emit signals.
+pub struct DbusService {
+    connection: zbus::blocking::Connection,
+}
+
+impl DbusService {
+    /// Connect to the session bus, claim `org.shakecursor.Daemon` and
+    /// serve the interface at `/org/shakecursor/Daemon`.
+    pub fn start(state: SharedControl) -> Result<Self, String> {
+        Self::serve(connection::Builder::session(), state)
+    }
+
+    /// Claim the name and serve the interface on the bus `builder`
+    /// connects to.
+    fn serve(builder: zbus::Result<connection::Builder>, state: SharedControl) -> Result<Self, String> {
+        let connection = builder
+            .and_then(|builder| builder.name(BUS_NAME))
+            .and_then(|builder| builder.serve_at(OBJECT_PATH, DaemonInterface { state }))
+            .and_then(|builder| builder.build())
+            .map_err(|err| format!("Failed to register {} on the session bus: {}", BUS_NAME, err))?;
+        Ok(Self { connection })
+    }
+
+    /// Emit ShakeDetected.
+    pub fn shake_detected(&self, x: i16, y: i16) {
+        let result = self.interface().and_then(|iface| {
+            zbus::block_on(DaemonInterface::shake_detected(iface.signal_emitter(), x, y))
+        });
+        if let Err(err) = result {
+            log::warn!("Failed to emit ShakeDetected: {}", err);
+        }
+    }
+
+    /// Announce a change of CursorState or Paused to property watchers.
+    pub fn state_changed(&self) {
+        let result = self.interface().and_then(|iface| {
+            let emitter = iface.signal_emitter();
+            let daemon = iface.get();
+            zbus::block_on(async {
+                daemon.cursor_state_changed(emitter).await?;
+                daemon.paused_changed(emitter).await
+            })
+        });
+        if let Err(err) = result {
+            log::warn!("Failed to emit PropertiesChanged: {}", err);
+        }
+    }
+
+    fn interface(&self) -> zbus::Result<zbus::blocking::object_server::InterfaceRef<DaemonInterface>> {
+        self.connection
+            .object_server()
+            .interface::<_, DaemonInterface>(OBJECT_PATH)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::io::{self, BufRead, BufReader};
+    use std::process::{Child, Command, Stdio};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::mpsc;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+
+    use shake_cursor_core::metrics::SharedMetrics;
+    use zbus::blocking::fdo::PropertiesProxy;
+    use zbus::blocking::{Connection, MessageIterator};
+    use zbus::message::Type;
+    use zbus::names::InterfaceName;
+    use zbus::zvariant::{OwnedValue, Value};
+    use zbus::MatchRule;
+
+    use crate::control::{ControlAction, ControlState};
+
+    const INTERFACE: &str = "org.shakecursor.Daemon";
+
+    /// How long to wait for a signal.
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// A private session bus, killed when dropped.
+    struct Bus {
+        process: Child,
+        address: String,
+    }
+
+    impl Bus {
+        /// Start dbus-daemon and read its address. None if it isn't
+        /// installed.
+        fn start() -> Option<Self> {
+            let mut process = match Command::new("dbus-daemon")
+                .args(["--session", "--nofork", "--print-address"])
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()
+            {
+                Ok(process) => process,
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                    eprintln!("dbus-daemon not found, skipping D-Bus test");
+                    return None;
+                }
+                Err(err) => panic!("Failed to start dbus-daemon: {}", err),
+            };
+            let mut address = String::new();
+            BufReader::new(process.stdout.take().unwrap()).read_line(&mut address).unwrap();
+            Some(Self { process, address: address.trim().to_string() })
+        }
+
+        fn connect(&self) -> Connection {
+            connection::Builder::address(self.address.as_str()).unwrap().build().unwrap()
+        }
+    }
+
+    impl Drop for Bus {
+        fn drop(&mut self) {
+            let _ = self.process.kill();
+            let _ = self.process.wait();
+        }
+    }
+
+    /// The service on `bus`, its shared state, and how often the state
+    /// woke the (absent) event loop.
+    fn serve(bus: &Bus) -> (DbusService, SharedControl, Arc<AtomicUsize>) {
+        let wakeups = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&wakeups);
+        let state = Arc::new(Mutex::new(ControlState {
+            paused: false,
+            cursor_state: "idle",
+            config: Config::default(),
+            metrics: SharedMetrics::default(),
+            pending: Vec::new(),
+            waker: Arc::new(move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+            }),
+        }));
+        let builder = connection::Builder::address(bus.address.as_str());
+        let service = DbusService::serve(builder, Arc::clone(&state)).unwrap();
+        (service, state, wakeups)
+    }
+
+    fn properties(client: &Connection) -> PropertiesProxy<'_> {
+        PropertiesProxy::builder(client)
+            .destination(BUS_NAME)
+            .unwrap()
+            .path(OBJECT_PATH)
+            .unwrap()
+            .build()
+            .unwrap()
+    }
+
+    fn interface() -> InterfaceName<'static> {
+        InterfaceName::try_from(INTERFACE).unwrap()
+    }
+
+    #[test]
+    fn method_calls_queue_actions() {
+        let Some(bus) = Bus::start() else { return };
+        let (_service, state, wakeups) = serve(&bus);
+        let client = bus.connect();
+
+        for method in ["Trigger", "Dismiss", "Reload", "Pause"] {
+            client.call_method(Some(BUS_NAME), OBJECT_PATH, Some(INTERFACE), method, &()).unwrap();
+        }
+        {
+            let state = state.lock().unwrap();
+            assert!(state.paused);
+            assert!(matches!(
+                state.pending[..],
+                [ControlAction::Trigger, ControlAction::Dismiss, ControlAction::Reload, ControlAction::Pause]
+            ));
+        }
+
+        client.call_method(Some(BUS_NAME), OBJECT_PATH, Some(INTERFACE), "Resume", &()).unwrap();
+        assert!(!state.lock().unwrap().paused);
+        assert_eq!(wakeups.load(Ordering::SeqCst), 5);
+    }
+
+    #[test]
+    fn properties_read_and_write_the_config() {
+        let Some(bus) = Bus::start() else { return };
+        let (_service, state, wakeups) = serve(&bus);
+        let client = bus.connect();
+        let properties = properties(&client);
+
+        let value = properties.get(interface(), "MinReversals").unwrap();
+        assert_eq!(u32::try_from(value).unwrap(), 3);
+        properties.set(interface(), "MinReversals", Value::from(5u32)).unwrap();
+        let value = properties.get(interface(), "MinReversals").unwrap();
+        assert_eq!(u32::try_from(value).unwrap(), 5);
+        {
+            let state = state.lock().unwrap();
+            assert_eq!(state.config.min_reversals, 5);
+            assert!(matches!(state.pending[..], [ControlAction::ApplyConfig]));
+        }
+
+        // Out of range, and a key that needs a restart: both fail and
+        // leave the config alone
+        assert!(properties.set(interface(), "CooldownMs", Value::from(0u32)).is_err());
+        assert!(properties.set(interface(), "Hotkey", Value::from("super+l")).is_err());
+        let state = state.lock().unwrap();
+        assert_eq!(state.config.cooldown_ms, Config::default().cooldown_ms);
+        assert_eq!(state.config.hotkey.name(), "ctrl");
+        assert_eq!(state.pending.len(), 1);
+        assert_eq!(wakeups.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn signals_reach_clients() {
+        let Some(bus) = Bus::start() else { return };
+        let (service, state, _) = serve(&bus);
+        let client = bus.connect();
+
+        // Collect the service's signals on a thread, so a missing one
+        // fails the test instead of hanging it
+        let rule = MatchRule::builder()
+            .msg_type(Type::Signal)
+            .sender(BUS_NAME)
+            .unwrap()
+            .path(OBJECT_PATH)
+            .unwrap()
+            .build();
+        let messages = MessageIterator::for_match_rule(rule, &client, None).unwrap();
+        let (sender, signals) = mpsc::channel();
+        thread::spawn(move || {
+            for message in messages {
+                let Ok(message) = message else { return };
+                if sender.send(message).is_err() {
+                    return;
+                }
+            }
+        });
+
+        service.shake_detected(12, -3);
+        let signal = signals.recv_timeout(TIMEOUT).expect("no ShakeDetected");
+        assert_eq!(signal.header().member().unwrap().as_str(), "ShakeDetected");
+        assert_eq!(signal.body().deserialize::<(i16, i16)>().unwrap(), (12, -3));
+
+        state.lock().unwrap().cursor_state = "enlarged";
+        service.state_changed();
+        // One PropertiesChanged per property
+        let mut changed = HashMap::new();
+        for _ in 0..2 {
+            let signal = signals.recv_timeout(TIMEOUT).expect("no PropertiesChanged");
+            assert_eq!(signal.header().member().unwrap().as_str(), "PropertiesChanged");
+            let (interface, properties, _): (String, HashMap<String, OwnedValue>, Vec<String>) =
+                signal.body().deserialize().unwrap();
+            assert_eq!(interface, INTERFACE);
+            changed.extend(properties);
+        }
+        assert_eq!(String::try_from(changed["CursorState"].try_clone().unwrap()).unwrap(), "enlarged");
+        assert!(!bool::try_from(&changed["Paused"]).unwrap());
+    }
+}
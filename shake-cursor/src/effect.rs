@@ -0,0 +1,35 @@
+use shake_cursor_core::backend::DisplayBackend;
+use shake_cursor_core::config::{Config, LocateEffect};
+use shake_cursor_core::effect::{CursorEffect, EffectChain, ResizeEffect};
+
+use crate::overlay::{CrosshairEffect, RingEffect, SpotlightEffect};
+
+/// Factory: build the configured effect chain. Effects that cannot be
+/// created (e.g. overlays without an X server, or on a backend whose
+/// positions aren't screen coordinates) are logged and left out.
+pub fn build_chain(config: &Config, backend: &dyn DisplayBackend) -> EffectChain {
+    let mut effects: Vec<Box<dyn CursorEffect>> = Vec::new();
+
+    for &kind in &config.effects {
+        let effect: Result<Box<dyn CursorEffect>, String> = match kind {
+            LocateEffect::Enlarge => Ok(Box::new(ResizeEffect::new(config.enlarged_size))),
+            _ if !backend.screen_coordinates() => {
+                Err("the display backend doesn't report where the pointer is on screen".to_string())
+            }
+            LocateEffect::Ring => {
+                RingEffect::new(config.ring_diameter).map(|effect| Box::new(effect) as _)
+            }
+            LocateEffect::Crosshair => CrosshairEffect::new().map(|effect| Box::new(effect) as _),
+            LocateEffect::Spotlight => {
+                SpotlightEffect::new(config.spotlight_radius).map(|effect| Box::new(effect) as _)
+            }
+        };
+
+        match effect {
+            Ok(effect) => effects.push(effect),
+            Err(err) => log::error!("{} effect unavailable: {}", kind.name(), err),
+        }
+    }
+
+    EffectChain::new(effects)
+}
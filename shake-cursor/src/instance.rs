@@ -0,0 +1,373 @@
+use std::env;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, Write};
+use std::os::fd::AsRawFd;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::*;
+use x11rb::protocol::Event;
+use x11rb::rust_connection::RustConnection;
+use x11rb::CURRENT_TIME;
+use x11rb_protocol::parse_display::parse_display;
+
+use crate::control::SharedControl;
+
+/// How long `--replace` waits for the running instance to restore the
+/// cursor and exit.
+const REPLACE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Proof that this is the only daemon on the display.
+///
+/// Two locks are held, because each covers a case the other misses:
+///   - an flock on `$XDG_RUNTIME_DIR/shake-cursor-<display>.lock` (see
+///     runtime_path()), which
+///     works on any display server and is released by the kernel if the
+///     daemon dies, and
+///   - on X11, ownership of the `_SHAKE_CURSOR_S<screen>` selection,
+///     which is visible to every client of the display, even one started
+///     with a different runtime directory. Taking the selection over is
+///     also how `--replace` asks the running instance to quit, the same
+///     convention window and composite managers use.
+///
+/// Both are released when the process exits. `--replace` returns only
+/// once the running instance has let go of both, so it has finished
+/// restoring the cursor before this one starts.
+pub struct InstanceLock {
+    /// Keeps the flock held.
+    _file: File,
+
+    /// Connection owning the selection, until watch_for_replacement()
+    /// moves it to its thread.
+    selection: Option<RustConnection>,
+}
+
+impl InstanceLock {
+    /// Take both locks. If another instance holds them, fail, or with
+    /// `replace` ask it to quit and wait until it has.
+    pub fn acquire(replace: bool) -> Result<Self, String> {
+        let path = runtime_path("lock")?;
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .map_err(|err| format!("Failed to open {}: {}", path.display(), err))?;
+
+        // The selection is optional: there is no X server on Wayland-only
+        // sessions, and the flock alone still prevents duplicates there
+        let x11 = RustConnection::connect(None).ok().and_then(|(conn, screen_num)| {
+            let atom = intern_atom(&conn, false, format!("_SHAKE_CURSOR_S{}", screen_num).as_bytes())
+                .ok()?
+                .reply()
+                .ok()?
+                .atom;
+            let root = conn.setup().roots[screen_num].root;
+            Some((conn, atom, root))
+        });
+        let selection_owner = x11.as_ref().and_then(|(conn, atom, _)| {
+            let owner = get_selection_owner(conn, *atom).ok()?.reply().ok()?.owner;
+            (owner != x11rb::NONE).then_some(owner)
+        });
+
+        let locked = try_lock(&file);
+        if !locked || selection_owner.is_some() {
+            let pid = running_pid(&mut file);
+            if !replace {
+                return Err(format!(
+                    "shake-cursor is already running on this display{}; use --replace to take over",
+                    pid.map(|pid| format!(" (pid {})", pid)).unwrap_or_default()
+                ));
+            }
+            log::info!("Replacing the running instance.");
+        }
+
+        // Claiming the selection tells the running instance to quit
+        let deadline = Instant::now() + REPLACE_TIMEOUT;
+        let mut selection = None;
+        if let Some((conn, atom, root)) = x11 {
+            // Its selection window goes away when it disconnects. Watch
+            // for that before claiming, so the notification can't be
+            // missed; an error means the window is already gone.
+            let previous_owner = selection_owner.filter(|&owner| {
+                let aux = ChangeWindowAttributesAux::new().event_mask(EventMask::STRUCTURE_NOTIFY);
+                change_window_attributes(&conn, owner, &aux)
+                    .ok()
+                    .and_then(|cookie| cookie.check().ok())
+                    .is_some()
+            });
+
+            let window = conn.generate_id().map_err(|err| format!("Failed to allocate window: {}", err))?;
+            create_window(
+                &conn,
+                x11rb::COPY_DEPTH_FROM_PARENT,
+                window,
+                root,
+                -1,
+                -1,
+                1,
+                1,
+                0,
+                WindowClass::INPUT_ONLY,
+                x11rb::COPY_FROM_PARENT,
+                &CreateWindowAux::new(),
+            )
+            .map_err(|err| format!("Failed to create selection window: {}", err))?;
+            set_selection_owner(&conn, window, atom, CURRENT_TIME)
+                .map_err(|err| format!("Failed to claim selection: {}", err))?;
+            let owner = get_selection_owner(&conn, atom)
+                .map_err(|err| format!("Failed to query selection: {}", err))?
+                .reply()
+                .map_err(|err| format!("Failed to query selection: {}", err))?
+                .owner;
+            if owner != window {
+                return Err("Another client took the shake-cursor selection".to_string());
+            }
+            if let Some(previous_owner) = previous_owner {
+                wait_for_destroy(&conn, previous_owner, deadline)?;
+            }
+            selection = Some(conn);
+        }
+
+        if !locked {
+            if selection_owner.is_none()
+                && let Some(pid) = running_pid(&mut file).filter(|&pid| signalable(pid))
+            {
+                // It isn't watching a selection (no X server), so ask
+                // with SIGTERM, which gives the same clean shutdown
+                // SAFETY: kill(2) has no memory safety requirements
+                unsafe { libc::kill(pid, libc::SIGTERM) };
+            }
+
+            while !try_lock(&file) {
+                if Instant::now() >= deadline {
+                    return Err("The running instance did not exit in time".to_string());
+                }
+                thread::sleep(Duration::from_millis(50));
+            }
+        }
+
+        // Record our pid for the next instance's error message
+        file.set_len(0)
+            .and_then(|()| file.rewind())
+            .and_then(|()| write!(file, "{}", std::process::id()))
+            .map_err(|err| format!("Failed to write {}: {}", path.display(), err))?;
+
+        Ok(Self { _file: file, selection })
+    }
+
+    /// Shut the daemon down cleanly once a newer instance started with
+    /// `--replace` takes the selection away.
+    pub fn watch_for_replacement(&mut self, control: SharedControl) {
+        let Some(conn) = self.selection.take() else {
+            return;
+        };
+
+        thread::spawn(move || {
+            while let Ok(event) = conn.wait_for_event() {
+                if let Event::SelectionClear(_) = event {
+                    log::info!("Replaced by a new instance, shutting down.");
+                    control.lock().unwrap().shutdown();
+                    // Keep the connection (and window) until we exit
+                }
+            }
+        });
+    }
+}
+
+/// `$XDG_RUNTIME_DIR/shake-cursor-<display>.<extension>`, for the files of
+/// the daemon on the current display, or `shake-cursor.<extension>`
+/// without one.
+pub fn runtime_path(extension: &str) -> Result<PathBuf, String> {
+    let runtime_dir = env::var_os("XDG_RUNTIME_DIR")
+        .filter(|dir| !dir.is_empty())
+        .ok_or_else(|| "XDG_RUNTIME_DIR is not set".to_string())?;
+    let name = match display_id() {
+        display if display.is_empty() => format!("shake-cursor.{}", extension),
+        display => format!("shake-cursor-{}.{}", display, extension),
+    };
+    Ok(PathBuf::from(runtime_dir).join(name))
+}
+
+/// The current display, for naming per-display files.
+///
+/// An X display is named by the display and screen the connection uses,
+/// so that every spelling of DISPLAY for it (`:0`, `:0.0`, `localhost:0`)
+/// gives the same name: `x11-0.0`, with the host added for remote ones.
+/// Without X, WAYLAND_DISPLAY is used as it is.
+pub fn display_id() -> String {
+    let name = match parse_display(None) {
+        Ok(parsed) if parsed.host.is_empty() || parsed.host == "localhost" => {
+            format!("x11-{}.{}", parsed.display, parsed.screen)
+        }
+        Ok(parsed) => format!("x11-{}-{}.{}", parsed.host, parsed.display, parsed.screen),
+        Err(_) => env::var("WAYLAND_DISPLAY").unwrap_or_default(),
+    };
+    name.chars()
+        .map(|ch| if ch.is_ascii_alphanumeric() || ch == '-' || ch == '.' { ch } else { '_' })
+        .collect()
+}
+
+/// Wait for the DestroyNotify of `window`, whose structure events `conn`
+/// has selected.
+fn wait_for_destroy(conn: &RustConnection, window: Window, deadline: Instant) -> Result<(), String> {
+    loop {
+        let event = conn
+            .poll_for_event()
+            .map_err(|err| format!("Lost the X connection while replacing: {}", err))?;
+        match event {
+            Some(Event::DestroyNotify(event)) if event.window == window => return Ok(()),
+            Some(_) => continue,
+            None => {}
+        }
+        if Instant::now() >= deadline {
+            return Err("The running instance did not exit in time".to_string());
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+/// Try to take an exclusive flock without blocking.
+fn try_lock(file: &File) -> bool {
+    // SAFETY: the descriptor is valid for as long as `file` is
+    unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) == 0 }
+}
+
+/// Pid the lock holder wrote into the lock file.
+fn running_pid(file: &mut File) -> Option<libc::pid_t> {
+    let mut content = String::new();
+    file.rewind().ok()?;
+    file.read_to_string(&mut content).ok()?;
+    content.trim().parse().ok()
+}
+
+/// Whether `pid` may be sent SIGTERM. The lock file is only as
+/// trustworthy as whoever wrote it: 0 and negative pids signal whole
+/// process groups, 1 is init, and we must not stop ourselves.
+fn signalable(pid: libc::pid_t) -> bool {
+    pid > 1 && pid as u32 != std::process::id()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::TempDir;
+    use std::fs;
+    use std::os::unix::process::ExitStatusExt;
+    use std::process::Command;
+    use std::sync::Mutex;
+
+    /// The tests change the process environment, so they take turns.
+    static ENVIRONMENT: Mutex<()> = Mutex::new(());
+
+    /// A runtime directory with no display.
+    fn runtime(name: &str) -> TempDir {
+        let dir = TempDir::new(&format!("instance-{}", name));
+        // SAFETY: ENVIRONMENT is held, and no other test reads these
+        unsafe {
+            env::set_var("XDG_RUNTIME_DIR", &dir.0);
+            env::remove_var("DISPLAY");
+            env::remove_var("WAYLAND_DISPLAY");
+        }
+        dir
+    }
+
+    fn lock_file(runtime: &TempDir) -> PathBuf {
+        runtime.0.join("shake-cursor.lock")
+    }
+
+    #[test]
+    fn stale_lock_file_is_taken_over() {
+        let _environment = ENVIRONMENT.lock().unwrap();
+        let runtime = runtime("stale");
+        // Left behind by an instance that died; nothing holds the flock
+        fs::write(lock_file(&runtime), "4194305").unwrap();
+
+        let lock = InstanceLock::acquire(false).unwrap();
+        assert!(lock.selection.is_none());
+        assert_eq!(fs::read_to_string(lock_file(&runtime)).unwrap(), std::process::id().to_string());
+
+        let err = InstanceLock::acquire(false).err().unwrap();
+        assert_eq!(
+            err,
+            format!(
+                "shake-cursor is already running on this display (pid {}); use --replace to take over",
+                std::process::id()
+            )
+        );
+    }
+
+    #[test]
+    fn every_spelling_of_a_display_shares_a_name() {
+        let _environment = ENVIRONMENT.lock().unwrap();
+        let runtime = runtime("display");
+
+        for display in [":0", ":0.0", "localhost:0", "unix/:0"] {
+            // SAFETY: ENVIRONMENT is held
+            unsafe { env::set_var("DISPLAY", display) };
+            assert_eq!(display_id(), "x11-0.0", "DISPLAY={}", display);
+        }
+        assert_eq!(runtime_path("sock").unwrap(), runtime.0.join("shake-cursor-x11-0.0.sock"));
+        // SAFETY: ENVIRONMENT is held
+        unsafe { env::set_var("DISPLAY", "remote:1.1") };
+        assert_eq!(display_id(), "x11-remote-1.1");
+
+        // SAFETY: ENVIRONMENT is held
+        unsafe {
+            env::remove_var("DISPLAY");
+            env::set_var("WAYLAND_DISPLAY", "wayland-1");
+        }
+        assert_eq!(display_id(), "wayland-1");
+
+        // SAFETY: ENVIRONMENT is held
+        unsafe { env::remove_var("WAYLAND_DISPLAY") };
+        assert_eq!(runtime_path("sock").unwrap(), runtime.0.join("shake-cursor.sock"));
+    }
+
+    #[test]
+    fn only_single_other_processes_are_signalled() {
+        let _environment = ENVIRONMENT.lock().unwrap();
+        let runtime = runtime("pid");
+        fs::write(lock_file(&runtime), "").unwrap();
+        let mut file = File::open(lock_file(&runtime)).unwrap();
+
+        let contents = [("4194305", Some(4194305)), ("0", Some(0)), ("-1", Some(-1)), ("4294967295", None), ("pid", None)];
+        for (content, pid) in contents {
+            fs::write(lock_file(&runtime), content).unwrap();
+            assert_eq!(running_pid(&mut file), pid, "lock file {:?}", content);
+        }
+
+        assert!(signalable(4194305));
+        assert!(!signalable(0));
+        assert!(!signalable(-1));
+        assert!(!signalable(1));
+        assert!(!signalable(std::process::id() as libc::pid_t));
+    }
+
+    #[test]
+    fn replace_without_x_terminates_the_lock_holder() {
+        let _environment = ENVIRONMENT.lock().unwrap();
+        let runtime = runtime("replace");
+
+        // A running instance: a process that exits on SIGTERM, whose
+        // lock is released when it does
+        let mut running = Command::new("sleep").arg("30").spawn().unwrap();
+        let held = File::create(lock_file(&runtime)).unwrap();
+        assert!(try_lock(&held));
+        fs::write(lock_file(&runtime), running.id().to_string()).unwrap();
+        let holder = thread::spawn(move || {
+            let status = running.wait().unwrap();
+            drop(held);
+            status
+        });
+
+        assert!(InstanceLock::acquire(false).is_err());
+        let lock = InstanceLock::acquire(true).unwrap();
+        assert_eq!(holder.join().unwrap().signal(), Some(libc::SIGTERM));
+        assert!(lock.selection.is_none());
+        assert_eq!(fs::read_to_string(lock_file(&runtime)).unwrap(), std::process::id().to_string());
+    }
+}
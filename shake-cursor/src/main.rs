@@ -1,99 +1,413 @@
-mod backend;
-mod config;
-mod detector;
+mod analyze;
+mod calibrate;
+mod cli;
+#[cfg(test)]
+#[path = "../tests/common/mod.rs"]
+mod common;
+mod control;
+mod cursor_image;
+mod dbus;
+mod effect;
+mod instance;
+mod notify;
+mod overlay;
+mod registry;
+mod replay_backend;
+mod script_backend;
+mod signals;
+mod simulate;
+mod wayland_backend;
 mod x11_backend;
 
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-use backend::DisplayBackend;
-use config::Config;
-use detector::{CursorState, ShakeDetector};
-use x11_backend::X11Backend;
+use shake_cursor_core::adaptive::Adaptive;
+use shake_cursor_core::config::Config;
+use shake_cursor_core::controller::{Controller, SystemClock};
+use shake_cursor_core::detector::CursorState;
+use shake_cursor_core::trace;
+
+use cli::{Args, Command, ReplayConfig};
+use control::{ControlAction, ControlState};
+
+/// How often the metrics textfile is rewritten.
+const METRICS_INTERVAL: Duration = Duration::from_secs(10);
 
 fn main() {
     // Initialize logging (controlled by RUST_LOG env var)
     env_logger::init();
 
-    // Build configuration with default thresholds
-    let config = Config::default();
-    let enlarged_size = config.enlarged_size;
-    let cooldown_ms = config.cooldown_ms;
+    let args = match cli::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    match args.command {
+        Command::Help => {
+            println!("{}", cli::USAGE);
+            return;
+        }
+        Command::ListBackends => {
+            registry::print_probes();
+            return;
+        }
+        Command::Ctl(ctl_args) => std::process::exit(control::run_client(&ctl_args)),
+        Command::Analyze(analyze_args) => std::process::exit(analyze::run(&analyze_args)),
+        Command::Calibrate(calibrate_args) => std::process::exit(calibrate::run(&calibrate_args)),
+        Command::Simulate(simulate_args) => std::process::exit(simulate::run(&simulate_args)),
+        Command::Daemon => {}
+    }
+
+    // Build configuration from the config file and command-line overrides
+    let mut config = match load_config(&args) {
+        Ok(config) => config,
+        Err(err) => {
+            log::error!("Failed to start: {}", err);
+            return;
+        }
+    };
+
+    // Adaptive mode would learn from (and save) recorded motion
+    if args.replay.is_some() && config.adaptive_enabled {
+        log::info!("Adaptive mode is off for replays.");
+        config.adaptive_enabled = false;
+    }
+
+    // Only one daemon per display: two would fight over the cursor.
+    // A replay never touches the display, so it needs no lock.
+    let mut instance = if args.replay.is_none() {
+        match instance::InstanceLock::acquire(args.replace) {
+            Ok(lock) => Some(lock),
+            Err(err) => {
+                log::error!("Failed to start: {}", err);
+                return;
+            }
+        }
+    } else {
+        None
+    };
+
+    // Strategy pattern: pick the backend for the current session
+    // (or the one named by --backend) and connect to it. A recorded
+    // trace replaces the display server with --replay.
+    let selected = match &args.replay {
+        Some(path) => {
+            let mut backend = registry::replay(path, args.replay_speed);
+            backend.connect().map(|()| backend)
+        }
+        None => registry::select(args.backend.as_deref(), &config),
+    };
+    let backend = match selected {
+        Ok(backend) => backend,
+        Err(err) => {
+            log::error!("Failed to start: {}", err);
+            return;
+        }
+    };
+    log::info!("Connected to display server. Listening for mouse motion.");
 
-    // Create shake detector with the config
-    let mut detector = ShakeDetector::new(config);
+    // The cursor state machine, driving the locate effects built from
+    // the config
+    let mut controller =
+        Controller::new(backend, Box::new(SystemClock::start()), config.clone(), effect::build_chain);
 
-    // Create and connect the X11 backend
-    let mut backend = X11Backend::new();
-    if let Err(err) = backend.connect() {
+    // The learned baseline of adaptive mode, kept between runs. A replay
+    // leaves it alone, even if adaptive mode is switched on later.
+    if args.replay.is_none()
+        && let Some(path) = adaptive_state_path()
+    {
+        controller.set_adaptive(Adaptive::load(&path));
+    }
+
+    // State shared with the control front ends, which wake the event
+    // loop through the backend's waker
+    if args.paused {
+        log::info!("Starting with detection paused.");
+    }
+    let control = Arc::new(Mutex::new(ControlState {
+        paused: args.paused,
+        cursor_state: CursorState::Idle.name(),
+        config: config.clone(),
+        metrics: controller.shared_metrics(),
+        pending: Vec::new(),
+        waker: controller.waker(),
+    }));
+
+    // Signals wake the event loop: SIGTERM/SIGINT shut down cleanly,
+    // SIGHUP reloads the config, SIGUSR1 toggles pause and SIGUSR2
+    // triggers the effects once
+    if let Err(err) = signals::start_dispatcher(Arc::clone(&control)) {
         log::error!("Failed to start: {}", err);
+        controller.shutdown();
         return;
     }
-    log::info!("Connected to X server. Listening for mouse motion.");
-
-    // Set up signal handler for clean shutdown (SIGTERM, SIGINT).
-    // AtomicBool is checked each iteration of the event loop.
-    let shutdown = Arc::new(AtomicBool::new(false));
-    signal_hook::flag::register(signal_hook::consts::SIGTERM, Arc::clone(&shutdown))
-        .expect("Failed to register SIGTERM handler");
-    signal_hook::flag::register(signal_hook::consts::SIGINT, Arc::clone(&shutdown))
-        .expect("Failed to register SIGINT handler");
-
-    // Event loop: runs until shutdown signal or X server disconnect
-    loop {
-        // Check if a shutdown signal was received
-        if shutdown.load(Ordering::Relaxed) {
-            log::info!("Shutdown signal received.");
-            break;
+
+    // A later `--replace` takes the lock away; shut down when it does
+    if let Some(instance) = &mut instance {
+        instance.watch_for_replacement(Arc::clone(&control));
+    }
+
+    // Start the control socket for `shake-cursor ctl`. The daemon still
+    // runs without it, only signals can control it then. A replay gets
+    // its own, so it never stands in for the daemon of the display.
+    let control_socket = match control::start_server(Arc::clone(&control), args.replay.is_some()) {
+        Ok(path) => {
+            log::info!("Control socket listening on {}", path.display());
+            Some(path)
         }
+        Err(err) => {
+            log::warn!("Control socket disabled: {}", err);
+            None
+        }
+    };
 
-        // Block until next mouse motion event (sleeps at 0% CPU)
-        let event = match backend.next_motion_event() {
-            Some(event) => event,
-            None => {
-                log::warn!("X server connection lost.");
-                break;
+    // Expose the same controls on the session bus. Optional as well:
+    // there may be no session bus, e.g. when started from a bare X session.
+    // The bus name is the display's daemon's, so a replay goes without.
+    let dbus = match args.replay {
+        Some(_) => None,
+        None => match dbus::DbusService::start(Arc::clone(&control)) {
+            Ok(service) => {
+                log::info!("Serving org.shakecursor.Daemon on the session bus");
+                Some(service)
             }
-        };
+            Err(err) => {
+                log::warn!("D-Bus interface disabled: {}", err);
+                None
+            }
+        },
+    };
+    let mut reported = (CursorState::Idle.name(), false);
 
-        let timestamp = event.timestamp;
-        log::debug!("Motion: x={}, y={}, t={}", event.x, event.y, timestamp);
-
-        // Feed the event into the shake detector's ring buffer
-        detector.record_motion(event);
-
-        // State machine transitions based on shake detection
-        match detector.state {
-            CursorState::Idle => {
-                if detector.is_shaking() {
-                    log::info!("Shake detected, enlarging cursor.");
-                    if let Err(err) = backend.set_cursor_size(enlarged_size) {
-                        log::error!("Failed to enlarge cursor: {}", err);
-                    }
-                    detector.state = CursorState::Enlarged { since: timestamp };
-                }
+    // With --record, log every motion event and decision to a trace
+    if let Some(path) = &args.record {
+        match trace::TraceWriter::create(path, &config) {
+            Ok(writer) => {
+                log::info!("Recording motion trace to {}", path.display());
+                controller.record_to(writer);
+            }
+            Err(err) => {
+                log::error!("Failed to start: {}", err);
+                controller.shutdown();
+                return;
+            }
+        }
+    }
+
+    // Under systemd (Type=notify), report readiness now that the backend
+    // is connected. With WatchdogSec set, a timer wakes the loop so it
+    // can keep pinging while the pointer is still.
+    let mut notifier = notify::Notifier::from_env();
+    if let Some(notifier) = &notifier {
+        notifier.ready();
+        notifier.status(&status_text(reported));
+        if let Some(timer) = notifier.watchdog_timer() {
+            notify::start_timer(timer, controller.waker());
+        }
+    }
+
+    // With metrics_textfile set (now or later, with `ctl set`), a timer
+    // wakes the loop so the file stays current while the pointer is still
+    let mut metrics_timer = false;
+    let mut metrics_written: Option<Instant> = None;
+
+    // Whether detection is paused, as of the last look at the control
+    // state, which happens once per event
+    let mut paused = args.paused;
+
+    // Event loop: runs until shutdown signal or display server disconnect
+    'events: loop {
+        if let Some(notifier) = &mut notifier {
+            notifier.watchdog();
+        }
+
+        // Publish a state change left by the previous iteration before
+        // blocking. Metrics need no publishing: the controller updates
+        // the shared copy in place.
+        let current = (controller.state_name(), paused);
+        if current != reported {
+            reported = current;
+            control.lock().unwrap().cursor_state = current.0;
+            if let Some(dbus) = &dbus {
+                dbus.state_changed();
+            }
+            if let Some(notifier) = &notifier {
+                notifier.status(&status_text(current));
             }
-            CursorState::Enlarged { since } => {
-                if detector.is_shaking() {
-                    // Still shaking — reset the cooldown timer
-                    detector.state = CursorState::Enlarged { since: timestamp };
-                } else if timestamp.saturating_sub(since) >= cooldown_ms {
-                    // Cooldown expired — begin restoring
-                    log::info!("Cooldown expired, restoring cursor.");
-                    detector.state = CursorState::Restoring;
+        }
+        if metrics_written.is_none_or(|written| written.elapsed() >= METRICS_INTERVAL) {
+            let textfile = control.lock().unwrap().config.metrics_textfile.clone();
+            metrics_written = Some(Instant::now());
+            if let Some(path) = textfile {
+                if let Err(err) = controller.metrics().write_textfile(&path) {
+                    log::warn!("Metrics textfile not updated: {}", err);
+                }
+                if !metrics_timer {
+                    notify::start_timer(METRICS_INTERVAL, controller.waker());
+                    metrics_timer = true;
                 }
             }
-            CursorState::Restoring => {
-                if let Err(err) = backend.set_cursor_size(24) {
-                    log::error!("Failed to restore cursor: {}", err);
+        }
+
+        // Block until next mouse motion event or hotkey, or until a
+        // control request or signal wakes the loop (sleeps at 0% CPU)
+        let Some(event) = controller.next_event() else {
+            log::warn!("Display server connection lost.");
+            break;
+        };
+
+        // Apply anything control clients or signals asked for. Their
+        // requests wake the loop, so this runs as soon as they arrive.
+        let actions = {
+            let mut control = control.lock().unwrap();
+            paused = control.paused;
+            std::mem::take(&mut control.pending)
+        };
+        for action in actions {
+            match action {
+                ControlAction::Trigger => controller.trigger(),
+                ControlAction::Dismiss => controller.dismiss(),
+                ControlAction::Pause => controller.pause(),
+                ControlAction::Shutdown => {
+                    log::info!("Shutdown signal received.");
+                    break 'events;
+                }
+                ControlAction::Reload | ControlAction::ApplyConfig => {
+                    let config = if matches!(action, ControlAction::Reload) {
+                        match load_config(&args) {
+                            Ok(mut config) => {
+                                control::keep_restart_keys(&control.lock().unwrap().config, &mut config);
+                                config
+                            }
+                            Err(err) => {
+                                log::error!("Reload failed, keeping current config: {}", err);
+                                continue;
+                            }
+                        }
+                    } else {
+                        control.lock().unwrap().config.clone()
+                    };
+                    control.lock().unwrap().config = config.clone();
+                    controller.apply_config(config);
+                    // Write a newly set metrics textfile right away
+                    metrics_written = None;
                 }
-                detector.state = CursorState::Idle;
             }
         }
+
+        if let Some(shake) = controller.handle(event, paused)
+            && let Some(dbus) = &dbus
+        {
+            dbus.shake_detected(shake.x, shake.y);
+        }
     }
 
-    // Clean shutdown: restore original cursor and disconnect
+    // Clean shutdown: stop any active effects and disconnect
     log::info!("Restoring cursor and disconnecting.");
-    let _ = backend.set_cursor_size(24);
-    backend.disconnect();
+    if let Some(notifier) = &notifier {
+        notifier.stopping();
+    }
+    controller.shutdown();
+    if let Some(path) = &control.lock().unwrap().config.metrics_textfile
+        && let Err(err) = controller.metrics().write_textfile(path)
+    {
+        log::warn!("Metrics textfile not updated: {}", err);
+    }
+    if let Some(path) = control_socket {
+        control::remove_socket(&path);
+    }
+}
+
+/// Status line for `systemctl status`, from the published
+/// (cursor state, paused) pair.
+fn status_text((cursor_state, paused): (&str, bool)) -> String {
+    if paused {
+        format!("Cursor {}, detection paused", cursor_state)
+    } else {
+        format!("Cursor {}, watching for shakes", cursor_state)
+    }
+}
+
+/// Where the adaptive baseline is kept: `$XDG_STATE_HOME/shake-cursor/adaptive`,
+/// falling back to `~/.local/state/shake-cursor/adaptive`.
+fn adaptive_state_path() -> Option<PathBuf> {
+    let base = env::var_os("XDG_STATE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("state")))?;
+    Some(base.join("shake-cursor").join("adaptive"))
+}
+
+/// Load the config file (the `--config` path, or the default location if
+/// it exists), then the recorded config of a `--replay`, then apply
+/// command-line overrides on top.
+fn load_config(args: &Args) -> Result<Config, String> {
+    let mut config = match &args.config_path {
+        Some(path) => Config::load(path)?,
+        None => match Config::default_path() {
+            Some(path) if path.exists() => Config::load(&path)?,
+            _ => Config::default(),
+        },
+    };
+
+    if let Some(trace) = &args.replay {
+        apply_recorded_config(&mut config, trace, args.replay_config)?;
+    }
+
+    if let Some(effects) = &args.effects {
+        config.effects = effects.clone();
+    }
+    if let Some(cursor) = &args.enlarged_cursor {
+        config.enlarged_cursor = Some(cursor.clone());
+    }
+    if let Some(hotkey) = &args.hotkey {
+        config.hotkey = hotkey.clone();
+        config.hotkey_enabled = true;
+    }
+    Ok(config)
+}
+
+/// Replay with the configuration recorded in the trace header, unless
+/// `--replay-config current` keeps the loaded one. Either way, warn about
+/// the values that differ, since they change what the replay detects.
+fn apply_recorded_config(config: &mut Config, trace: &Path, mode: ReplayConfig) -> Result<(), String> {
+    let Some(recorded) = replay_backend::recorded_config(trace)? else {
+        log::warn!("{} has no header; replaying with the current config.", trace.display());
+        return Ok(());
+    };
+
+    let current = config.entries();
+    let mut differences = Vec::new();
+    for (key, value) in recorded {
+        // Keys this version doesn't know can't be compared or applied
+        let Some((_, ours)) = current.iter().find(|(name, _)| *name == key) else {
+            log::warn!("Ignoring recorded {} = {}: unknown key", key, value);
+            continue;
+        };
+        if *ours == value {
+            continue;
+        }
+        differences.push(format!("{} = {} (config: {})", key, value, ours));
+        if mode == ReplayConfig::Recorded
+            && let Err(err) = config.set(&key, &value)
+        {
+            log::warn!("Ignoring recorded {} = {}: {}", key, value, err);
+        }
+    }
+
+    if !differences.is_empty() {
+        log::warn!(
+            "{} was recorded with {}; replaying with the {} values.",
+            trace.display(),
+            differences.join(", "),
+            if mode == ReplayConfig::Recorded { "recorded" } else { "current" }
+        );
+    }
+    Ok(())
 }
@@ -0,0 +1,220 @@
+use std::env;
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use shake_cursor_core::backend::Waker;
+
+/// Native sd_notify(3): reports readiness, status and watchdog pings to
+/// systemd for `Type=notify` units.
+///
+/// The protocol is a newline-separated `KEY=value` datagram sent to the
+/// socket in $NOTIFY_SOCKET, so it needs no libsystemd. Outside systemd
+/// NOTIFY_SOCKET is unset and from_env() returns None.
+pub struct Notifier {
+    socket: UnixDatagram,
+    addr: SocketAddr,
+
+    /// How often to send WATCHDOG=1: half of $WATCHDOG_USEC, as
+    /// sd_watchdog_enabled(3) recommends. None if the unit has no
+    /// WatchdogSec.
+    watchdog_interval: Option<Duration>,
+
+    /// When WATCHDOG=1 was last sent.
+    last_watchdog: Instant,
+}
+
+impl Notifier {
+    /// Connect to $NOTIFY_SOCKET, if systemd set it.
+    pub fn from_env() -> Option<Self> {
+        let path = env::var("NOTIFY_SOCKET").ok().filter(|path| !path.is_empty())?;
+
+        // WATCHDOG_PID names the process the watchdog is meant for
+        let for_us = env::var("WATCHDOG_PID")
+            .map_or(true, |pid| pid.parse() == Ok(std::process::id()));
+        let watchdog_usec = env::var("WATCHDOG_USEC")
+            .ok()
+            .and_then(|usec| usec.parse::<u64>().ok())
+            .filter(|&usec| usec > 0 && for_us);
+
+        match Self::new(&path, watchdog_usec.map(Duration::from_micros)) {
+            Ok(notifier) => Some(notifier),
+            Err(err) => {
+                log::warn!("systemd notifications disabled: {}", err);
+                None
+            }
+        }
+    }
+
+    /// Notifier for the socket at `path`, which may name an abstract
+    /// socket with a leading '@'. `watchdog` is the unit's WatchdogSec.
+    pub fn new(path: &str, watchdog: Option<Duration>) -> Result<Self, String> {
+        let addr = match path.strip_prefix('@') {
+            Some(name) => SocketAddr::from_abstract_name(name),
+            None => SocketAddr::from_pathname(path),
+        }
+        .map_err(|err| format!("Invalid NOTIFY_SOCKET '{}': {}", path, err))?;
+        let socket = UnixDatagram::unbound()
+            .map_err(|err| format!("Failed to create notify socket: {}", err))?;
+
+        Ok(Self {
+            socket,
+            addr,
+            watchdog_interval: watchdog.map(|timeout| timeout / 2),
+            last_watchdog: Instant::now(),
+        })
+    }
+
+    fn send(&self, message: &str) {
+        if let Err(err) = self.socket.send_to_addr(message.as_bytes(), &self.addr) {
+            log::debug!("sd_notify '{}' failed: {}", message, err);
+        }
+    }
+
+    /// Tell systemd startup has finished.
+    pub fn ready(&self) {
+        self.send("READY=1");
+    }
+
+    /// Set the one-line status shown by `systemctl status`.
+    pub fn status(&self, status: &str) {
+        self.send(&format!("STATUS={}", status));
+    }
+
+    /// Tell systemd the daemon is shutting down.
+    pub fn stopping(&self) {
+        self.send("STOPPING=1");
+    }
+
+    /// How often to wake the event loop so it calls watchdog() in time:
+    /// twice per interval, since a call just before a ping is due leaves
+    /// it for the next one.
+    pub fn watchdog_timer(&self) -> Option<Duration> {
+        self.watchdog_interval.map(|interval| interval / 2)
+    }
+
+    /// Send WATCHDOG=1 if it is due. Called from the event loop, so a
+    /// stuck loop stops the pings and systemd restarts the daemon.
+    pub fn watchdog(&mut self) {
+        if let Some(interval) = self.watchdog_interval
+            && self.last_watchdog.elapsed() >= interval
+        {
+            self.send("WATCHDOG=1");
+            self.last_watchdog = Instant::now();
+        }
+    }
+}
+
+/// Wake the event loop every `interval`, so it keeps sending watchdog
+/// pings (or writing metrics) while the pointer is still.
+pub fn start_timer(interval: Duration, waker: Waker) {
+    thread::spawn(move || {
+        loop {
+            thread::sleep(interval);
+            waker();
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::TempDir;
+
+    /// A fake NOTIFY_SOCKET listener in `dir`.
+    fn listener(dir: &TempDir) -> (UnixDatagram, String) {
+        let path = dir.0.join("notify");
+        let socket = UnixDatagram::bind(&path).unwrap();
+        socket.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+        (socket, path.to_str().unwrap().to_string())
+    }
+
+    fn receive(socket: &UnixDatagram) -> String {
+        let mut buf = [0u8; 256];
+        let len = socket.recv(&mut buf).unwrap();
+        String::from_utf8_lossy(&buf[..len]).into_owned()
+    }
+
+    #[test]
+    fn sends_ready_status_and_stopping() {
+        let dir = TempDir::new("notify-messages");
+        let (socket, path) = listener(&dir);
+        let notifier = Notifier::new(&path, None).unwrap();
+
+        notifier.ready();
+        notifier.status("enlarged");
+        notifier.stopping();
+
+        assert_eq!(receive(&socket), "READY=1");
+        assert_eq!(receive(&socket), "STATUS=enlarged");
+        assert_eq!(receive(&socket), "STOPPING=1");
+    }
+
+    #[test]
+    fn watchdog_pings_at_half_the_timeout() {
+        let dir = TempDir::new("notify-watchdog");
+        let (socket, path) = listener(&dir);
+        let mut notifier = Notifier::new(&path, Some(Duration::from_millis(100))).unwrap();
+        assert_eq!(notifier.watchdog_interval, Some(Duration::from_millis(50)));
+        assert_eq!(notifier.watchdog_timer(), Some(Duration::from_millis(25)));
+
+        // Not due yet: nothing is sent
+        notifier.watchdog();
+        thread::sleep(Duration::from_millis(60));
+        notifier.watchdog();
+        notifier.watchdog();
+
+        socket.set_nonblocking(true).unwrap();
+        assert_eq!(receive(&socket), "WATCHDOG=1");
+        assert!(socket.recv(&mut [0u8; 16]).is_err(), "only one ping per interval");
+    }
+
+    #[test]
+    fn timer_wakeups_keep_pings_on_time() {
+        let dir = TempDir::new("notify-timer");
+        let (socket, path) = listener(&dir);
+        socket.set_nonblocking(true).unwrap();
+        let mut notifier = Notifier::new(&path, Some(Duration::from_millis(200))).unwrap();
+        let interval = notifier.watchdog_interval.unwrap();
+        let timer = notifier.watchdog_timer().unwrap();
+        let late = Duration::from_millis(10);
+
+        // The timer ticks on its own schedule; the loop gets to some ticks
+        // late, so a ping can be sent a little after it was due and the
+        // next tick comes a little before the following one is
+        let started = Instant::now();
+        let mut pings = Vec::new();
+        for tick in 1..=12 {
+            let handled = timer * tick + if tick % 2 == 0 { late } else { Duration::ZERO };
+            thread::sleep(handled.saturating_sub(started.elapsed()));
+            notifier.watchdog();
+            if socket.recv(&mut [0u8; 16]).is_ok() {
+                pings.push(started.elapsed());
+            }
+        }
+
+        // Well inside the timeout (2 * interval), with room for scheduling
+        assert!(pings.len() >= 3, "pings at {:?}", pings);
+        for gap in pings.windows(2).map(|pair| pair[1] - pair[0]) {
+            assert!(gap < interval * 3 / 2 + 3 * late, "{:?} between pings", gap);
+        }
+    }
+
+    #[test]
+    fn no_watchdog_without_watchdog_sec() {
+        let dir = TempDir::new("notify-disabled");
+        let (socket, path) = listener(&dir);
+        let mut notifier = Notifier::new(&path, None).unwrap();
+
+        notifier.watchdog();
+        notifier.ready();
+
+        assert_eq!(receive(&socket), "READY=1");
+    }
+
+    #[test]
+    fn abstract_socket_address() {
+        assert!(Notifier::new("@shake-cursor-test", None).is_ok());
+    }
+}
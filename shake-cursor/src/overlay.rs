@@ -0,0 +1,794 @@
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use x11rb::connection::Connection;
+use x11rb::protocol::shape;
+use x11rb::protocol::xfixes;
+use x11rb::protocol::xproto::*;
+use x11rb::rust_connection::RustConnection;
+
+use shake_cursor_core::backend::DisplayBackend;
+use shake_cursor_core::config::RING_DIAMETER;
+use shake_cursor_core::effect::CursorEffect;
+
+use crate::cursor_image;
+
+/// Duration of one pulse of the ring animation in milliseconds.
+/// The ring contracts from full size towards the pointer once per period.
+const PULSE_PERIOD_MS: u32 = 600;
+
+/// Ring stroke width in pixels.
+const RING_THICKNESS: f64 = 6.0;
+
+/// Highlight colour as (red, green, blue, alpha). A saturated yellow
+/// reads well on both light and dark backgrounds.
+const HIGHLIGHT_COLOR: (u8, u8, u8, u8) = (255, 200, 0, 220);
+
+/// Crosshair line thickness in pixels.
+const CROSSHAIR_THICKNESS: u16 = 2;
+
+/// Opacity of the spotlight's dimmed area (0 = none, 255 = black).
+const SPOTLIGHT_DIM_ALPHA: u32 = 0xA0;
+
+/// A dedicated X connection for drawing overlay windows.
+///
+/// Every overlay window is:
+/// - override-redirect, so the window manager doesn't decorate or focus it
+/// - click-through, via an empty XFixes input shape, so every click passes
+///   to whatever is underneath
+///
+/// Overlays use their own connection, so they work alongside any backend
+/// as long as an X server (Xorg or XWayland) is reachable. Motion events
+/// still arrive on the backend's connection.
+struct OverlayConnection {
+    conn: RustConnection,
+    root: Window,
+    screen_width: u16,
+    screen_height: u16,
+
+    /// A 32-bit TrueColor visual for per-pixel alpha, if the server has one.
+    argb_visual: Option<Visualid>,
+
+    /// True if a compositing manager owns _NET_WM_CM_Sn, meaning the
+    /// alpha channel of ARGB windows is honoured.
+    composited: bool,
+}
+
+impl OverlayConnection {
+    fn connect() -> Result<Self, String> {
+        let (conn, screen_num) = RustConnection::connect(None)
+            .map_err(|err| format!("Failed to connect to X server: {}", err))?;
+        let screen = &conn.setup().roots[screen_num];
+        let root = screen.root;
+        let screen_width = screen.width_in_pixels;
+        let screen_height = screen.height_in_pixels;
+
+        let argb_visual = screen
+            .allowed_depths
+            .iter()
+            .filter(|depth| depth.depth == 32)
+            .flat_map(|depth| depth.visuals.iter())
+            .find(|visual| visual.class == VisualClass::TRUE_COLOR)
+            .map(|visual| visual.visual_id);
+
+        xfixes::query_version(&conn, 6, 0)
+            .map_err(|err| format!("Failed to query XFixes: {}", err))?
+            .reply()
+            .map_err(|err| format!("XFixes not supported: {}", err))?;
+
+        // A compositing manager announces itself by owning this selection.
+        let cm_atom = conn.intern_atom(false, format!("_NET_WM_CM_S{}", screen_num).as_bytes())
+            .map_err(|err| format!("Failed to intern atom: {}", err))?
+            .reply()
+            .map_err(|err| format!("Failed to intern atom: {}", err))?
+            .atom;
+        let composited = conn.get_selection_owner(cm_atom)
+            .map_err(|err| format!("Failed to query selection owner: {}", err))?
+            .reply()
+            .map_err(|err| format!("Failed to query selection owner: {}", err))?
+            .owner != x11rb::NONE;
+
+        Ok(Self {
+            conn,
+            root,
+            screen_width,
+            screen_height,
+            argb_visual,
+            composited,
+        })
+    }
+
+    /// Create an unmapped, override-redirect, click-through window.
+    /// With `argb`, the window uses the 32-bit visual and `background`
+    /// is a premultiplied ARGB pixel; otherwise it inherits the root's
+    /// visual and `background` is a 0xRRGGBB TrueColor pixel.
+    fn create_window(&self, argb: bool, width: u16, height: u16, background: u32) -> Result<Window, String> {
+        let window = self.conn.generate_id()
+            .map_err(|err| format!("Failed to allocate window ID: {}", err))?;
+        let mut aux = CreateWindowAux::new()
+            .background_pixel(background)
+            .border_pixel(0)
+            .override_redirect(1);
+
+        let (depth, visual) = if argb {
+            let visual = self.argb_visual
+                .ok_or_else(|| "X server has no 32-bit TrueColor visual".to_string())?;
+
+            // An ARGB window must set border_pixel and colormap explicitly,
+            // otherwise it inherits the root's depth-24 values (BadMatch).
+            let colormap = self.conn.generate_id()
+                .map_err(|err| format!("Failed to allocate colormap ID: {}", err))?;
+            create_colormap(&self.conn, ColormapAlloc::NONE, colormap, self.root, visual)
+                .map_err(|err| format!("Failed to create colormap: {}", err))?;
+            aux = aux.colormap(colormap);
+            (32, visual)
+        } else {
+            (x11rb::COPY_DEPTH_FROM_PARENT, x11rb::COPY_FROM_PARENT)
+        };
+
+        create_window(
+            &self.conn,
+            depth,
+            window,
+            self.root,
+            0,
+            0,
+            width.max(1),
+            height.max(1),
+            0,
+            WindowClass::INPUT_OUTPUT,
+            visual,
+            &aux,
+        )
+        .map_err(|err| format!("Failed to create overlay window: {}", err))?
+        .check()
+        .map_err(|err| format!("X server rejected overlay window: {}", err))?;
+
+        // Click-through: an empty input region means the window never
+        // receives pointer events.
+        self.set_shape(window, shape::SK::INPUT, &[])?;
+        Ok(window)
+    }
+
+    /// Replace a window's bounding or input shape with the given rectangles.
+    fn set_shape(&self, window: Window, kind: shape::SK, rectangles: &[Rectangle]) -> Result<(), String> {
+        let region = self.conn.generate_id()
+            .map_err(|err| format!("Failed to allocate region ID: {}", err))?;
+        xfixes::create_region(&self.conn, region, rectangles)
+            .map_err(|err| format!("Failed to create region: {}", err))?;
+        xfixes::set_window_shape_region(&self.conn, window, kind, 0, 0, region)
+            .map_err(|err| format!("Failed to set window shape: {}", err))?;
+        xfixes::destroy_region(&self.conn, region)
+            .map_err(|err| format!("Failed to destroy region: {}", err))?;
+        Ok(())
+    }
+
+    /// Move a window and raise it above everything else.
+    fn place(&self, window: Window, x: i32, y: i32) -> Result<(), String> {
+        let aux = ConfigureWindowAux::new()
+            .x(x)
+            .y(y)
+            .stack_mode(StackMode::ABOVE);
+        configure_window(&self.conn, window, &aux)
+            .map_err(|err| format!("Failed to move overlay: {}", err))?;
+        Ok(())
+    }
+
+    fn map(&self, window: Window) -> Result<(), String> {
+        map_window(&self.conn, window)
+            .map_err(|err| format!("Failed to map overlay: {}", err))?;
+        Ok(())
+    }
+
+    fn unmap(&self, window: Window) -> Result<(), String> {
+        unmap_window(&self.conn, window)
+            .map_err(|err| format!("Failed to unmap overlay: {}", err))?;
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<(), String> {
+        self.conn.flush()
+            .map_err(|err| format!("Failed to flush X connection: {}", err))
+    }
+
+    /// Destroy overlay windows so they never outlive the daemon.
+    fn destroy(&self, windows: &[Window]) {
+        for &window in windows {
+            let _ = destroy_window(&self.conn, window);
+        }
+        let _ = self.conn.flush();
+    }
+}
+
+/// An animated translucent ring drawn around the pointer.
+///
+/// Drawn into a square ARGB window that a compositing manager blends over
+/// the windows below. Without a compositing manager the alpha channel is
+/// ignored, so the bounding shape is clipped to the ring's pixels instead.
+///
+/// Motion only moves the window. The pulse is animated by a thread of its
+/// own, so it keeps going while the pointer is still, and a frame is only
+/// drawn when its phase or intensity step differs from the one on screen.
+pub struct RingEffect {
+    ring: Arc<Ring>,
+
+    /// The animation thread, until the effect is dropped.
+    animator: Option<JoinHandle<()>>,
+}
+
+/// The part of the ring shared with its animation thread.
+struct Ring {
+    overlay: OverlayConnection,
+
+    /// The ring window, unmapped while the effect is inactive.
+    window: Window,
+
+    /// Graphics context used for put_image.
+    gc: Gcontext,
+
+    /// Width and height of the square ring window in pixels.
+    diameter: u16,
+
+    animation: Mutex<Animation>,
+
+    /// Signalled when the animation starts, or the effect is dropped.
+    changed: Condvar,
+}
+
+/// What the animation thread draws.
+struct Animation {
+    /// When the running effect started; None while inactive.
+    started: Option<Instant>,
+
+    /// Shake intensity from the latest update(), 0.0 to 1.0.
+    intensity: f64,
+
+    /// The frame on screen, to skip drawing it again.
+    drawn: Option<RingFrame>,
+
+    /// Set when the effect is dropped, to end the thread.
+    closed: bool,
+}
+
+/// One distinct animation frame: a step of the pulse and a step of the
+/// intensity it fades with.
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct RingFrame {
+    phase: u32,
+    intensity: u32,
+}
+
+/// Animation steps per pulse, which sets the frame rate while the
+/// effect runs: one frame every PULSE_PERIOD_MS / PHASE_STEPS.
+const PHASE_STEPS: u32 = 20;
+
+/// Steps the ring's opacity fades through as the intensity drops.
+const INTENSITY_STEPS: u32 = 8;
+
+impl RingFrame {
+    fn at(elapsed: Duration, intensity: f64) -> Self {
+        let elapsed_ms = elapsed.as_millis() as u64 % PULSE_PERIOD_MS as u64;
+        Self {
+            phase: (elapsed_ms * PHASE_STEPS as u64 / PULSE_PERIOD_MS as u64) as u32,
+            intensity: (intensity.clamp(0.0, 1.0) * INTENSITY_STEPS as f64).round() as u32,
+        }
+    }
+}
+
+impl RingEffect {
+    /// Connect to the X server, create the (unmapped) ring window, and
+    /// start its animation thread. `diameter` is clamped to
+    /// RING_DIAMETER, which Config::set() already enforces.
+    pub fn new(diameter: u32) -> Result<Self, String> {
+        let overlay = OverlayConnection::connect()?;
+        if !overlay.composited {
+            log::warn!("No compositing manager running; the ring will be drawn without translucency.");
+        }
+
+        let diameter = diameter.clamp(*RING_DIAMETER.start(), *RING_DIAMETER.end()) as u16;
+        let window = overlay.create_window(true, diameter, diameter, 0)?;
+
+        let gc = overlay.conn.generate_id()
+            .map_err(|err| format!("Failed to allocate GC ID: {}", err))?;
+        create_gc(&overlay.conn, gc, window, &CreateGCAux::new())
+            .map_err(|err| format!("Failed to create GC: {}", err))?;
+        overlay.flush()?;
+
+        let ring = Arc::new(Ring {
+            overlay,
+            window,
+            gc,
+            diameter,
+            animation: Mutex::new(Animation { started: None, intensity: 1.0, drawn: None, closed: false }),
+            changed: Condvar::new(),
+        });
+        let animator = {
+            let ring = Arc::clone(&ring);
+            thread::spawn(move || ring.animate())
+        };
+
+        Ok(Self { ring, animator: Some(animator) })
+    }
+}
+
+impl Ring {
+    /// Center the window on (x, y).
+    fn place(&self, x: i16, y: i16) -> Result<(), String> {
+        let half = (self.diameter / 2) as i32;
+        self.overlay.place(self.window, x as i32 - half, y as i32 - half)?;
+        self.overlay.flush()
+    }
+
+    /// Draw frames while the effect runs, each when it is due and
+    /// differs from the one on screen.
+    fn animate(&self) {
+        let step = Duration::from_millis((PULSE_PERIOD_MS / PHASE_STEPS) as u64);
+        let mut animation = self.animation.lock().unwrap();
+        loop {
+            if animation.closed {
+                return;
+            }
+            let Some(started) = animation.started else {
+                animation = self.changed.wait(animation).unwrap();
+                continue;
+            };
+
+            let frame = RingFrame::at(started.elapsed(), animation.intensity);
+            if animation.drawn != Some(frame) {
+                animation.drawn = Some(frame);
+                drop(animation);
+                if let Err(err) = self.draw(frame) {
+                    log::warn!("ring effect: {}", err);
+                }
+                animation = self.animation.lock().unwrap();
+            }
+            animation = self.changed.wait_timeout(animation, step).unwrap().0;
+        }
+    }
+
+    /// Draw one animation frame into the window.
+    fn draw(&self, frame: RingFrame) -> Result<(), String> {
+        let phase = frame.phase as f64 / PHASE_STEPS as f64;
+        let intensity = frame.intensity as f64 / INTENSITY_STEPS as f64;
+        let pixels = ring_frame(self.diameter, intensity, phase);
+
+        if !self.overlay.composited {
+            let rectangles = opaque_runs(&pixels, self.diameter);
+            self.overlay.set_shape(self.window, shape::SK::BOUNDING, &rectangles)?;
+        }
+
+        let data = cursor_image::image_data(&pixels, self.overlay.conn.setup().image_byte_order);
+        put_image(
+            &self.overlay.conn,
+            ImageFormat::Z_PIXMAP,
+            self.window,
+            self.gc,
+            self.diameter,
+            self.diameter,
+            0,
+            0,
+            0,
+            32,
+            &data,
+        )
+        .map_err(|err| format!("Failed to draw overlay: {}", err))?;
+
+        self.overlay.flush()
+    }
+}
+
+impl CursorEffect for RingEffect {
+    fn name(&self) -> &'static str {
+        "ring"
+    }
+
+    /// Draw the first frame, then show the window and hand over to the
+    /// animation thread.
+    fn start(&mut self, _backend: &mut dyn DisplayBackend, x: i16, y: i16) -> Result<(), String> {
+        let frame = RingFrame::at(Duration::ZERO, 1.0);
+        self.ring.draw(frame)?;
+        self.ring.place(x, y)?;
+        self.ring.overlay.map(self.ring.window)?;
+        self.ring.overlay.flush()?;
+
+        let mut animation = self.ring.animation.lock().unwrap();
+        animation.started = Some(Instant::now());
+        animation.intensity = 1.0;
+        animation.drawn = Some(frame);
+        self.ring.changed.notify_one();
+        Ok(())
+    }
+
+    /// Move the window along; the animation thread picks up the intensity.
+    fn update(
+        &mut self,
+        _backend: &mut dyn DisplayBackend,
+        x: i16,
+        y: i16,
+        intensity: f64,
+        _elapsed_ms: u32,
+    ) -> Result<(), String> {
+        self.ring.animation.lock().unwrap().intensity = intensity;
+        self.ring.place(x, y)
+    }
+
+    fn stop(&mut self, _backend: &mut dyn DisplayBackend) -> Result<(), String> {
+        self.ring.animation.lock().unwrap().started = None;
+        self.ring.overlay.unmap(self.ring.window)?;
+        self.ring.overlay.flush()
+    }
+}
+
+impl Drop for RingEffect {
+    fn drop(&mut self) {
+        self.ring.animation.lock().unwrap().closed = true;
+        self.ring.changed.notify_one();
+        if let Some(animator) = self.animator.take() {
+            let _ = animator.join();
+        }
+        self.ring.overlay.destroy(&[self.ring.window]);
+    }
+}
+
+/// Render one ring frame as premultiplied ARGB pixels, `diameter` square.
+///
+/// The ring radius contracts from the window edge towards a third of
+/// that size over one pulse, as `phase` goes from 0.0 to 1.0, drawing the
+/// eye inwards to the pointer. It fades as the shake intensity drops.
+/// Edges are antialiased by distance to the stroke.
+fn ring_frame(diameter: u16, intensity: f64, phase: f64) -> Vec<u32> {
+    let size = diameter as usize;
+    let center = size as f64 / 2.0;
+    let max_radius = center - RING_THICKNESS;
+    let radius = max_radius * (1.0 - 0.66 * phase);
+
+    let (red, green, blue, alpha) = HIGHLIGHT_COLOR;
+    let alpha = alpha as f64 * (0.4 + 0.6 * intensity);
+    let mut pixels = vec![0u32; size * size];
+
+    for row in 0..size {
+        for col in 0..size {
+            let dx = col as f64 + 0.5 - center;
+            let dy = row as f64 + 0.5 - center;
+            let distance = (dx * dx + dy * dy).sqrt();
+            let from_stroke = (distance - radius).abs() - RING_THICKNESS / 2.0;
+            let coverage = (0.5 - from_stroke).clamp(0.0, 1.0);
+            if coverage == 0.0 {
+                continue;
+            }
+
+            let a = alpha * coverage;
+            let premultiply = |channel: u8| (channel as f64 * a / 255.0) as u32;
+            pixels[row * size + col] =
+                (a as u32) << 24 | premultiply(red) << 16 | premultiply(green) << 8 | premultiply(blue);
+        }
+    }
+
+    pixels
+}
+
+/// The pixels of a `diameter` square frame that are at least half
+/// opaque, as one rectangle per horizontal run. Without compositing, the
+/// window's bounding shape is clipped to these.
+fn opaque_runs(pixels: &[u32], diameter: u16) -> Vec<Rectangle> {
+    let size = diameter as usize;
+    let mut rectangles = Vec::new();
+
+    for row in 0..size {
+        let mut run_start: Option<usize> = None;
+        for col in 0..=size {
+            let opaque = col < size && pixels[row * size + col] >> 24 >= 128;
+            match (opaque, run_start) {
+                (true, None) => run_start = Some(col),
+                (false, Some(start)) => {
+                    rectangles.push(Rectangle {
+                        x: start as i16,
+                        y: row as i16,
+                        width: (col - start) as u16,
+                        height: 1,
+                    });
+                    run_start = None;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    rectangles
+}
+
+/// Horizontal and vertical lines spanning the whole screen, crossing
+/// at the pointer.
+///
+/// Each line is a thin solid-colour window, so moving the crosshair is
+/// just two ConfigureWindow requests; nothing is redrawn.
+pub struct CrosshairEffect {
+    overlay: OverlayConnection,
+
+    /// Full-width window CROSSHAIR_THICKNESS pixels tall.
+    horizontal: Window,
+
+    /// Full-height window CROSSHAIR_THICKNESS pixels wide.
+    vertical: Window,
+}
+
+impl CrosshairEffect {
+    /// Connect to the X server and create the (unmapped) line windows.
+    pub fn new() -> Result<Self, String> {
+        let overlay = OverlayConnection::connect()?;
+        let (red, green, blue, _) = HIGHLIGHT_COLOR;
+        let color = (red as u32) << 16 | (green as u32) << 8 | blue as u32;
+
+        let horizontal = overlay.create_window(false, overlay.screen_width, CROSSHAIR_THICKNESS, color)?;
+        let vertical = overlay.create_window(false, CROSSHAIR_THICKNESS, overlay.screen_height, color)?;
+        overlay.flush()?;
+
+        Ok(Self {
+            overlay,
+            horizontal,
+            vertical,
+        })
+    }
+
+    fn place(&self, x: i16, y: i16) -> Result<(), String> {
+        let half = (CROSSHAIR_THICKNESS / 2) as i32;
+        self.overlay.place(self.horizontal, 0, y as i32 - half)?;
+        self.overlay.place(self.vertical, x as i32 - half, 0)?;
+        self.overlay.flush()
+    }
+}
+
+impl CursorEffect for CrosshairEffect {
+    fn name(&self) -> &'static str {
+        "crosshair"
+    }
+
+    fn start(&mut self, _backend: &mut dyn DisplayBackend, x: i16, y: i16) -> Result<(), String> {
+        self.overlay.map(self.horizontal)?;
+        self.overlay.map(self.vertical)?;
+        self.place(x, y)
+    }
+
+    fn update(
+        &mut self,
+        _backend: &mut dyn DisplayBackend,
+        x: i16,
+        y: i16,
+        _intensity: f64,
+        _elapsed_ms: u32,
+    ) -> Result<(), String> {
+        self.place(x, y)
+    }
+
+    fn stop(&mut self, _backend: &mut dyn DisplayBackend) -> Result<(), String> {
+        self.overlay.unmap(self.horizontal)?;
+        self.overlay.unmap(self.vertical)?;
+        self.overlay.flush()
+    }
+}
+
+impl Drop for CrosshairEffect {
+    fn drop(&mut self) {
+        self.overlay.destroy(&[self.horizontal, self.vertical]);
+    }
+}
+
+/// Dims the whole screen except a circle around the pointer.
+///
+/// A full-screen ARGB window filled by the server with translucent black
+/// (its background pixel), with the circle cut out of its bounding shape.
+/// Moving the spotlight only replaces the shape; nothing is redrawn.
+pub struct SpotlightEffect {
+    overlay: OverlayConnection,
+
+    /// The full-screen dimming window.
+    window: Window,
+
+    /// Radius in pixels of the undimmed circle.
+    radius: u32,
+}
+
+impl SpotlightEffect {
+    /// Connect to the X server and create the (unmapped) dimming window.
+    /// Fails without a compositing manager, since the dim layer would
+    /// then be drawn opaque black.
+    pub fn new(radius: u32) -> Result<Self, String> {
+        let overlay = OverlayConnection::connect()?;
+        if !overlay.composited {
+            return Err(String::from("spotlight requires a compositing manager"));
+        }
+
+        // Premultiplied ARGB: translucent black is just the alpha byte.
+        let background = SPOTLIGHT_DIM_ALPHA << 24;
+        let window = overlay.create_window(true, overlay.screen_width, overlay.screen_height, background)?;
+        overlay.flush()?;
+
+        Ok(Self {
+            overlay,
+            window,
+            radius,
+        })
+    }
+
+    /// Cut a circle centered on (x, y) out of the window's bounding shape.
+    fn place(&self, x: i16, y: i16) -> Result<(), String> {
+        let rectangles = spotlight_shape(
+            self.overlay.screen_width as i32,
+            self.overlay.screen_height as i32,
+            x as i32,
+            y as i32,
+            self.radius as i32,
+        );
+        self.overlay.set_shape(self.window, shape::SK::BOUNDING, &rectangles)?;
+        self.overlay.flush()
+    }
+}
+
+impl CursorEffect for SpotlightEffect {
+    fn name(&self) -> &'static str {
+        "spotlight"
+    }
+
+    fn start(&mut self, _backend: &mut dyn DisplayBackend, x: i16, y: i16) -> Result<(), String> {
+        self.place(x, y)?;
+        self.overlay.place(self.window, 0, 0)?;
+        self.overlay.map(self.window)?;
+        self.overlay.flush()
+    }
+
+    fn update(
+        &mut self,
+        _backend: &mut dyn DisplayBackend,
+        x: i16,
+        y: i16,
+        _intensity: f64,
+        _elapsed_ms: u32,
+    ) -> Result<(), String> {
+        self.place(x, y)
+    }
+
+    fn stop(&mut self, _backend: &mut dyn DisplayBackend) -> Result<(), String> {
+        self.overlay.unmap(self.window)?;
+        self.overlay.flush()
+    }
+}
+
+impl Drop for SpotlightEffect {
+    fn drop(&mut self) {
+        self.overlay.destroy(&[self.window]);
+    }
+}
+
+/// Rectangles covering a width × height screen minus a circle of `radius`
+/// around (cx, cy): one band above the circle, one below, and a left and
+/// right piece for every row the circle spans.
+fn spotlight_shape(width: i32, height: i32, cx: i32, cy: i32, radius: i32) -> Vec<Rectangle> {
+    let mut rectangles = Vec::new();
+    let mut push = |x: i32, y: i32, w: i32, h: i32| {
+        let (x0, y0) = (x.max(0), y.max(0));
+        let (x1, y1) = ((x + w).min(width), (y + h).min(height));
+        if x1 > x0 && y1 > y0 {
+            rectangles.push(Rectangle {
+                x: x0 as i16,
+                y: y0 as i16,
+                width: (x1 - x0) as u16,
+                height: (y1 - y0) as u16,
+            });
+        }
+    };
+
+    let top = cy - radius;
+    let bottom = cy + radius;
+    push(0, 0, width, top);
+    push(0, bottom, width, height - bottom);
+
+    for row in top..bottom {
+        let dy = row as f64 + 0.5 - cy as f64;
+        let half_chord = ((radius * radius) as f64 - dy * dy).max(0.0).sqrt() as i32;
+        push(0, row, cx - half_chord, 1);
+        push(cx + half_chord, row, width - (cx + half_chord), 1);
+    }
+
+    rectangles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Whether any rectangle covers pixel (x, y).
+    fn covered(rectangles: &[Rectangle], x: i32, y: i32) -> bool {
+        rectangles.iter().any(|rect| {
+            (rect.x as i32..rect.x as i32 + rect.width as i32).contains(&x)
+                && (rect.y as i32..rect.y as i32 + rect.height as i32).contains(&y)
+        })
+    }
+
+    #[test]
+    fn spotlight_leaves_the_circle_uncovered() {
+        let (width, height, cx, cy, radius) = (200, 150, 100, 70, 30);
+        let rectangles = spotlight_shape(width, height, cx, cy, radius);
+
+        for y in 0..height {
+            for x in 0..width {
+                let (dx, dy) = (x as f64 + 0.5 - cx as f64, y as f64 + 0.5 - cy as f64);
+                let distance = (dx * dx + dy * dy).sqrt();
+                if distance < radius as f64 - 1.0 {
+                    assert!(!covered(&rectangles, x, y), "({}, {}) is dimmed", x, y);
+                } else if distance > radius as f64 + 1.0 {
+                    assert!(covered(&rectangles, x, y), "({}, {}) is not dimmed", x, y);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn spotlight_is_clipped_to_the_screen() {
+        // Circle hanging over the top-left corner
+        let rectangles = spotlight_shape(200, 150, 10, 5, 40);
+        assert!(!rectangles.is_empty());
+        for rect in &rectangles {
+            assert!(rect.x >= 0 && rect.y >= 0 && rect.width > 0 && rect.height > 0, "{:?}", rect);
+            assert!(rect.x as i32 + rect.width as i32 <= 200, "{:?}", rect);
+            assert!(rect.y as i32 + rect.height as i32 <= 150, "{:?}", rect);
+        }
+        assert!(!covered(&rectangles, 0, 0));
+        assert!(covered(&rectangles, 199, 149));
+    }
+
+    #[test]
+    fn ring_is_transparent_at_the_center_and_corners() {
+        let diameter = 64;
+        for phase in [0.0, 0.5, 0.95] {
+            let pixels = ring_frame(diameter, 1.0, phase);
+            let size = diameter as usize;
+            let alpha = |x: usize, y: usize| pixels[y * size + x] >> 24;
+            assert_eq!(alpha(size / 2, size / 2), 0);
+            for (x, y) in [(0, 0), (size - 1, 0), (0, size - 1), (size - 1, size - 1)] {
+                assert_eq!(alpha(x, y), 0, "corner ({}, {})", x, y);
+            }
+            assert!(pixels.iter().any(|pixel| pixel >> 24 > 128), "no ring at phase {}", phase);
+        }
+    }
+
+    #[test]
+    fn ring_is_premultiplied_and_fades_with_intensity() {
+        let strong = ring_frame(64, 1.0, 0.0);
+        let faint = ring_frame(64, 0.0, 0.0);
+        for pixel in strong.iter().chain(&faint) {
+            let alpha = pixel >> 24;
+            for shift in [16, 8, 0] {
+                assert!((pixel >> shift) & 0xff <= alpha, "{:08x}", pixel);
+            }
+        }
+        let max_alpha = |pixels: &[u32]| pixels.iter().map(|pixel| pixel >> 24).max().unwrap();
+        assert_eq!(max_alpha(&strong), HIGHLIGHT_COLOR.3 as u32);
+        assert!(max_alpha(&faint) < max_alpha(&strong) / 2);
+    }
+
+    #[test]
+    fn opaque_runs_cover_the_opaque_pixels() {
+        let opaque = 0xff000000;
+        let pixels = [
+            0, opaque, opaque, 0,
+            opaque, 0, 0, 0x40000000,
+            0, 0, 0, 0,
+            opaque, opaque, opaque, opaque,
+        ];
+        let runs: Vec<_> = opaque_runs(&pixels, 4).iter().map(|rect| (rect.x, rect.y, rect.width)).collect();
+        assert_eq!(runs, [(1, 0, 2), (0, 1, 1), (0, 3, 4)]);
+    }
+
+    #[test]
+    fn frames_step_through_the_pulse() {
+        let step = PULSE_PERIOD_MS / PHASE_STEPS;
+        assert_eq!(RingFrame::at(Duration::ZERO, 1.0), RingFrame { phase: 0, intensity: INTENSITY_STEPS });
+        let within = RingFrame::at(Duration::from_millis((step - 1) as u64), 1.0);
+        assert_eq!(within, RingFrame::at(Duration::ZERO, 1.0));
+        assert_eq!(RingFrame::at(Duration::from_millis(step as u64), 0.5).phase, 1);
+        assert_eq!(RingFrame::at(Duration::from_millis(PULSE_PERIOD_MS as u64), 0.5).phase, 0);
+        assert_eq!(RingFrame::at(Duration::ZERO, 0.5).intensity, INTENSITY_STEPS / 2);
+        assert_eq!(RingFrame::at(Duration::ZERO, 1.7).intensity, INTENSITY_STEPS);
+    }
+}
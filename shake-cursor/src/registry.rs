@@ -0,0 +1,276 @@
+use std::env;
+use std::path::Path;
+
+use shake_cursor_core::backend::{BackendProbe, DisplayBackend};
+use shake_cursor_core::config::Config;
+
+use crate::replay_backend::ReplayBackend;
+use crate::wayland_backend::WaylandBackend;
+use crate::x11_backend::X11Backend;
+
+/// A display backend that can be selected at runtime.
+///
+/// Factory pattern: the registry stores how to probe and construct each
+/// backend, so main.rs never names a concrete backend type. To add a new
+/// backend, add one entry to BACKENDS.
+pub struct BackendEntry {
+    /// Name used by `--backend` and printed by `--list-backends`.
+    pub name: &'static str,
+
+    /// Cheap check of whether this backend can run in the current session.
+    pub probe: fn() -> BackendProbe,
+
+    /// Construct an unconnected instance of the backend, applying any
+    /// backend-specific settings from the config.
+    pub create: fn(&Config) -> Box<dyn DisplayBackend>,
+}
+
+/// Every backend compiled into this binary.
+pub const BACKENDS: &[BackendEntry] = &[
+    BackendEntry {
+        name: "x11",
+        probe: X11Backend::probe,
+        create: |config| {
+            let mut backend = X11Backend::new();
+            if let Some(cursor) = &config.enlarged_cursor {
+                backend.set_enlarged_cursor(cursor.clone());
+            }
+            if config.hotkey_enabled {
+                backend.set_hotkey(config.hotkey.clone());
+            }
+            Box::new(backend)
+        },
+    },
+    BackendEntry {
+        name: "wayland",
+        probe: WaylandBackend::probe,
+        create: |config| {
+            if config.enlarged_cursor.is_some() {
+                log::warn!("Custom cursor images are not supported on Wayland; using the theme.");
+            }
+            if config.hotkey_enabled {
+                log::warn!("The locate hotkey is not supported on Wayland; bind `shake-cursor ctl trigger` in the compositor instead.");
+            }
+            Box::new(WaylandBackend::new())
+        },
+    },
+];
+
+/// Session type reported by the login manager via XDG_SESSION_TYPE,
+/// e.g. "x11", "wayland" or "tty". None if unset.
+pub fn session_type() -> Option<String> {
+    env::var("XDG_SESSION_TYPE").ok().filter(|session| !session.is_empty())
+}
+
+/// Run every backend's probe, in registry order.
+pub fn probe_all() -> Vec<(&'static BackendEntry, BackendProbe)> {
+    BACKENDS.iter().map(|entry| (entry, (entry.probe)())).collect()
+}
+
+/// Pick the backend to use and connect to it.
+///
+/// An explicit `requested` name is always tried, even if its probe finds
+/// it unusable, since the probe may be wrong about an unusual setup; if it
+/// fails to connect, the error includes what the probe found. Otherwise
+/// the session type decides the preference order: Wayland sessions try the
+/// native Wayland backend before falling back to X11 (which under XWayland
+/// only reaches X11 windows); everything else tries X11 first. Probe
+/// warnings for the chosen backend are logged.
+pub fn select(requested: Option<&str>, config: &Config) -> Result<Box<dyn DisplayBackend>, String> {
+    let wayland_session =
+        session_type().as_deref() == Some("wayland") || env::var_os("WAYLAND_DISPLAY").is_some();
+    select_from(BACKENDS, requested, wayland_session, config).map(|(_, backend)| backend)
+}
+
+/// The backends to try, in order.
+fn order(
+    entries: &[BackendEntry],
+    requested: Option<&str>,
+    wayland_session: bool,
+) -> Result<Vec<&'static str>, String> {
+    match requested {
+        Some(name) => match entries.iter().find(|entry| entry.name == name) {
+            Some(entry) => Ok(vec![entry.name]),
+            None => {
+                let names: Vec<&str> = entries.iter().map(|entry| entry.name).collect();
+                Err(format!("Unknown backend '{}' (available: {})", name, names.join(", ")))
+            }
+        },
+        None if wayland_session => Ok(vec!["wayland", "x11"]),
+        None => Ok(vec!["x11", "wayland"]),
+    }
+}
+
+/// select() over `entries`, returning the chosen backend's name as well.
+fn select_from(
+    entries: &'static [BackendEntry],
+    requested: Option<&str>,
+    wayland_session: bool,
+    config: &Config,
+) -> Result<(&'static str, Box<dyn DisplayBackend>), String> {
+    let order = order(entries, requested, wayland_session)?;
+    let mut details = Vec::new();
+
+    for name in order {
+        let Some(entry) = entries.iter().find(|entry| entry.name == name) else {
+            continue;
+        };
+        let probe = (entry.probe)();
+        if !probe.usable {
+            if requested.is_none() {
+                log::debug!("Backend {} not usable: {}", entry.name, probe.detail);
+                details.push(format!("{}: {}", entry.name, probe.detail));
+                continue;
+            }
+            log::warn!("Trying the {} backend as requested, although {}", entry.name, probe.detail);
+        }
+
+        let mut backend = (entry.create)(config);
+        if let Err(err) = backend.connect() {
+            if requested.is_some() {
+                return Err(format!("{} backend: {} ({})", entry.name, err, probe.detail));
+            }
+            log::warn!("Backend {} failed to connect: {}", entry.name, err);
+            details.push(format!("{}: {}", entry.name, err));
+            continue;
+        }
+
+        log::info!("Using {} backend ({})", entry.name, probe.detail);
+        if let Some(warning) = &probe.warning {
+            log::warn!("{}", warning);
+        }
+        return Ok((entry.name, backend));
+    }
+
+    Err(format!("No usable display backend ({})", details.join("; ")))
+}
+
+/// Backend that plays back a recorded trace instead of connecting to a
+/// display server. Not in BACKENDS: it is never auto-selected and needs
+/// a trace file, given with `--replay`.
+pub fn replay(path: &Path, speed: f64) -> Box<dyn DisplayBackend> {
+    Box::new(ReplayBackend::new(path.to_path_buf(), speed))
+}
+
+/// Print what each probe found, for `--list-backends`.
+pub fn print_probes() {
+    println!(
+        "Session type: {}",
+        session_type().as_deref().unwrap_or("unset")
+    );
+    for (entry, probe) in probe_all() {
+        let status = if probe.usable { "usable" } else { "unavailable" };
+        println!("{:<8} {:<12} {}", entry.name, status, probe.detail);
+        if let Some(warning) = &probe.warning {
+            println!("{:<8} {:<12} warning: {}", "", "", warning);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shake_cursor_core::backend::{InputEvent, Waker};
+
+    use crate::script_backend::ScriptBackend;
+
+    /// A backend whose display server refuses the connection.
+    struct Refused;
+
+    impl DisplayBackend for Refused {
+        fn connect(&mut self) -> Result<(), String> {
+            Err("connection refused".to_string())
+        }
+
+        fn next_event(&mut self) -> Option<InputEvent> {
+            None
+        }
+
+        fn waker(&self) -> Waker {
+            std::sync::Arc::new(|| {})
+        }
+
+        fn set_cursor_size(&mut self, _: u32) -> Result<(), String> {
+            Ok(())
+        }
+
+        fn original_cursor_size(&self) -> u32 {
+            24
+        }
+
+        fn disconnect(&mut self) {}
+    }
+
+    fn usable() -> BackendProbe {
+        BackendProbe { usable: true, detail: "found".to_string(), warning: None }
+    }
+
+    fn unusable() -> BackendProbe {
+        BackendProbe { usable: false, detail: "not found".to_string(), warning: None }
+    }
+
+    fn connects(_: &Config) -> Box<dyn DisplayBackend> {
+        Box::new(ScriptBackend::new([]))
+    }
+
+    fn refused(_: &Config) -> Box<dyn DisplayBackend> {
+        Box::new(Refused)
+    }
+
+    const BOTH_USABLE: &[BackendEntry] = &[
+        BackendEntry { name: "x11", probe: usable, create: connects },
+        BackendEntry { name: "wayland", probe: usable, create: connects },
+    ];
+
+    const X11_UNUSABLE: &[BackendEntry] = &[
+        BackendEntry { name: "x11", probe: unusable, create: connects },
+        BackendEntry { name: "wayland", probe: usable, create: connects },
+    ];
+
+    const X11_REFUSED: &[BackendEntry] = &[
+        BackendEntry { name: "x11", probe: unusable, create: refused },
+        BackendEntry { name: "wayland", probe: usable, create: connects },
+    ];
+
+    fn selected(
+        entries: &'static [BackendEntry],
+        requested: Option<&str>,
+        wayland_session: bool,
+    ) -> Result<&'static str, String> {
+        select_from(entries, requested, wayland_session, &Config::default()).map(|(name, _)| name)
+    }
+
+    #[test]
+    fn session_type_decides_the_order() {
+        assert_eq!(order(BACKENDS, None, false).unwrap(), ["x11", "wayland"]);
+        assert_eq!(order(BACKENDS, None, true).unwrap(), ["wayland", "x11"]);
+        assert_eq!(order(BACKENDS, Some("wayland"), false).unwrap(), ["wayland"]);
+        let err = order(BACKENDS, Some("mir"), false).unwrap_err();
+        assert_eq!(err, "Unknown backend 'mir' (available: x11, wayland)");
+    }
+
+    #[test]
+    fn first_usable_backend_is_selected() {
+        assert_eq!(selected(BOTH_USABLE, None, false), Ok("x11"));
+        assert_eq!(selected(BOTH_USABLE, None, true), Ok("wayland"));
+        assert_eq!(selected(X11_UNUSABLE, None, false), Ok("wayland"));
+        assert_eq!(selected(X11_REFUSED, None, false), Ok("wayland"));
+    }
+
+    #[test]
+    fn requested_backend_is_tried_despite_its_probe() {
+        assert_eq!(selected(X11_UNUSABLE, Some("x11"), true), Ok("x11"));
+        let err = selected(X11_REFUSED, Some("x11"), false).unwrap_err();
+        assert_eq!(err, "x11 backend: connection refused (not found)");
+    }
+
+    #[test]
+    fn no_usable_backend_lists_what_was_found() {
+        const NONE_USABLE: &[BackendEntry] = &[
+            BackendEntry { name: "x11", probe: usable, create: refused },
+            BackendEntry { name: "wayland", probe: unusable, create: connects },
+        ];
+        let err = selected(NONE_USABLE, None, false).unwrap_err();
+        assert_eq!(err, "No usable display backend (x11: connection refused; wayland: not found)");
+    }
+}
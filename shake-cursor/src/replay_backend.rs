@@ -0,0 +1,145 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use shake_cursor_core::backend::{DisplayBackend, InputEvent, MotionEvent, Waker};
+use shake_cursor_core::trace::{TraceReader, TraceRecord};
+
+/// Plays a recorded motion trace back instead of talking to a display
+/// server, so the detector and state machine can run on any Linux box,
+/// without X or Wayland.
+///
+/// Events are delivered with the gaps they were recorded with, divided
+/// by `speed`. Cursor changes are only logged. The end of the trace acts
+/// like a lost display connection, so the daemon exits when it is done.
+pub struct ReplayBackend {
+    path: PathBuf,
+
+    /// Playback speed: 1.0 is real time, 10.0 ten times faster, and 0.0
+    /// delivers every event immediately.
+    speed: f64,
+
+    /// The trace being played. None until connect() is called.
+    reader: Option<TraceReader>,
+
+    /// Timestamp of the previous event, to reproduce the gaps.
+    last_timestamp: Option<u32>,
+
+    /// The next event, read from the trace but not yet due.
+    pending: Option<MotionEvent>,
+
+    /// When the pending event is due. Each event is scheduled relative to
+    /// the previous one's due time, so waiting doesn't accumulate drift.
+    due: Instant,
+
+    /// The waker sends on this channel; next_event() waits on it instead
+    /// of sleeping, so a wakeup interrupts the gap between events.
+    wake_rx: Receiver<()>,
+    wake_tx: Sender<()>,
+
+    /// Size reported as the user's cursor size.
+    original_cursor_size: u32,
+}
+
+impl ReplayBackend {
+    pub fn new(path: PathBuf, speed: f64) -> Self {
+        let (wake_tx, wake_rx) = mpsc::channel();
+        Self {
+            path,
+            speed,
+            reader: None,
+            last_timestamp: None,
+            pending: None,
+            due: Instant::now(),
+            wake_rx,
+            wake_tx,
+            original_cursor_size: 24,
+        }
+    }
+
+    /// The next motion event in the trace, or None at its end.
+    fn next_motion(&mut self) -> Option<MotionEvent> {
+        loop {
+            match self.reader.as_mut()?.next()? {
+                Ok(TraceRecord::Motion { event }) => return Some(event),
+                // The header was applied (or not) with the config
+                Ok(TraceRecord::Header { .. } | TraceRecord::Label { .. }) => continue,
+                Err(err) => {
+                    log::error!("Replay stopped: {}", err);
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+/// The configuration in the header of the trace at `path`, as (key,
+/// value) pairs for Config::set(), or None if the trace has no header.
+pub fn recorded_config(path: &Path) -> Result<Option<Vec<(String, String)>>, String> {
+    match TraceReader::open(path)?.next() {
+        Some(Ok(TraceRecord::Header { config })) => Ok(Some(config)),
+        Some(Err(err)) => Err(err),
+        _ => Ok(None),
+    }
+}
+
+impl DisplayBackend for ReplayBackend {
+    fn connect(&mut self) -> Result<(), String> {
+        self.reader = Some(TraceReader::open(&self.path)?);
+        log::info!("Replaying {} at {}x speed", self.path.display(), self.speed);
+        Ok(())
+    }
+
+    /// Wait out the recorded gap to the next motion event and return it.
+    fn next_event(&mut self) -> Option<InputEvent> {
+        if self.pending.is_none() {
+            let event = self.next_motion()?;
+            if let Some(last) = self.last_timestamp
+                && self.speed > 0.0
+            {
+                let gap = Duration::from_millis(event.timestamp.saturating_sub(last) as u64);
+                self.due += gap.div_f64(self.speed);
+            } else {
+                self.due = Instant::now();
+            }
+            self.pending = Some(event);
+        }
+
+        let wait = self.due.saturating_duration_since(Instant::now());
+        if wait.is_zero() {
+            // Requests are handled after every event anyway
+            while self.wake_rx.try_recv().is_ok() {}
+        } else {
+            match self.wake_rx.recv_timeout(wait) {
+                Ok(()) => return Some(InputEvent::Interrupted),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return None,
+            }
+        }
+
+        let event = self.pending.take()?;
+        self.last_timestamp = Some(event.timestamp);
+        Some(InputEvent::Motion(event))
+    }
+
+    fn waker(&self) -> Waker {
+        let wake_tx = self.wake_tx.clone();
+        Arc::new(move || {
+            let _ = wake_tx.send(());
+        })
+    }
+
+    fn set_cursor_size(&mut self, size: u32) -> Result<(), String> {
+        log::info!("Replay: cursor size {}px", size);
+        Ok(())
+    }
+
+    fn original_cursor_size(&self) -> u32 {
+        self.original_cursor_size
+    }
+
+    fn disconnect(&mut self) {
+        self.reader = None;
+    }
+}
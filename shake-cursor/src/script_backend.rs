@@ -0,0 +1,42 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use shake_cursor_core::backend::{DisplayBackend, InputEvent, MotionEvent, Waker};
+
+/// Feeds a fixed list of motion events to the controller, for the
+/// offline commands (`simulate`, `analyze`). The cursor changes it asks
+/// for are only logged, and the end of the list ends the event loop.
+pub struct ScriptBackend {
+    events: VecDeque<MotionEvent>,
+}
+
+impl ScriptBackend {
+    pub fn new(events: impl IntoIterator<Item = MotionEvent>) -> Self {
+        Self { events: events.into_iter().collect() }
+    }
+}
+
+impl DisplayBackend for ScriptBackend {
+    fn connect(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn next_event(&mut self) -> Option<InputEvent> {
+        self.events.pop_front().map(InputEvent::Motion)
+    }
+
+    fn waker(&self) -> Waker {
+        Arc::new(|| {})
+    }
+
+    fn set_cursor_size(&mut self, size: u32) -> Result<(), String> {
+        log::debug!("Scripted cursor size: {}", size);
+        Ok(())
+    }
+
+    fn original_cursor_size(&self) -> u32 {
+        24
+    }
+
+    fn disconnect(&mut self) {}
+}
@@ -0,0 +1,55 @@
+use std::thread;
+
+use signal_hook::consts::{SIGHUP, SIGINT, SIGQUIT, SIGTERM, SIGUSR1, SIGUSR2};
+use signal_hook::iterator::Signals;
+
+use crate::control::SharedControl;
+
+/// Handle signals on a background thread: SIGTERM and SIGINT shut down,
+/// SIGHUP reloads the config file, SIGUSR1 toggles pause, SIGUSR2 shows
+/// the locate effects once and SIGQUIT prints the metrics to stderr.
+///
+/// signal-hook turns each signal into an ordinary message for this
+/// thread, so handling it can lock the shared state and wake the event
+/// loop, neither of which is safe inside a signal handler. The event
+/// loop is blocked in the backend most of the time, so without the
+/// wakeup a shutdown would wait for the next mouse motion, and systemd
+/// would kill the daemon before it restored the cursor.
+pub fn start_dispatcher(control: SharedControl) -> Result<(), String> {
+    let mut signals = Signals::new([SIGTERM, SIGINT, SIGHUP, SIGUSR1, SIGUSR2, SIGQUIT])
+        .map_err(|err| format!("Failed to register signal handlers: {}", err))?;
+
+    thread::spawn(move || {
+        for signal in signals.forever() {
+            let mut control = control.lock().unwrap();
+            match signal {
+                SIGTERM | SIGINT => control.shutdown(),
+                SIGHUP => {
+                    log::info!("SIGHUP: reloading the config file.");
+                    control.reload();
+                }
+                SIGUSR1 if control.paused => {
+                    log::info!("SIGUSR1: resuming detection.");
+                    control.resume();
+                }
+                SIGUSR1 => {
+                    log::info!("SIGUSR1: pausing detection.");
+                    control.pause();
+                }
+                SIGUSR2 => {
+                    log::info!("SIGUSR2: showing locate effects.");
+                    control.trigger();
+                }
+                // Both SIGUSRs are taken, see the README's Metrics section.
+                // Printed rather than logged, so it shows at any RUST_LOG.
+                SIGQUIT => {
+                    log::info!("SIGQUIT: dumping metrics.");
+                    eprint!("{}", control.metrics.lock().unwrap().to_prometheus());
+                }
+                _ => {}
+            }
+        }
+    });
+
+    Ok(())
+}
@@ -0,0 +1,215 @@
+use std::path::PathBuf;
+
+use shake_cursor_core::backend::MotionEvent;
+use shake_cursor_core::config::{Config, LocateEffect};
+use shake_cursor_core::controller::{Controller, SystemClock};
+use shake_cursor_core::gesture::{Gesture, Generator};
+use shake_cursor_core::trace::TraceWriter;
+
+use crate::effect;
+use crate::script_backend::ScriptBackend;
+
+pub const USAGE: &str = "\
+Usage: shake-cursor simulate [OPTIONS] <GESTURE>...
+
+Synthesizes the mouse motion of a sequence of gestures and runs it
+through the daemon's shake detection, reporting what it made of each
+gesture. With --output, the motion is also written as a trace, with the
+shake gestures labelled, for `analyze` and `--replay`.
+
+Gestures are KIND[:KEY=VALUE,...], each starting where the last ended:
+  swipe:length=600,angle=0,duration=300
+  circle:radius=100,period=500,duration=1000
+  shake:axis=horizontal,frequency=6,amplitude=60,duration=1000
+                     (axis is horizontal, vertical or diagonal)
+  pause:duration=500
+Values shown are the defaults; lengths are in px, times in ms.
+
+Options:
+  --rate <HZ>        Mouse event rate, 125 to 8000 for real mice
+                     (default: 1000)
+  --noise <PX>       Hand tremor, standard deviation in px (default: 0)
+  --drop <FRACTION>  Fraction of events lost, 0 to 1 (default: 0)
+  --seed <N>         Seed for tremor and drops (default: 1)
+  --config <PATH>    Detect with this config file instead of the defaults
+  --set <KEY=VALUE>  Override one config parameter
+  --output <PATH>    Write the motion as a labelled trace";
+
+/// Parsed `simulate` arguments.
+struct Options {
+    gestures: Vec<Gesture>,
+    rate_hz: f64,
+    noise: f64,
+    drop_rate: f64,
+    seed: u64,
+    config: Config,
+    output: Option<PathBuf>,
+}
+
+/// Run `shake-cursor simulate` with the arguments after the subcommand
+/// name. Returns the process exit code.
+pub fn run(args: &[String]) -> i32 {
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", USAGE);
+        return 0;
+    }
+
+    let options = match parse(args) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, USAGE);
+            return 2;
+        }
+    };
+
+    match simulate(options) {
+        Ok(()) => 0,
+        Err(err) => {
+            eprintln!("{}", err);
+            1
+        }
+    }
+}
+
+fn parse(args: &[String]) -> Result<Options, String> {
+    fn value<T: std::str::FromStr>(option: &str, value: Option<&String>) -> Result<T, String> {
+        let value = value.ok_or_else(|| format!("{} requires a value", option))?;
+        value.parse().map_err(|_| format!("Invalid value '{}' for {}", value, option))
+    }
+
+    let mut options = Options {
+        gestures: Vec::new(),
+        rate_hz: 1000.0,
+        noise: 0.0,
+        drop_rate: 0.0,
+        seed: 1,
+        config: Config::default(),
+        output: None,
+    };
+    let mut overrides = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--rate" => {
+                options.rate_hz = value(arg, args.next())?;
+                if !(options.rate_hz > 0.0 && options.rate_hz.is_finite()) {
+                    return Err(format!("Invalid rate {} (expected Hz above 0)", options.rate_hz));
+                }
+            }
+            "--noise" => {
+                options.noise = value(arg, args.next())?;
+                if !(options.noise >= 0.0 && options.noise.is_finite()) {
+                    return Err(format!("Invalid noise {} (expected px, 0 or more)", options.noise));
+                }
+            }
+            "--drop" => {
+                options.drop_rate = value(arg, args.next())?;
+                if !(0.0..=1.0).contains(&options.drop_rate) {
+                    return Err(format!("Invalid drop fraction {} (expected 0 to 1)", options.drop_rate));
+                }
+            }
+            "--seed" => options.seed = value(arg, args.next())?,
+            "--config" => {
+                let path: PathBuf = value(arg, args.next())?;
+                options.config = Config::load(&path)?;
+            }
+            "--set" => {
+                let setting = args.next()
+                    .ok_or_else(|| "--set requires a value".to_string())?;
+                let (key, value) = setting.split_once('=')
+                    .ok_or_else(|| format!("Invalid setting '{}' (expected KEY=VALUE)", setting))?;
+                overrides.push((key.trim().to_string(), value.trim().to_string()));
+            }
+            "--output" => options.output = Some(value(arg, args.next())?),
+            other if other.starts_with('-') => return Err(format!("Unknown argument '{}'", other)),
+            spec => options.gestures.push(Gesture::parse(spec)?),
+        }
+    }
+
+    // Applied after the loop so --set wins over a later --config
+    for (key, value) in &overrides {
+        options.config.set(key, value)?;
+    }
+    if options.gestures.is_empty() {
+        return Err("simulate requires at least one gesture".to_string());
+    }
+    Ok(options)
+}
+
+fn simulate(options: Options) -> Result<(), String> {
+    // Only the cursor size goes through the backend, so only that effect
+    // can run without a display. Adaptive mode would learn from (and
+    // save) synthetic motion.
+    let config = Config { effects: vec![LocateEffect::Enlarge], adaptive_enabled: false, ..options.config };
+
+    let mut generator = Generator::new(options.seed);
+    generator.rate_hz = options.rate_hz;
+    generator.noise = options.noise;
+    generator.drop_rate = options.drop_rate;
+
+    // Generate everything up front: the trace's labels go before the
+    // motion the controller writes
+    let mut gestures = Vec::new();
+    for gesture in &options.gestures {
+        let start = generator.time_ms();
+        let events = generator.generate(gesture);
+        gestures.push((gesture, start, generator.time_ms(), events));
+    }
+
+    let events: Vec<MotionEvent> = gestures.iter().flat_map(|(_, _, _, events)| events.clone()).collect();
+    let mut controller = Controller::new(
+        Box::new(ScriptBackend::new(events)),
+        Box::new(SystemClock::start()),
+        config.clone(),
+        effect::build_chain,
+    );
+    if let Some(path) = &options.output {
+        let mut trace = TraceWriter::create(path, &config)?;
+        for (gesture, start, end, _) in &gestures {
+            if matches!(gesture, Gesture::Shake { .. }) {
+                trace.write_label(*start, *end);
+            }
+        }
+        controller.record_to(trace);
+    }
+
+    println!(
+        "{} gestures at {} Hz, noise {} px, {:.0}% dropped (seed {})",
+        gestures.len(), options.rate_hz, options.noise, options.drop_rate * 100.0, options.seed
+    );
+    println!(
+        "time_window_ms = {}, min_reversals = {}, min_velocity = {}, cooldown_ms = {}",
+        config.time_window_ms, config.min_reversals, config.min_velocity, config.cooldown_ms
+    );
+    println!();
+
+    let mut detected = 0;
+    for (index, (gesture, start, end, events)) in gestures.iter().enumerate() {
+        let mut peak_score: f64 = 0.0;
+        let mut onsets = Vec::new();
+        for motion in events {
+            // The backend yields these same events, in order
+            let Some(event) = controller.next_event() else { break };
+            if controller.handle(event, false).is_some() {
+                onsets.push(motion.timestamp);
+            }
+            peak_score = peak_score.max(controller.score());
+        }
+        detected += onsets.len();
+
+        println!(
+            "{:>3}. t={}..{}  {}: {:.0} px/s, {} events, peak score {:.2}",
+            index + 1, start, end, gesture, gesture.speed(), events.len(), peak_score
+        );
+        for timestamp in onsets {
+            println!("       shake detected at t={}", timestamp);
+        }
+    }
+
+    println!();
+    let shakes = gestures.iter().filter(|(gesture, ..)| matches!(gesture, Gesture::Shake { .. })).count();
+    println!("{} shakes detected, {} shake gestures", detected, shakes);
+    controller.shutdown();
+    Ok(())
+}
@@ -0,0 +1,584 @@
+use std::env;
+use std::ffi::c_ulong;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::mem;
+use std::os::fd::AsRawFd;
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+
+use serde_json::Value;
+
+use shake_cursor_core::backend::{BackendProbe, DisplayBackend, InputEvent, MotionEvent, Waker};
+
+/// Linux input event types and codes from <linux/input-event-codes.h>.
+const EV_SYN: u16 = 0x00;
+const EV_REL: u16 = 0x02;
+const SYN_REPORT: u16 = 0;
+const REL_X: u16 = 0x00;
+const REL_Y: u16 = 0x01;
+
+/// EVIOCSCLOCKID from <linux/input.h>, _IOW('E', 0xa0, int): selects the
+/// clock a device's event timestamps are taken from.
+const EVIOCSCLOCKID: c_ulong = 0x4004_45a0;
+
+/// Size of `struct input_event` from <linux/input.h>: the time as two
+/// `unsigned long`s (seconds, microseconds), then type (u16), code (u16)
+/// and value (i32). 24 bytes on 64-bit Linux, 16 on 32-bit.
+const TIME_FIELD_SIZE: usize = mem::size_of::<c_ulong>();
+const INPUT_EVENT_SIZE: usize = 2 * TIME_FIELD_SIZE + 8;
+
+/// Sway IPC header: "i3-ipc" magic, payload length (u32), message type (u32).
+const SWAY_IPC_MAGIC: &[u8] = b"i3-ipc";
+const SWAY_IPC_HEADER_SIZE: usize = SWAY_IPC_MAGIC.len() + 8;
+
+/// Upper bound of the virtual pointer plane. evdev only reports relative
+/// deltas, so we integrate them into a position clamped to this range,
+/// the same way the compositor clamps the real pointer to the screen edges.
+/// The position is good for detecting shakes, but it isn't where the
+/// pointer is on screen, so this backend doesn't support overlays.
+const VIRTUAL_PLANE_SIZE: i32 = 8192;
+
+/// The wlroots compositor we are running under. Each one exposes a
+/// different IPC mechanism for changing the seat's cursor theme size.
+enum Compositor {
+    /// Sway: i3-compatible IPC over the Unix socket in $SWAYSOCK.
+    Sway { socket: PathBuf },
+
+    /// Hyprland: `hyprctl setcursor <theme> <size>`.
+    Hyprland,
+
+    /// Any other wlroots compositor (e.g. labwc). Motion detection works,
+    /// but there is no IPC to resize the cursor at runtime.
+    Unsupported { name: String },
+}
+
+/// One `struct input_event`, as read from an evdev device.
+#[derive(Debug, PartialEq)]
+struct RawEvent {
+    /// Event time in milliseconds, truncated to u32 like X11 timestamps.
+    /// CLOCK_MONOTONIC once connect() has selected it with EVIOCSCLOCKID;
+    /// the kernel's default is wall-clock time, which jumps when the clock
+    /// is set.
+    timestamp: u32,
+    type_: u16,
+    code: u16,
+    value: i32,
+}
+
+impl RawEvent {
+    fn decode(buf: &[u8; INPUT_EVENT_SIZE]) -> Self {
+        let field = |index: usize| {
+            let start = index * TIME_FIELD_SIZE;
+            c_ulong::from_ne_bytes(buf[start..start + TIME_FIELD_SIZE].try_into().unwrap())
+        };
+        let (seconds, micros) = (field(0), field(1));
+        let rest = &buf[2 * TIME_FIELD_SIZE..];
+        Self {
+            // Wrapping arithmetic keeps the low 32 bits right either way
+            timestamp: seconds.wrapping_mul(1000).wrapping_add(micros / 1000) as u32,
+            type_: u16::from_ne_bytes(rest[0..2].try_into().unwrap()),
+            code: u16::from_ne_bytes(rest[2..4].try_into().unwrap()),
+            value: i32::from_ne_bytes(rest[4..8].try_into().unwrap()),
+        }
+    }
+}
+
+/// Message from a device reader thread, or from the waker.
+#[derive(Debug, PartialEq)]
+enum DeviceMessage {
+    /// One SYN_REPORT worth of relative motion from a single input device.
+    Motion { dx: i32, dy: i32, timestamp: u32, device: u16 },
+
+    /// The reader's device disappeared and the thread exited.
+    Closed,
+
+    /// The waker was called.
+    Wake,
+}
+
+/// Wayland implementation of the display backend for wlroots compositors.
+///
+/// Wayland does not expose global pointer position to clients, so motion is
+/// read directly from the kernel's evdev devices (/dev/input/event*), which
+/// requires the user to be in the `input` group. Cursor enlargement goes
+/// through compositor IPC, changing the seat's Xcursor theme size.
+pub struct WaylandBackend {
+    /// Which compositor IPC to use. None until connect() is called.
+    compositor: Option<Compositor>,
+
+    /// Receives relative motion from one reader thread per pointer device.
+    /// Dropping it makes the reader threads exit on their next send.
+    motion_rx: Option<Receiver<DeviceMessage>>,
+
+    /// Sender for the waker. The waker keeps the channel open, so
+    /// unplugged devices are counted through DeviceMessage::Closed.
+    wake_tx: Option<Sender<DeviceMessage>>,
+
+    /// Reader threads still running.
+    open_devices: usize,
+
+    /// Integrated pointer position on the virtual plane.
+    x: i32,
+    y: i32,
+
+    /// The Xcursor theme name, read from XCURSOR_THEME on startup.
+    /// The compositor commands require a theme alongside the size.
+    cursor_theme: String,
+
+    /// The original cursor size in pixels, read from XCURSOR_SIZE on startup.
+    original_cursor_size: u32,
+}
+
+impl WaylandBackend {
+    /// Create an unconnected backend. Call connect() to detect the
+    /// compositor and open the input devices.
+    pub fn new() -> Self {
+        Self {
+            compositor: None,
+            motion_rx: None,
+            wake_tx: None,
+            open_devices: 0,
+            x: VIRTUAL_PLANE_SIZE / 2,
+            y: VIRTUAL_PLANE_SIZE / 2,
+            cursor_theme: String::from("default"),
+            original_cursor_size: 24,
+        }
+    }
+
+    /// Check for a Wayland session, a compositor with cursor IPC, and at
+    /// least one pointer device this user can read.
+    pub fn probe() -> BackendProbe {
+        let display = env::var("WAYLAND_DISPLAY").ok();
+        if display.is_none() && env::var_os("SWAYSOCK").is_none() {
+            return BackendProbe {
+                usable: false,
+                detail: String::from("WAYLAND_DISPLAY unset"),
+                warning: None,
+            };
+        }
+
+        let (compositor, supported) = match Self::detect_compositor() {
+            Compositor::Sway { .. } => (String::from("sway"), true),
+            Compositor::Hyprland => (String::from("hyprland"), true),
+            Compositor::Unsupported { name } => (name, false),
+        };
+
+        let devices = Self::pointer_devices();
+        let readable = devices.iter().filter(|path| File::open(path).is_ok()).count();
+
+        let detail = format!(
+            "WAYLAND_DISPLAY={}, compositor {}{}, {}/{} pointer device(s) readable",
+            display.as_deref().unwrap_or("unset"),
+            compositor,
+            if supported { "" } else { " (no cursor IPC)" },
+            readable,
+            devices.len(),
+        );
+
+        BackendProbe {
+            usable: supported && readable > 0,
+            detail,
+            warning: None,
+        }
+    }
+
+    /// Identify the running compositor from the environment variables
+    /// each one exports to its clients.
+    fn detect_compositor() -> Compositor {
+        if let Some(socket) = env::var_os("SWAYSOCK") {
+            return Compositor::Sway { socket: PathBuf::from(socket) };
+        }
+        if env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_some() {
+            return Compositor::Hyprland;
+        }
+        let name = env::var("XDG_CURRENT_DESKTOP").unwrap_or_else(|_| String::from("unknown"));
+        Compositor::Unsupported { name }
+    }
+
+    /// Find every evdev device that reports relative X and Y motion
+    /// (mice, trackballs, trackpoints). The capability bitmask is read
+    /// from sysfs so no ioctl is needed.
+    fn pointer_devices() -> Vec<PathBuf> {
+        let Ok(entries) = fs::read_dir("/sys/class/input") else {
+            return Vec::new();
+        };
+
+        let mut devices: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .filter(|name| name.starts_with("event"))
+            .filter(|name| {
+                let caps = Path::new("/sys/class/input")
+                    .join(name)
+                    .join("device/capabilities/rel");
+                fs::read_to_string(caps)
+                    .ok()
+                    .and_then(|hex| u64::from_str_radix(hex.split_whitespace().last()?, 16).ok())
+                    .is_some_and(|bits| bits & (1 << REL_X) != 0 && bits & (1 << REL_Y) != 0)
+            })
+            .map(|name| Path::new("/dev/input").join(name))
+            .collect();
+
+        devices.sort();
+        devices
+    }
+
+    /// Read `struct input_event`s from one device and forward accumulated
+    /// relative motion on every SYN_REPORT. Runs on its own thread and
+    /// exits when the device disappears or the receiver is dropped.
+    fn read_device(mut input: impl Read, device: u16, tx: Sender<DeviceMessage>) {
+        let mut buf = [0u8; INPUT_EVENT_SIZE];
+        let mut dx: i32 = 0;
+        let mut dy: i32 = 0;
+
+        while input.read_exact(&mut buf).is_ok() {
+            let event = RawEvent::decode(&buf);
+            match (event.type_, event.code) {
+                (EV_REL, REL_X) => dx += event.value,
+                (EV_REL, REL_Y) => dy += event.value,
+                (EV_SYN, SYN_REPORT) if dx != 0 || dy != 0 => {
+                    let message = DeviceMessage::Motion { dx, dy, timestamp: event.timestamp, device };
+                    if tx.send(message).is_err() {
+                        return;
+                    }
+                    dx = 0;
+                    dy = 0;
+                }
+                _ => {}
+            }
+        }
+
+        let _ = tx.send(DeviceMessage::Closed);
+    }
+
+    /// Send a RUN_COMMAND message over Sway's i3-compatible IPC socket.
+    ///
+    /// Wire format: "i3-ipc" magic, u32 payload length, u32 message type,
+    /// then the payload. The reply uses the same header followed by JSON
+    /// like `[{"success":true}]`, one object per command.
+    fn sway_command(socket: &Path, command: &str) -> Result<(), String> {
+        const RUN_COMMAND: u32 = 0;
+
+        let mut stream = UnixStream::connect(socket)
+            .map_err(|err| format!("Failed to connect to Sway IPC: {}", err))?;
+        stream.write_all(&sway_message(RUN_COMMAND, command.as_bytes()))
+            .map_err(|err| format!("Failed to send Sway IPC command: {}", err))?;
+
+        let mut header = [0u8; SWAY_IPC_HEADER_SIZE];
+        stream.read_exact(&mut header)
+            .map_err(|err| format!("Failed to read Sway IPC reply: {}", err))?;
+        if !header.starts_with(SWAY_IPC_MAGIC) {
+            return Err("Invalid Sway IPC reply header".to_string());
+        }
+        let length = u32::from_ne_bytes(header[6..10].try_into().unwrap()) as usize;
+        let mut payload = vec![0u8; length];
+        stream.read_exact(&mut payload)
+            .map_err(|err| format!("Failed to read Sway IPC reply: {}", err))?;
+
+        command_result(command, &payload)
+    }
+}
+
+/// A Sway IPC message: header, then the payload.
+fn sway_message(message_type: u32, payload: &[u8]) -> Vec<u8> {
+    let mut message = Vec::with_capacity(SWAY_IPC_HEADER_SIZE + payload.len());
+    message.extend_from_slice(SWAY_IPC_MAGIC);
+    message.extend_from_slice(&(payload.len() as u32).to_ne_bytes());
+    message.extend_from_slice(&message_type.to_ne_bytes());
+    message.extend_from_slice(payload);
+    message
+}
+
+/// Check the reply to RUN_COMMAND: every command in it has to report
+/// success, otherwise its error is returned.
+fn command_result(command: &str, payload: &[u8]) -> Result<(), String> {
+    let reply: Value = serde_json::from_slice(payload)
+        .map_err(|err| format!("Invalid Sway IPC reply to '{}': {}", command, err))?;
+    let results = reply.as_array().filter(|results| !results.is_empty())
+        .ok_or_else(|| format!("Unexpected Sway IPC reply to '{}': {}", command, reply))?;
+    for result in results {
+        if result.get("success").and_then(Value::as_bool) != Some(true) {
+            let error = result.get("error").and_then(Value::as_str).unwrap_or("no reason given");
+            return Err(format!("Sway rejected '{}': {}", command, error));
+        }
+    }
+    Ok(())
+}
+
+impl DisplayBackend for WaylandBackend {
+    /// Detect the compositor and spawn one reader thread per pointer device.
+    /// Fails if no pointer device can be opened (usually missing `input` group).
+    fn connect(&mut self) -> Result<(), String> {
+        let compositor = Self::detect_compositor();
+        if let Compositor::Unsupported { name } = &compositor {
+            log::warn!("Compositor '{}' has no cursor IPC; the cursor cannot be resized.", name);
+        }
+
+        if let Ok(theme) = env::var("XCURSOR_THEME")
+            && !theme.is_empty()
+        {
+            self.cursor_theme = theme;
+        }
+        if let Some(size) = env::var("XCURSOR_SIZE").ok().and_then(|size| size.parse().ok()) {
+            self.original_cursor_size = size;
+        }
+        log::info!("Cursor theme: {}, original size: {}px", self.cursor_theme, self.original_cursor_size);
+
+        let (tx, rx) = mpsc::channel();
+        let mut opened = 0;
+        for path in Self::pointer_devices() {
+            match File::open(&path) {
+                Ok(file) => {
+                    log::info!("Reading pointer motion from {}", path.display());
+                    if let Err(err) = use_monotonic_clock(&file) {
+                        log::warn!("{}: {}; timestamps will follow the wall clock", path.display(), err);
+                    }
+                    let tx = tx.clone();
+                    let device = opened as u16;
+                    thread::spawn(move || Self::read_device(file, device, tx));
+                    opened += 1;
+                }
+                Err(err) => log::debug!("Skipping {}: {}", path.display(), err),
+            }
+        }
+
+        if opened == 0 {
+            return Err(String::from(
+                "No readable pointer devices in /dev/input (is the user in the 'input' group?)",
+            ));
+        }
+
+        self.compositor = Some(compositor);
+        self.motion_rx = Some(rx);
+        self.wake_tx = Some(tx);
+        self.open_devices = opened;
+        Ok(())
+    }
+
+    /// Block until any pointer device reports motion, then integrate the
+    /// delta into the virtual position. Returns None once every device
+    /// reader has exited (all devices unplugged).
+    fn next_event(&mut self) -> Option<InputEvent> {
+        loop {
+            match self.motion_rx.as_ref()?.recv().ok()? {
+                DeviceMessage::Motion { dx, dy, timestamp, device } => {
+                    self.x = (self.x + dx).clamp(0, VIRTUAL_PLANE_SIZE - 1);
+                    self.y = (self.y + dy).clamp(0, VIRTUAL_PLANE_SIZE - 1);
+
+                    return Some(InputEvent::Motion(MotionEvent {
+                        x: self.x as i16,
+                        y: self.y as i16,
+                        timestamp,
+                        device,
+                    }));
+                }
+                DeviceMessage::Closed => {
+                    self.open_devices = self.open_devices.saturating_sub(1);
+                    if self.open_devices == 0 {
+                        return None;
+                    }
+                }
+                DeviceMessage::Wake => return Some(InputEvent::Interrupted),
+            }
+        }
+    }
+
+    fn waker(&self) -> Waker {
+        let tx = self.wake_tx.clone();
+        Arc::new(move || {
+            if let Some(tx) = &tx {
+                let _ = tx.send(DeviceMessage::Wake);
+            }
+        })
+    }
+
+    /// Change the seat's cursor theme size through compositor IPC.
+    fn set_cursor_size(&mut self, size: u32) -> Result<(), String> {
+        let compositor = self.compositor.as_ref()
+            .ok_or_else(|| "Not connected to Wayland compositor".to_string())?;
+
+        log::info!("Setting cursor size to {}px (theme: {})", size, self.cursor_theme);
+
+        match compositor {
+            Compositor::Sway { socket } => {
+                let command = format!("seat seat0 xcursor_theme {} {}", self.cursor_theme, size);
+                Self::sway_command(socket, &command)
+            }
+            Compositor::Hyprland => {
+                let status = Command::new("hyprctl")
+                    .args(["setcursor", &self.cursor_theme, &size.to_string()])
+                    .status()
+                    .map_err(|err| format!("Failed to run hyprctl: {}", err))?;
+                if status.success() {
+                    Ok(())
+                } else {
+                    Err(format!("hyprctl setcursor exited with {}", status))
+                }
+            }
+            Compositor::Unsupported { name } => {
+                Err(format!("Cursor resizing is not supported on '{}'", name))
+            }
+        }
+    }
+
+    fn original_cursor_size(&self) -> u32 {
+        self.original_cursor_size
+    }
+
+    /// Positions are on the virtual plane, not the screen.
+    fn screen_coordinates(&self) -> bool {
+        false
+    }
+
+    /// Drop the motion receiver so the reader threads stop.
+    fn disconnect(&mut self) {
+        self.motion_rx = None;
+        self.wake_tx = None;
+        self.compositor = None;
+    }
+}
+
+/// Have the device timestamp its events with CLOCK_MONOTONIC, which
+/// never steps backwards, instead of CLOCK_REALTIME.
+fn use_monotonic_clock(file: &File) -> Result<(), String> {
+    let clock: libc::c_int = libc::CLOCK_MONOTONIC;
+    // SAFETY: the descriptor is valid for as long as `file` is, and the
+    // kernel only reads the int the pointer refers to
+    let result = unsafe { libc::ioctl(file.as_raw_fd(), EVIOCSCLOCKID as _, &clock) };
+    if result == -1 {
+        return Err(format!("Failed to select the monotonic clock: {}", std::io::Error::last_os_error()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::TempDir;
+    use std::io::Cursor;
+
+    /// Encode one `struct input_event` the way the kernel writes it.
+    fn raw(seconds: u64, micros: u64, type_: u16, code: u16, value: i32) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(INPUT_EVENT_SIZE);
+        buf.extend_from_slice(&(seconds as c_ulong).to_ne_bytes());
+        buf.extend_from_slice(&(micros as c_ulong).to_ne_bytes());
+        buf.extend_from_slice(&type_.to_ne_bytes());
+        buf.extend_from_slice(&code.to_ne_bytes());
+        buf.extend_from_slice(&value.to_ne_bytes());
+        buf
+    }
+
+    #[test]
+    fn input_event_size_matches_the_platform() {
+        #[cfg(target_pointer_width = "64")]
+        assert_eq!(INPUT_EVENT_SIZE, 24);
+        #[cfg(target_pointer_width = "32")]
+        assert_eq!(INPUT_EVENT_SIZE, 16);
+        assert_eq!(raw(0, 0, 0, 0, 0).len(), INPUT_EVENT_SIZE);
+    }
+
+    #[test]
+    fn decode_reads_time_type_code_and_value() {
+        let buf = raw(5120, 331_999, EV_REL, REL_Y, -7);
+        let event = RawEvent::decode(buf.as_slice().try_into().unwrap());
+        assert_eq!(event, RawEvent { timestamp: 5_120_331, type_: EV_REL, code: REL_Y, value: -7 });
+    }
+
+    #[test]
+    fn read_device_sums_motion_per_report() {
+        let events = [
+            raw(1, 0, EV_REL, REL_X, 5),
+            raw(1, 0, EV_REL, REL_X, 2),
+            raw(1, 0, EV_REL, REL_Y, -3),
+            raw(1, 0, EV_SYN, SYN_REPORT, 0),
+            // A report without relative motion, e.g. a button press
+            raw(1, 8000, EV_SYN, SYN_REPORT, 0),
+            raw(1, 16000, EV_REL, REL_Y, 4),
+            raw(1, 16000, EV_SYN, SYN_REPORT, 0),
+        ]
+        .concat();
+        let (tx, rx) = mpsc::channel();
+        // A trailing partial event is where the device went away
+        WaylandBackend::read_device(Cursor::new([events, vec![0; 3]].concat()), 2, tx);
+
+        let messages: Vec<DeviceMessage> = rx.try_iter().collect();
+        assert_eq!(
+            messages,
+            [
+                DeviceMessage::Motion { dx: 7, dy: -3, timestamp: 1000, device: 2 },
+                DeviceMessage::Motion { dx: 0, dy: 4, timestamp: 1016, device: 2 },
+                DeviceMessage::Closed,
+            ]
+        );
+    }
+
+    #[test]
+    fn sway_message_has_magic_length_and_type() {
+        let message = sway_message(0, b"seat seat0 xcursor_theme default 96");
+        assert_eq!(&message[..6], b"i3-ipc");
+        assert_eq!(u32::from_ne_bytes(message[6..10].try_into().unwrap()), 35);
+        assert_eq!(u32::from_ne_bytes(message[10..14].try_into().unwrap()), 0);
+        assert_eq!(&message[14..], b"seat seat0 xcursor_theme default 96");
+    }
+
+    #[test]
+    fn command_result_needs_every_command_to_succeed() {
+        assert!(command_result("c", br#"[{"success":true}]"#).is_ok());
+        assert!(command_result("c", br#"[ { "success" : true }, {"success": true} ]"#).is_ok());
+
+        let err = command_result("c", br#"[{"success":true},{"success":false,"error":"Unknown theme"}]"#).unwrap_err();
+        assert!(err.contains("Unknown theme"), "{}", err);
+        // Not fooled by the text appearing elsewhere
+        assert!(command_result("c", br#"[{"success":false,"error":"\"success\":true"}]"#).is_err());
+        assert!(command_result("c", b"[]").is_err());
+        assert!(command_result("c", br#"{"success":true}"#).is_err());
+        assert!(command_result("c", b"[{\"success\":tr").is_err());
+    }
+
+    #[test]
+    fn sway_command_round_trip() {
+        let dir = TempDir::new("sway");
+        let socket = dir.0.join("sway-ipc.sock");
+        let listener = std::os::unix::net::UnixListener::bind(&socket).unwrap();
+
+        // A fake Sway: accept two commands, rejecting the second
+        let server = thread::spawn(move || {
+            let mut commands = Vec::new();
+            for reply in [&br#"[{"success":true}]"#[..], br#"[{"success":false,"error":"Unknown theme"}]"#] {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut header = [0u8; SWAY_IPC_HEADER_SIZE];
+                stream.read_exact(&mut header).unwrap();
+                assert_eq!(&header[..6], SWAY_IPC_MAGIC);
+                assert_eq!(u32::from_ne_bytes(header[10..14].try_into().unwrap()), 0);
+                let mut payload = vec![0u8; u32::from_ne_bytes(header[6..10].try_into().unwrap()) as usize];
+                stream.read_exact(&mut payload).unwrap();
+                commands.push(String::from_utf8(payload).unwrap());
+                stream.write_all(&sway_message(0, reply)).unwrap();
+            }
+            commands
+        });
+
+        let result = WaylandBackend::sway_command(&socket, "seat seat0 xcursor_theme default 96");
+        let rejected = WaylandBackend::sway_command(&socket, "seat seat0 xcursor_theme nope 96");
+        let commands = server.join().unwrap();
+
+        assert!(result.is_ok(), "{:?}", result);
+        assert!(rejected.unwrap_err().contains("Unknown theme"));
+        assert_eq!(commands, ["seat seat0 xcursor_theme default 96", "seat seat0 xcursor_theme nope 96"]);
+    }
+
+    #[test]
+    fn clock_is_selected_with_eviocsclockid() {
+        // _IOW: write direction, argument size, type 'E', number 0xa0
+        let request = 1 << 30 | (mem::size_of::<libc::c_int>() as c_ulong) << 16 | (b'E' as c_ulong) << 8 | 0xa0;
+        assert_eq!(EVIOCSCLOCKID, request);
+        // Only evdev devices know the request
+        let err = use_monotonic_clock(&File::open("/dev/null").unwrap()).unwrap_err();
+        assert!(err.starts_with("Failed to select the monotonic clock"), "{}", err);
+    }
+}
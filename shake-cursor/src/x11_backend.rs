@@ -1,7 +1,13 @@
+use std::env;
 use std::ffi::OsString;
+use std::io::{self, Read, Write};
+use std::os::fd::{AsRawFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::sync::Arc;
 
-use x11rb::connection::Connection;
+use x11rb::connection::{Connection, RequestConnection};
 use x11rb::cursor::Handle as CursorHandle;
+use x11rb::protocol::render;
 use x11rb::protocol::xfixes;
 use x11rb::protocol::xinput;
 use x11rb::protocol::xproto::*;
@@ -9,7 +15,23 @@ use x11rb::protocol::Event;
 use x11rb::resource_manager::Database;
 use x11rb::rust_connection::RustConnection;
 
-use crate::backend::{DisplayBackend, MotionEvent};
+use shake_cursor_core::backend::{BackendProbe, DisplayBackend, InputEvent, MotionEvent, Waker};
+use shake_cursor_core::config::{CustomCursor, Hotkey, Modifier};
+
+use crate::cursor_image::{self, CursorImage};
+
+/// Cursor shapes replaced when the size changes. Each group is loaded
+/// from the theme under its first name and applied to every name in it,
+/// since toolkits use different names for the same shape:
+///   "left_ptr" — X11 core name, used by GTK and many legacy apps
+///   "default"  — freedesktop.org standard, used by Electron (VSCode), Qt
+/// The busy cursors are usually animated, and are included so they grow
+/// with the arrow instead of snapping back to normal size.
+const CURSOR_GROUPS: &[&[&str]] = &[
+    &["left_ptr", "default"],
+    &["left_ptr_watch", "progress"],
+    &["watch", "wait"],
+];
 
 /// X11 implementation of the display backend.
 ///
@@ -39,6 +61,41 @@ pub struct X11Backend {
     /// modified resource database so CursorHandle loads sized Xcursor
     /// files instead of falling back to fixed-size core X11 cursors.
     cursor_theme: String,
+
+    /// Image configured for the enlarged state, if any. Loaded on connect().
+    enlarged_cursor: Option<CustomCursor>,
+
+    /// All images (every nominal size and frame) from enlarged_cursor.
+    /// Empty when no custom image is configured or it failed to load,
+    /// in which case the theme's cursor is enlarged instead.
+    enlarged_images: Vec<CursorImage>,
+
+    /// All images (every nominal size and animation frame) of each entry
+    /// in CURSOR_GROUPS, parsed directly from the theme's Xcursor files on
+    /// connect(). Loading them ourselves keeps every frame and delay of
+    /// animated cursors, and lets us synthesize sizes the theme lacks.
+    /// An entry is empty if its file could not be found or parsed.
+    theme_cursors: Vec<Vec<CursorImage>>,
+
+    /// The Render picture format for 32-bit ARGB, used to upload
+    /// custom cursor images. Found on connect().
+    argb_format: Option<render::Pictformat>,
+
+    /// Locate-pointer hotkey to listen for, if enabled. Set up on connect().
+    hotkey: Option<Hotkey>,
+
+    /// Keycodes of the hotkey's key (for combos, which are grabbed) or of
+    /// its modifier (for taps, which are watched through raw key events).
+    hotkey_keycodes: Vec<Keycode>,
+
+    /// True between a press of the tap modifier and its release, as long
+    /// as no other key or button was pressed in between.
+    tap_armed: bool,
+
+    /// Self-pipe for the waker: next_event() sleeps until either the X
+    /// connection or the read end is readable, and the waker writes a
+    /// byte to the write end. Created on connect().
+    wake_pipe: Option<(UnixStream, Arc<UnixStream>)>,
 }
 
 impl X11Backend {
@@ -51,6 +108,317 @@ impl X11Backend {
             root: 0,
             original_cursor_size: 24,
             cursor_theme: String::from("default"),
+            enlarged_cursor: None,
+            enlarged_images: Vec::new(),
+            theme_cursors: Vec::new(),
+            argb_format: None,
+            hotkey: None,
+            hotkey_keycodes: Vec::new(),
+            tap_armed: false,
+            wake_pipe: None,
+        }
+    }
+
+    /// Show this image instead of the theme's arrow when enlarging.
+    pub fn set_enlarged_cursor(&mut self, cursor: CustomCursor) {
+        self.enlarged_cursor = Some(cursor);
+    }
+
+    /// Listen for this key combination and report it as InputEvent::Hotkey.
+    pub fn set_hotkey(&mut self, hotkey: Hotkey) {
+        self.hotkey = Some(hotkey);
+    }
+
+    /// Check whether an X server is reachable via DISPLAY, and whether
+    /// it is XWayland. Under XWayland the XFixes cursor replacement only
+    /// reaches X11 clients, so native Wayland windows keep their cursor.
+    pub fn probe() -> BackendProbe {
+        let Ok(display) = env::var("DISPLAY") else {
+            return BackendProbe {
+                usable: false,
+                detail: String::from("DISPLAY unset"),
+                warning: None,
+            };
+        };
+
+        let conn = match RustConnection::connect(None) {
+            Ok((conn, _)) => conn,
+            Err(err) => {
+                return BackendProbe {
+                    usable: false,
+                    detail: format!("DISPLAY={}, connection failed: {}", display, err),
+                    warning: None,
+                };
+            }
+        };
+
+        // XWayland advertises its own protocol extension; Xorg never does.
+        let xwayland = matches!(conn.extension_information("XWAYLAND"), Ok(Some(_)));
+        if xwayland {
+            BackendProbe {
+                usable: true,
+                detail: format!("DISPLAY={}, XWayland", display),
+                warning: Some(String::from(
+                    "X server is XWayland: cursor changes will only affect X11 windows, \
+                     not native Wayland windows",
+                )),
+            }
+        } else {
+            BackendProbe {
+                usable: true,
+                detail: format!("DISPLAY={}, Xorg", display),
+                warning: None,
+            }
+        }
+    }
+
+    /// Start listening for the hotkey.
+    ///
+    /// A combination with a key is grabbed on the root window with XGrabKey,
+    /// so applications don't see it, once for each state of Caps Lock and
+    /// Num Lock. A modifier tap can't be grabbed without breaking every
+    /// other shortcut that uses the modifier, so it is watched through
+    /// XInput2 raw key events instead, which report every key on the
+    /// keyboard whichever window has focus.
+    fn listen_for_hotkey(&mut self, conn: &RustConnection) -> Result<(), String> {
+        let Some(hotkey) = &self.hotkey else {
+            return Ok(());
+        };
+
+        let setup = conn.setup();
+        let (min_keycode, max_keycode) = (setup.min_keycode, setup.max_keycode);
+        let mapping = get_keyboard_mapping(conn, min_keycode, max_keycode - min_keycode + 1)
+            .map_err(|err| format!("Failed to query keyboard mapping: {}", err))?
+            .reply()
+            .map_err(|err| format!("Failed to read keyboard mapping: {}", err))?;
+
+        let keysyms = match &hotkey.key {
+            Some(key) => vec![keysym_from_name(key).ok_or_else(|| format!("Unknown key '{}'", key))?],
+            None => modifier_keysyms(hotkey.modifiers[0]).to_vec(),
+        };
+
+        // Every keycode that produces one of the keysyms, in any column
+        let per_keycode = mapping.keysyms_per_keycode.max(1) as usize;
+        let keycodes: Vec<Keycode> = mapping
+            .keysyms
+            .chunks(per_keycode)
+            .enumerate()
+            .filter(|(_, syms)| syms.iter().any(|sym| keysyms.contains(sym)))
+            .map(|(index, _)| min_keycode + index as u8)
+            .collect();
+        if keycodes.is_empty() {
+            return Err(format!("No key on the keyboard produces '{}'", hotkey.name()));
+        }
+
+        if hotkey.is_tap() {
+            let event_mask = xinput::EventMask {
+                deviceid: xinput::Device::ALL_MASTER.into(),
+                mask: vec![
+                    xinput::XIEventMask::RAW_KEY_PRESS
+                        | xinput::XIEventMask::RAW_KEY_RELEASE
+                        | xinput::XIEventMask::RAW_BUTTON_PRESS,
+                ],
+            };
+            xinput::xi_select_events(conn, self.root, &[event_mask])
+                .map_err(|err| format!("Failed to select raw key events: {}", err))?
+                .check()
+                .map_err(|err| format!("X server rejected raw key events: {}", err))?;
+        } else {
+            let modifiers = hotkey
+                .modifiers
+                .iter()
+                .fold(ModMask::from(0u16), |mask, &modifier| mask | modifier_mask(modifier));
+            let locks = [ModMask::from(0u16), ModMask::LOCK, ModMask::M2, ModMask::LOCK | ModMask::M2];
+            for &keycode in &keycodes {
+                for lock in locks {
+                    grab_key(conn, false, self.root, modifiers | lock, keycode, GrabMode::ASYNC, GrabMode::ASYNC)
+                        .map_err(|err| format!("Failed to grab {}: {}", hotkey.name(), err))?
+                        .check()
+                        .map_err(|_| format!("{} is already grabbed by another application", hotkey.name()))?;
+                }
+            }
+        }
+
+        log::info!("Locate hotkey: {}", hotkey.name());
+        self.hotkey_keycodes = keycodes;
+        Ok(())
+    }
+
+    /// Pointer position, for hotkey events that don't carry one.
+    fn pointer_position(&self, conn: &RustConnection) -> (i16, i16) {
+        query_pointer(conn, self.root)
+            .ok()
+            .and_then(|cookie| cookie.reply().ok())
+            .map_or((0, 0), |reply| (reply.root_x, reply.root_y))
+    }
+
+    /// Load `left_ptr` through x11rb's CursorHandle at the given size.
+    /// Fallback for when the theme's Xcursor file can't be parsed directly,
+    /// e.g. core-font cursors. Only the arrow is loaded this way.
+    fn load_theme_cursor(&self, conn: &RustConnection, size: u32) -> Result<Cursor, String> {
+        // Query the real resource database to get the cursor theme name,
+        // then build a modified version with our desired cursor size.
+        // CursorHandle reads size from the resource database, not from
+        // the XCURSOR_SIZE env var, so we must override it here.
+        let rm_reply = conn.get_property(
+            false,
+            self.root,
+            AtomEnum::RESOURCE_MANAGER,
+            AtomEnum::STRING,
+            0,
+            1024 * 1024,
+        )
+        .map_err(|err| format!("Failed to query resource manager: {}", err))?
+        .reply()
+        .map_err(|err| format!("Failed to read resource manager reply: {}", err))?;
+
+        // Take the existing resource string, remove Xcursor settings we need
+        // to override, then inject our desired values.
+        // - Xcursor.size: the cursor size in pixels
+        // - Xcursor.theme: force a named theme so CursorHandle loads Xcursor
+        //   files instead of falling back to core X11 bitmap cursors
+        // - Xcursor.theme_core: remove this flag because when set to 1 it
+        //   forces CursorHandle to use fixed-size core cursors, ignoring
+        //   the Xcursor size entirely
+        let original = String::from_utf8_lossy(&rm_reply.value);
+        let modified: String = original
+            .lines()
+            .filter(|line| {
+                !line.starts_with("Xcursor.size")
+                    && !line.starts_with("Xcursor.theme_core")
+                    && !line.starts_with("Xcursor.theme")
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let modified = format!(
+            "{}\nXcursor.size: {}\nXcursor.theme: {}\n",
+            modified, size, self.cursor_theme
+        );
+
+        // Build a new resource database with our modified size
+        let modified_reply = GetPropertyReply {
+            format: 8,
+            sequence: 0,
+            length: 0,
+            type_: AtomEnum::STRING.into(),
+            bytes_after: 0,
+            value_len: modified.len() as u32,
+            value: modified.into_bytes(),
+        };
+        let db = Database::new_from_default(&modified_reply, OsString::new());
+
+        log::info!("Loading cursor at size {}px (theme: {})", size, self.cursor_theme);
+
+        // CursorHandle::new returns a Cookie that must be resolved with .reply()
+        let cursor_handle = CursorHandle::new(conn, self.screen_num, &db)
+            .map_err(|err| format!("Failed to create cursor handle: {}", err))?
+            .reply()
+            .map_err(|err| format!("Failed to resolve cursor handle: {}", err))?;
+
+        cursor_handle.load_cursor(conn, "left_ptr")
+            .map_err(|err| format!("Failed to load cursor: {}", err))
+    }
+
+    /// True if the theme's largest `left_ptr` asset is well below `size`
+    /// (more than an eighth smaller), so the enlarged cursor has to be
+    /// synthesized by upscaling and will look softer.
+    fn theme_lacks_size(&self, size: u32) -> bool {
+        self.theme_cursors
+            .first()
+            .and_then(|images| images.iter().map(|image| image.nominal_size).max())
+            .is_some_and(|largest| largest < size - size / 8)
+    }
+
+    /// Build a cursor from parsed theme images. Enlarging scales the best
+    /// source size to exactly `size` (upscaling the largest asset if the
+    /// theme has nothing bigger); restoring uses the nearest size unscaled,
+    /// as libXcursor does. Every animation frame is kept either way.
+    fn load_theme_frames(&self, conn: &RustConnection, images: &[CursorImage], size: u32) -> Result<Cursor, String> {
+        let frames = if size == self.original_cursor_size {
+            cursor_image::nearest_frames(images, size)
+        } else {
+            cursor_image::frames_at_size(images, size).0
+        };
+        self.upload_cursor(conn, &frames)
+    }
+
+    /// Build a cursor from the configured custom image at the given size.
+    fn load_custom_cursor(&self, conn: &RustConnection, size: u32) -> Result<Cursor, String> {
+        let (frames, scaled) = cursor_image::frames_at_size(&self.enlarged_images, size);
+        if scaled {
+            log::info!("Scaling custom cursor image to {}px", size);
+        }
+        self.upload_cursor(conn, &frames)
+    }
+
+    /// Upload cursor images to the X server as an ARGB cursor.
+    ///
+    /// Each image goes into a 32-bit pixmap, is wrapped in a Render picture,
+    /// and becomes a cursor via Render's CreateCursor. Several frames are
+    /// combined with CreateAnimCursor using each frame's delay.
+    fn upload_cursor(&self, conn: &RustConnection, frames: &[CursorImage]) -> Result<Cursor, String> {
+        let format = self.argb_format
+            .ok_or_else(|| "X server has no 32-bit ARGB picture format".to_string())?;
+
+        let mut cursors = Vec::with_capacity(frames.len());
+        for frame in frames {
+            let width = u16::try_from(frame.width)
+                .map_err(|_| format!("Cursor image too wide: {}px", frame.width))?;
+            let height = u16::try_from(frame.height)
+                .map_err(|_| format!("Cursor image too tall: {}px", frame.height))?;
+
+            let pixmap = conn.generate_id()
+                .map_err(|err| format!("Failed to allocate pixmap ID: {}", err))?;
+            create_pixmap(conn, 32, pixmap, self.root, width, height)
+                .map_err(|err| format!("Failed to create pixmap: {}", err))?;
+
+            let gc = conn.generate_id()
+                .map_err(|err| format!("Failed to allocate GC ID: {}", err))?;
+            create_gc(conn, gc, pixmap, &CreateGCAux::new())
+                .map_err(|err| format!("Failed to create GC: {}", err))?;
+
+            let data = cursor_image::image_data(&frame.pixels, conn.setup().image_byte_order);
+            put_image(conn, ImageFormat::Z_PIXMAP, pixmap, gc, width, height, 0, 0, 0, 32, &data)
+                .map_err(|err| format!("Failed to upload cursor image: {}", err))?;
+
+            let picture = conn.generate_id()
+                .map_err(|err| format!("Failed to allocate picture ID: {}", err))?;
+            render::create_picture(conn, picture, pixmap, format, &render::CreatePictureAux::new())
+                .map_err(|err| format!("Failed to create picture: {}", err))?;
+
+            let cursor = conn.generate_id()
+                .map_err(|err| format!("Failed to allocate cursor ID: {}", err))?;
+            render::create_cursor(conn, cursor, picture, frame.xhot as u16, frame.yhot as u16)
+                .map_err(|err| format!("Failed to create cursor: {}", err))?;
+
+            // The cursor keeps its own copy of the image
+            render::free_picture(conn, picture)
+                .map_err(|err| format!("Failed to free picture: {}", err))?;
+            free_gc(conn, gc)
+                .map_err(|err| format!("Failed to free GC: {}", err))?;
+            free_pixmap(conn, pixmap)
+                .map_err(|err| format!("Failed to free pixmap: {}", err))?;
+
+            cursors.push(render::Animcursorelt {
+                cursor,
+                delay: frame.delay_ms,
+            });
+        }
+
+        match cursors.as_slice() {
+            [] => Err("Cursor image has no frames".to_string()),
+            [single] => Ok(single.cursor),
+            frames => {
+                let cursor = conn.generate_id()
+                    .map_err(|err| format!("Failed to allocate cursor ID: {}", err))?;
+                render::create_anim_cursor(conn, cursor, frames)
+                    .map_err(|err| format!("Failed to create animated cursor: {}", err))?;
+                for frame in frames {
+                    free_cursor(conn, frame.cursor)
+                        .map_err(|err| format!("Failed to free cursor: {}", err))?;
+                }
+                Ok(cursor)
+            }
         }
     }
 }
@@ -117,138 +485,204 @@ impl DisplayBackend for X11Backend {
         .map_err(|err| format!("Failed to read resource manager reply: {}", err))?;
 
         let db = Database::new_from_default(&rm_reply, OsString::new());
-        if let Some(size_str) = db.get_string("Xcursor.size", "Xcursor.Size") {
-            if let Ok(size) = size_str.parse::<u32>() {
-                self.original_cursor_size = size;
-            }
+        if let Some(size_str) = db.get_string("Xcursor.size", "Xcursor.Size")
+            && let Ok(size) = size_str.parse::<u32>()
+        {
+            self.original_cursor_size = size;
         }
 
         // Discover the cursor theme name from the resource database.
         // If empty or unset, fall back to "default" which on most distros
         // inherits from Adwaita via /usr/share/icons/default/index.theme.
-        if let Some(theme) = db.get_string("Xcursor.theme", "Xcursor.Theme") {
-            if !theme.is_empty() {
-                self.cursor_theme = theme.to_string();
-            }
+        if let Some(theme) = db.get_string("Xcursor.theme", "Xcursor.Theme")
+            && !theme.is_empty()
+        {
+            self.cursor_theme = theme.to_string();
         }
         log::info!("Cursor theme: {}, original size: {}px", self.cursor_theme, self.original_cursor_size);
 
+        // Find the 32-bit ARGB picture format the Render extension needs
+        // to turn an uploaded image into a cursor.
+        let formats = render::query_pict_formats(&conn)
+            .map_err(|err| format!("Failed to query Render formats: {}", err))?
+            .reply()
+            .map_err(|err| format!("Render not supported: {}", err))?;
+        self.argb_format = formats
+            .formats
+            .iter()
+            .find(|format| {
+                format.type_ == render::PictType::DIRECT
+                    && format.depth == 32
+                    && format.direct.alpha_mask == 0xff
+                    && format.direct.alpha_shift == 24
+                    && format.direct.red_shift == 16
+                    && format.direct.green_shift == 8
+                    && format.direct.blue_shift == 0
+            })
+            .map(|format| format.id);
+
+        // Parse the theme's cursors ourselves so we know which sizes it
+        // ships and keep all animation frames. CursorHandle silently
+        // returns the nearest size it has.
+        let theme = xcursor::CursorTheme::load(&self.cursor_theme);
+        self.theme_cursors = CURSOR_GROUPS
+            .iter()
+            .map(|names| match theme.load_icon(names[0]) {
+                Some(path) => cursor_image::load_xcursor(&path).unwrap_or_else(|err| {
+                    log::warn!("Cannot load theme cursor {}: {}", names[0], err);
+                    Vec::new()
+                }),
+                None => {
+                    log::debug!("Theme {} has no {} Xcursor file", self.cursor_theme, names[0]);
+                    Vec::new()
+                }
+            })
+            .collect();
+
+        if let Some(custom) = &self.enlarged_cursor {
+            match cursor_image::load_file(&custom.path, custom.hotspot) {
+                Ok(images) => {
+                    log::info!("Enlarged cursor image: {}", custom.path.display());
+                    self.enlarged_images = images;
+                }
+                Err(err) => log::error!("Custom cursor unavailable, using theme: {}", err),
+            }
+        }
+
+        // The daemon is still useful without the hotkey
+        if let Err(err) = self.listen_for_hotkey(&conn) {
+            log::error!("Locate hotkey disabled: {}", err);
+            self.hotkey_keycodes.clear();
+        }
+
+        // Both ends non-blocking: the reader drains it, and a waker called
+        // many times must never block on a full pipe
+        let (wake_rx, wake_tx) = UnixStream::pair()
+            .and_then(|(rx, tx)| {
+                rx.set_nonblocking(true)?;
+                tx.set_nonblocking(true)?;
+                Ok((rx, tx))
+            })
+            .map_err(|err| format!("Failed to create wake pipe: {}", err))?;
+        self.wake_pipe = Some((wake_rx, Arc::new(wake_tx)));
+
         self.conn = Some(conn);
         Ok(())
     }
 
-    /// Block until the next mouse motion event arrives from the X server.
-    /// Returns None if the connection is lost (Xorg crashed or was restarted).
-    /// Non-motion events are silently skipped.
-    fn next_motion_event(&mut self) -> Option<MotionEvent> {
+    /// Block until the next mouse motion event or hotkey press arrives
+    /// from the X server, or the waker is called. Returns None if the
+    /// connection is lost (Xorg crashed or was restarted). Other events
+    /// are silently skipped.
+    fn next_event(&mut self) -> Option<InputEvent> {
         let conn = self.conn.as_ref()?;
+        let (wake_rx, _) = self.wake_pipe.as_ref()?;
 
         loop {
-            match conn.wait_for_event() {
-                Ok(Event::XinputMotion(motion)) => {
+            // Events x11rb has already read from the socket never show up
+            // in poll(), so drain those before going to sleep
+            let event = match conn.poll_for_event() {
+                Ok(Some(event)) => event,
+                Ok(None) => match wait_readable(conn.stream().as_raw_fd(), wake_rx.as_raw_fd()) {
+                    Ok(true) => {
+                        while (&*wake_rx).read(&mut [0u8; 64]).is_ok_and(|read| read > 0) {}
+                        return Some(InputEvent::Interrupted);
+                    }
+                    Ok(false) => continue,
+                    Err(err) => {
+                        log::error!("Failed to wait for X events: {}", err);
+                        return None;
+                    }
+                },
+                Err(err) => {
+                    log::error!("X connection error: {:?}", err);
+                    return None;
+                }
+            };
+
+            match event {
+                Event::XinputMotion(motion) => {
                     // XInput2 coordinates are Fp1616 (fixed-point 16.16).
                     // Right-shift by 16 to get integer pixel values.
-                    return Some(MotionEvent {
+                    return Some(InputEvent::Motion(MotionEvent {
                         x: (motion.root_x >> 16) as i16,
                         y: (motion.root_y >> 16) as i16,
                         timestamp: motion.time,
+                        device: motion.sourceid,
+                    }));
+                }
+                Event::KeyPress(key) if self.hotkey_keycodes.contains(&key.detail) => {
+                    return Some(InputEvent::Hotkey {
+                        x: key.root_x,
+                        y: key.root_y,
+                        timestamp: key.time,
                     });
                 }
-                Ok(other) => {
-                    log::debug!("Received non-motion event: {:?}", other);
-                    continue;
+                Event::XinputRawKeyPress(key) => {
+                    // Only a press of the tap modifier arms the tap; any
+                    // other key means it is part of a shortcut
+                    self.tap_armed = self.hotkey_keycodes.contains(&(key.detail as Keycode));
                 }
-                Err(err) => {
-                    log::error!("X connection error: {:?}", err);
-                    return None;
+                Event::XinputRawButtonPress(_) => self.tap_armed = false,
+                Event::XinputRawKeyRelease(key) => {
+                    if self.tap_armed && self.hotkey_keycodes.contains(&(key.detail as Keycode)) {
+                        self.tap_armed = false;
+                        let (x, y) = self.pointer_position(conn);
+                        return Some(InputEvent::Hotkey { x, y, timestamp: key.time });
+                    }
                 }
+                other => log::debug!("Received non-motion event: {:?}", other),
             }
         }
     }
 
-    /// Load a cursor at the given size from the current theme and apply it
-    /// to the root window. Used for both enlarging and restoring.
+    /// Load every cursor in CURSOR_GROUPS at the given size and apply them
+    /// globally. Used for both enlarging and restoring. Enlarging the arrow
+    /// uses the configured custom image if there is one; restoring always
+    /// goes back to the theme, animations included.
     fn set_cursor_size(&mut self, size: u32) -> Result<(), String> {
         let conn = self.conn.as_ref()
             .ok_or_else(|| "Not connected to X server".to_string())?;
 
-        // Query the real resource database to get the cursor theme name,
-        // then build a modified version with our desired cursor size.
-        // CursorHandle reads size from the resource database, not from
-        // the XCURSOR_SIZE env var, so we must override it here.
-        let rm_reply = conn.get_property(
-            false,
-            self.root,
-            AtomEnum::RESOURCE_MANAGER,
-            AtomEnum::STRING,
-            0,
-            1024 * 1024,
-        )
-        .map_err(|err| format!("Failed to query resource manager: {}", err))?
-        .reply()
-        .map_err(|err| format!("Failed to read resource manager reply: {}", err))?;
-
-        // Take the existing resource string, remove Xcursor settings we need
-        // to override, then inject our desired values.
-        // - Xcursor.size: the cursor size in pixels
-        // - Xcursor.theme: force a named theme so CursorHandle loads Xcursor
-        //   files instead of falling back to core X11 bitmap cursors
-        // - Xcursor.theme_core: remove this flag because when set to 1 it
-        //   forces CursorHandle to use fixed-size core cursors, ignoring
-        //   the Xcursor size entirely
-        let original = String::from_utf8_lossy(&rm_reply.value);
-        let modified: String = original
-            .lines()
-            .filter(|line| {
-                !line.starts_with("Xcursor.size")
-                    && !line.starts_with("Xcursor.theme_core")
-                    && !line.starts_with("Xcursor.theme")
-            })
-            .collect::<Vec<_>>()
-            .join("\n");
-        let modified = format!(
-            "{}\nXcursor.size: {}\nXcursor.theme: {}\n",
-            modified, size, self.cursor_theme
-        );
-
-        // Build a new resource database with our modified size
-        let modified_reply = GetPropertyReply {
-            format: 8,
-            sequence: 0,
-            length: 0,
-            type_: AtomEnum::STRING.into(),
-            bytes_after: 0,
-            value_len: modified.len() as u32,
-            value: modified.into_bytes(),
-        };
-        let db = Database::new_from_default(&modified_reply, OsString::new());
-
-        log::info!("Loading cursor at size {}px (theme: {})", size, self.cursor_theme);
+        let enlarging = size != self.original_cursor_size;
+        let use_custom = enlarging && !self.enlarged_images.is_empty();
+        if enlarging && !use_custom && self.theme_lacks_size(size) {
+            let largest = self.theme_cursors[0].iter().map(|image| image.nominal_size).max().unwrap_or(0);
+            log::info!(
+                "Theme {} has no {}px cursor (largest is {}px); synthesizing an upscaled cursor, \
+                 which may look softer than a native asset",
+                self.cursor_theme, size, largest
+            );
+        }
 
-        // CursorHandle::new returns a Cookie that must be resolved with .reply()
-        let cursor_handle = CursorHandle::new(conn, self.screen_num, &db)
-            .map_err(|err| format!("Failed to create cursor handle: {}", err))?
-            .reply()
-            .map_err(|err| format!("Failed to resolve cursor handle: {}", err))?;
+        for (index, names) in CURSOR_GROUPS.iter().enumerate() {
+            let images = self.theme_cursors.get(index).map_or(&[][..], |images| images.as_slice());
+
+            let cursor = if index == 0 && use_custom {
+                self.load_custom_cursor(conn, size)?
+            } else if !images.is_empty() {
+                self.load_theme_frames(conn, images, size)?
+            } else if index == 0 {
+                self.load_theme_cursor(conn, size)?
+            } else {
+                // Theme doesn't ship this shape; leave it alone
+                continue;
+            };
+
+            log::debug!("Cursor XID: {} for {} at {}px", cursor, names[0], size);
+
+            // XFixes: replace the cursor globally across ALL windows.
+            // Unlike change_window_attributes (which only affects root window),
+            // change_cursor_by_name replaces every instance of the named cursor
+            // in every application window on screen.
+            for name in names.iter() {
+                xfixes::change_cursor_by_name(conn, cursor, name.as_bytes())
+                    .map_err(|err| format!("Failed to replace cursor '{}': {}", name, err))?;
+            }
 
-        let cursor = cursor_handle.load_cursor(conn, "left_ptr")
-            .map_err(|err| format!("Failed to load cursor: {}", err))?;
-
-        log::debug!("Cursor XID: {} for size {}px", cursor, size);
-
-        // XFixes: replace the cursor globally across ALL windows.
-        // Unlike change_window_attributes (which only affects root window),
-        // change_cursor_by_name replaces every instance of the named cursor
-        // in every application window on screen.
-        //
-        // We replace multiple cursor names because different toolkits use
-        // different names for the default arrow cursor:
-        //   "left_ptr" — X11 core name, used by GTK and many legacy apps
-        //   "default"  — freedesktop.org standard, used by Electron (VSCode), Qt
-        for name in [b"left_ptr" as &[u8], b"default"] {
-            xfixes::change_cursor_by_name(conn, cursor, name)
-                .map_err(|err| format!("Failed to replace cursor '{}': {}",
-                    String::from_utf8_lossy(name), err))?;
+            // Windows now hold their own references to the cursor
+            free_cursor(conn, cursor)
+                .map_err(|err| format!("Failed to free cursor: {}", err))?;
         }
 
         conn.flush()
@@ -257,9 +691,135 @@ impl DisplayBackend for X11Backend {
         Ok(())
     }
 
+    fn waker(&self) -> Waker {
+        let wake_tx = self.wake_pipe.as_ref().map(|(_, tx)| Arc::clone(tx));
+        Arc::new(move || {
+            if let Some(tx) = &wake_tx {
+                let _ = (&**tx).write(&[1]);
+            }
+        })
+    }
+
+    fn original_cursor_size(&self) -> u32 {
+        self.original_cursor_size
+    }
+
     /// Close the connection to the X server.
     /// Dropping RustConnection automatically closes the underlying socket.
     fn disconnect(&mut self) {
         self.conn = None;
+        self.wake_pipe = None;
+    }
+}
+
+/// Sleep until the X connection or the wake pipe is readable.
+/// Returns true if the wake pipe is, false if only the X connection is.
+fn wait_readable(x_fd: RawFd, wake_fd: RawFd) -> io::Result<bool> {
+    let mut fds = [
+        libc::pollfd { fd: x_fd, events: libc::POLLIN, revents: 0 },
+        libc::pollfd { fd: wake_fd, events: libc::POLLIN, revents: 0 },
+    ];
+    loop {
+        // SAFETY: fds is a valid array of pollfd for the duration of the call
+        let ready = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, -1) };
+        if ready >= 0 {
+            return Ok(fds[1].revents != 0);
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+}
+
+/// X11 modifier mask for a hotkey modifier, in the conventional layout
+/// (Alt on Mod1, Super on Mod4).
+fn modifier_mask(modifier: Modifier) -> ModMask {
+    match modifier {
+        Modifier::Ctrl => ModMask::CONTROL,
+        Modifier::Shift => ModMask::SHIFT,
+        Modifier::Alt => ModMask::M1,
+        Modifier::Super => ModMask::M4,
+    }
+}
+
+/// Keysyms of the left and right keys for a modifier.
+fn modifier_keysyms(modifier: Modifier) -> [Keysym; 2] {
+    match modifier {
+        Modifier::Ctrl => [0xffe3, 0xffe4],
+        Modifier::Shift => [0xffe1, 0xffe2],
+        Modifier::Alt => [0xffe9, 0xffea],
+        Modifier::Super => [0xffeb, 0xffec],
+    }
+}
+
+/// Keysym for a key name as used in hotkeys: letters, digits, F1-F24
+/// and the common named keys.
+fn keysym_from_name(name: &str) -> Option<Keysym> {
+    let mut chars = name.chars();
+    if let (Some(ch), None) = (chars.next(), chars.next())
+        && (ch.is_ascii_lowercase() || ch.is_ascii_digit())
+    {
+        // Latin-1 keysyms equal their character codes
+        return Some(ch as Keysym);
+    }
+
+    if let Some(number) = name.strip_prefix('f').and_then(|n| n.parse::<Keysym>().ok())
+        && (1..=24).contains(&number)
+    {
+        return Some(0xffbe + number - 1);
+    }
+
+    let keysym = match name {
+        "space" => 0x0020,
+        "escape" => 0xff1b,
+        "return" | "enter" => 0xff0d,
+        "tab" => 0xff09,
+        "backspace" => 0xff08,
+        "delete" => 0xffff,
+        "insert" => 0xff63,
+        "home" => 0xff50,
+        "end" => 0xff57,
+        "pageup" => 0xff55,
+        "pagedown" => 0xff56,
+        "left" => 0xff51,
+        "up" => 0xff52,
+        "right" => 0xff53,
+        "down" => 0xff54,
+        "pause" => 0xff13,
+        "print" => 0xff61,
+        _ => return None,
+    };
+    Some(keysym)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(nominal_size: u32) -> CursorImage {
+        CursorImage {
+            nominal_size,
+            width: nominal_size,
+            height: nominal_size,
+            xhot: 0,
+            yhot: 0,
+            delay_ms: 0,
+            pixels: vec![0; (nominal_size * nominal_size) as usize],
+        }
+    }
+
+    #[test]
+    fn theme_lacks_sizes_well_above_its_largest() {
+        let mut backend = X11Backend::new();
+        assert!(!backend.theme_lacks_size(96), "no theme images to judge by");
+
+        backend.theme_cursors = vec![vec![image(24), image(48), image(88)]];
+        assert!(!backend.theme_lacks_size(48));
+        // 88 is within an eighth of 96 and 100, but not of 101
+        assert!(!backend.theme_lacks_size(96));
+        assert!(!backend.theme_lacks_size(100));
+        assert!(backend.theme_lacks_size(101));
+        assert!(backend.theme_lacks_size(128));
     }
 }
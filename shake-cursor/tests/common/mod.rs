@@ -0,0 +1,25 @@
+//! Fixtures shared by the integration tests, and by the unit tests in
+//! src/ through the `common` module in main.rs.
+
+use std::fs;
+use std::path::PathBuf;
+
+/// A scratch directory, removed when dropped.
+pub struct TempDir(pub PathBuf);
+
+impl TempDir {
+    /// An empty `shake-cursor-<name>-<pid>` in the system temp directory.
+    /// `name` must be unique among the tests of one binary.
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("shake-cursor-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
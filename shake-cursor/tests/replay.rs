@@ -0,0 +1,241 @@
+//! End-to-end tests of the daemon on a replayed trace, which needs no
+//! display server: the daemon is controlled through signals and the
+//! control socket, and reports its cursor changes in its log.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+mod common;
+use common::TempDir;
+
+const COOLDOWN_MS: u64 = 1000;
+
+/// How long to wait for the daemon or a state change.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The daemon replaying a trace, sent SIGTERM when dropped.
+struct Daemon {
+    process: Child,
+    dir: PathBuf,
+}
+
+impl Daemon {
+    /// Replay `motion` (x, y, t) with its own runtime, config and state
+    /// directories in `dir`, and `config` added to the config file.
+    fn start(dir: &Path, motion: &[(i16, i16, u32)], config: &str) -> Self {
+        Self::start_with(dir, None, motion, config, &[])
+    }
+
+    /// Like start(), with a trace header recording `header` (key, value)
+    /// pairs and extra daemon arguments.
+    fn start_with(
+        dir: &Path,
+        header: Option<&[(&str, &str)]>,
+        motion: &[(i16, i16, u32)],
+        config: &str,
+        args: &[&str],
+    ) -> Self {
+        let trace = dir.join("trace.jsonl");
+        let mut lines: Vec<String> = header
+            .map(|entries| {
+                let config: serde_json::Map<String, serde_json::Value> =
+                    entries.iter().map(|(key, value)| (key.to_string(), (*value).into())).collect();
+                serde_json::json!({ "type": "header", "version": 1, "config": config }).to_string()
+            })
+            .into_iter()
+            .collect();
+        lines.extend(
+            motion
+                .iter()
+                .map(|(x, y, t)| format!("{{\"type\":\"motion\",\"x\":{},\"y\":{},\"t\":{}}}", x, y, t)),
+        );
+        fs::write(&trace, lines.join("\n")).unwrap();
+        let config_path = dir.join("config");
+        fs::write(&config_path, format!("cooldown_ms = {}\n{}", COOLDOWN_MS, config)).unwrap();
+        let runtime = dir.join("runtime");
+        fs::create_dir_all(&runtime).unwrap();
+
+        let process = Command::new(env!("CARGO_BIN_EXE_shake-cursor"))
+            .arg("--replay")
+            .arg(&trace)
+            .arg("--config")
+            .arg(&config_path)
+            .args(args)
+            .env("XDG_RUNTIME_DIR", &runtime)
+            .env("XDG_CONFIG_HOME", dir)
+            .env("XDG_STATE_HOME", dir)
+            .env("RUST_LOG", "info")
+            .env_remove("DBUS_SESSION_BUS_ADDRESS")
+            .env_remove("NOTIFY_SOCKET")
+            .stdout(Stdio::null())
+            .stderr(fs::File::create(dir.join("daemon.log")).unwrap())
+            .spawn()
+            .unwrap();
+        let mut daemon = Self { process, dir: dir.to_path_buf() };
+
+        let socket = runtime.join("shake-cursor-replay.sock");
+        let started = Instant::now();
+        while !socket.exists() {
+            if let Some(status) = daemon.process.try_wait().unwrap() {
+                panic!("Daemon exited with {}:\n{}", status, daemon.log());
+            }
+            assert!(started.elapsed() < TIMEOUT, "Daemon didn't start:\n{}", daemon.log());
+            thread::sleep(Duration::from_millis(50));
+        }
+        daemon
+    }
+
+    fn log(&self) -> String {
+        fs::read_to_string(self.dir.join("daemon.log")).unwrap_or_default()
+    }
+
+    fn signal(&self, signal: libc::c_int) {
+        // SAFETY: kill(2) has no memory safety requirements
+        unsafe { libc::kill(self.process.id() as libc::pid_t, signal) };
+    }
+
+    /// The cursor state, from `ctl status`.
+    fn state(&self) -> String {
+        let output = Command::new(env!("CARGO_BIN_EXE_shake-cursor"))
+            .args(["ctl", "--replay", "status"])
+            .env("XDG_RUNTIME_DIR", self.dir.join("runtime"))
+            .output()
+            .unwrap();
+        let status: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        status["state"].as_str().unwrap().to_string()
+    }
+
+    /// One value of the active configuration, from `ctl get-config`.
+    fn config(&self, key: &str) -> String {
+        let output = Command::new(env!("CARGO_BIN_EXE_shake-cursor"))
+            .args(["ctl", "--replay", "get-config"])
+            .env("XDG_RUNTIME_DIR", self.dir.join("runtime"))
+            .output()
+            .unwrap();
+        let reply: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        reply["config"][key].as_str().unwrap().to_string()
+    }
+
+    /// The daemon's metrics, from `ctl metrics`.
+    fn metrics(&self) -> serde_json::Value {
+        let output = Command::new(env!("CARGO_BIN_EXE_shake-cursor"))
+            .args(["ctl", "--replay", "metrics"])
+            .env("XDG_RUNTIME_DIR", self.dir.join("runtime"))
+            .output()
+            .unwrap();
+        let reply: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        reply["metrics"].clone()
+    }
+
+    /// Wait until the cursor state is `state`, returning how long it took.
+    fn wait_for_state(&self, state: &str) -> Duration {
+        let started = Instant::now();
+        while self.state() != state {
+            assert!(started.elapsed() < TIMEOUT, "Never reached {}:\n{}", state, self.log());
+            thread::sleep(Duration::from_millis(20));
+        }
+        started.elapsed()
+    }
+}
+
+impl Drop for Daemon {
+    fn drop(&mut self) {
+        self.signal(libc::SIGTERM);
+        let _ = self.process.wait();
+    }
+}
+
+#[test]
+fn sigusr2_enlarges_once_then_restores_while_still() {
+    let dir = TempDir::new("replay-sigusr2");
+    // One event, then the pointer stays still for longer than the test
+    let daemon = Daemon::start(&dir.0, &[(500, 300, 0), (501, 300, 60000)], "");
+    assert_eq!(daemon.state(), "idle");
+
+    let sent = Instant::now();
+    daemon.signal(libc::SIGUSR2);
+    daemon.wait_for_state("enlarged");
+    daemon.wait_for_state("idle");
+
+    let restored_after = sent.elapsed();
+    assert!(restored_after >= Duration::from_millis(COOLDOWN_MS), "restored after {:?}", restored_after);
+    let log = daemon.log();
+    assert!(log.contains("cursor size 96px"), "{}", log);
+    assert!(log.contains("cursor size 24px"), "{}", log);
+}
+
+#[test]
+fn replay_leaves_adaptive_baseline_alone() {
+    let dir = TempDir::new("replay-adaptive");
+    // Enough normal movement for adaptive mode to sample, and to save
+    // at exit if it were on
+    let motion: Vec<(i16, i16, u32)> = (0..300).map(|step| (500 + (step % 40) as i16, 300, step * 10)).collect();
+    let mut daemon = Daemon::start(&dir.0, &motion, "adaptive_enabled = true\n");
+
+    let status = daemon.process.wait().unwrap();
+    assert!(status.success(), "{}", daemon.log());
+    assert!(daemon.log().contains("Adaptive mode is off for replays"), "{}", daemon.log());
+    assert!(!dir.0.join("shake-cursor").join("adaptive").exists());
+}
+
+#[test]
+fn replay_uses_the_recorded_config() {
+    let dir = TempDir::new("replay-recorded");
+    let header: &[(&str, &str)] = &[("min_reversals", "6"), ("cooldown_ms", "1000")];
+    let still = [(500, 300, 0), (501, 300, 60000)];
+    let daemon = Daemon::start_with(&dir.0, Some(header), &still, "min_reversals = 4\n", &[]);
+
+    assert_eq!(daemon.config("min_reversals"), "6");
+    let log = daemon.log();
+    assert!(log.contains("recorded with min_reversals = 6 (config: 4); replaying with the recorded values"), "{}", log);
+}
+
+#[test]
+fn replay_can_keep_the_current_config() {
+    let dir = TempDir::new("replay-current");
+    let header: &[(&str, &str)] = &[("min_reversals", "6")];
+    let still = [(500, 300, 0), (501, 300, 60000)];
+    let args = ["--replay-config", "current"];
+    let daemon = Daemon::start_with(&dir.0, Some(header), &still, "min_reversals = 4\n", &args);
+
+    assert_eq!(daemon.config("min_reversals"), "4");
+    let log = daemon.log();
+    assert!(log.contains("recorded with min_reversals = 6 (config: 4); replaying with the current values"), "{}", log);
+}
+
+#[test]
+fn metrics_are_current_while_the_pointer_is_still() {
+    let dir = TempDir::new("replay-metrics");
+    let motion = [(500, 300, 0), (510, 300, 10), (520, 300, 20), (521, 300, 60000)];
+    let daemon = Daemon::start(&dir.0, &motion, "");
+
+    let started = Instant::now();
+    while daemon.metrics()["motion_events"] != 3 {
+        assert!(started.elapsed() < TIMEOUT, "Events not counted: {}", daemon.metrics());
+        thread::sleep(Duration::from_millis(20));
+    }
+    assert_eq!(daemon.metrics()["handle_seconds"]["count"], 3);
+}
+
+#[test]
+fn replay_keeps_off_the_daemon_socket() {
+    let dir = TempDir::new("replay-socket");
+    let still = [(500, 300, 0), (501, 300, 60000)];
+    let _daemon = Daemon::start(&dir.0, &still, "");
+
+    // A daemon started on the display can still bind its own socket
+    let runtime = dir.0.join("runtime");
+    let sockets: Vec<_> = fs::read_dir(&runtime).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+    assert_eq!(sockets, ["shake-cursor-replay.sock"]);
+
+    // and plain `ctl` doesn't reach the replay
+    let output = Command::new(env!("CARGO_BIN_EXE_shake-cursor"))
+        .args(["ctl", "status"])
+        .env("XDG_RUNTIME_DIR", &runtime)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+}
@@ -0,0 +1,353 @@
+//! End-to-end tests of the X11 backend. Each test starts a private Xvfb
+//! server and runs the daemon against it, moves the pointer with XTEST,
+//! and watches the cursor with XFixes, as a user would see it.
+//!
+//! Skipped when Xvfb is not installed.
+
+use std::fs;
+use std::io::{self, BufRead, BufReader};
+use std::os::fd::AsRawFd;
+use std::os::unix::net::UnixStream;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use x11rb::connection::Connection;
+use x11rb::protocol::xfixes::{self, ConnectionExt as _};
+use x11rb::protocol::xproto::{self, ChangeWindowAttributesAux, ConnectionExt as _, Window};
+use x11rb::protocol::xtest::{self, ConnectionExt as _};
+use x11rb::rust_connection::RustConnection;
+
+mod common;
+use common::TempDir;
+
+/// Cursor sizes the test theme ships. The enlarged size isn't one of
+/// them, so enlarging also exercises scaling.
+const THEME_SIZES: &[u32] = &[24, 48];
+
+/// The default Xcursor.size, which the daemon restores to.
+const ORIGINAL_SIZE: u16 = 24;
+const ENLARGED_SIZE: u16 = 96;
+const COOLDOWN_MS: u64 = 1000;
+
+/// How long to wait for the server, the daemon, or a cursor change.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A private Xvfb server, killed when dropped.
+struct Xvfb {
+    process: Child,
+    display: String,
+}
+
+impl Xvfb {
+    /// Start Xvfb on a display it picks itself. None if Xvfb isn't
+    /// installed.
+    ///
+    /// With -displayfd, Xvfb finds a free display number and writes it to
+    /// the given descriptor once it accepts connections, so tests running
+    /// in parallel never race for the same display.
+    fn start() -> Option<Self> {
+        let (read, write) = UnixStream::pair().unwrap();
+        let fd = write.as_raw_fd();
+        let mut command = Command::new("Xvfb");
+        command
+            .args(["-displayfd", &fd.to_string(), "-screen", "0", "1280x1024x24", "-nolisten", "tcp", "-noreset"])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        // SAFETY: fcntl(2) is async-signal-safe, and `fd` stays open in
+        // the parent until spawn() returns
+        unsafe {
+            command.pre_exec(move || {
+                // Let Xvfb inherit the descriptor
+                if libc::fcntl(fd, libc::F_SETFD, 0) == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        let process = match command.spawn() {
+            Ok(process) => process,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return None,
+            Err(err) => panic!("Failed to start Xvfb: {}", err),
+        };
+        // Only Xvfb holds the write end now, so its exit ends the read
+        drop(write);
+
+        let mut server = Self { process, display: String::new() };
+        read.set_read_timeout(Some(TIMEOUT)).unwrap();
+        let mut number = String::new();
+        BufReader::new(read).read_line(&mut number).unwrap();
+        let number = number.trim();
+        if number.is_empty() {
+            panic!("Xvfb exited with {:?} before opening a display", server.process.try_wait());
+        }
+        server.display = format!(":{}", number);
+        Some(server)
+    }
+
+    fn connect(&self) -> X {
+        let (conn, screen) = RustConnection::connect(Some(&self.display)).unwrap();
+        let root = conn.setup().roots[screen].root;
+        xfixes::query_version(&conn, 6, 0).unwrap().reply().unwrap();
+        xtest::get_version(&conn, 2, 2).unwrap().reply().unwrap();
+        X { conn, root }
+    }
+}
+
+impl Drop for Xvfb {
+    fn drop(&mut self) {
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+    }
+}
+
+/// The test's own X client: moves the pointer and reads the cursor.
+struct X {
+    conn: RustConnection,
+    root: Window,
+}
+
+impl X {
+    /// Give the root window the core left_ptr, named so that XFixes
+    /// replaces it like any themed arrow. Xvfb starts with the X cursor.
+    fn name_root_cursor(&self) {
+        let font = self.conn.generate_id().unwrap();
+        xproto::open_font(&self.conn, font, b"cursor").unwrap();
+        let cursor = self.conn.generate_id().unwrap();
+        // 68 is left_ptr in the cursor font, 69 its mask
+        xproto::create_glyph_cursor(&self.conn, cursor, font, font, 68, 69, 0, 0, 0, 0xffff, 0xffff, 0xffff)
+            .unwrap();
+        xfixes::set_cursor_name(&self.conn, cursor, b"left_ptr").unwrap();
+        self.conn
+            .change_window_attributes(self.root, &ChangeWindowAttributesAux::new().cursor(cursor))
+            .unwrap()
+            .check()
+            .unwrap();
+    }
+
+    fn move_pointer(&self, x: i16, y: i16) {
+        self.conn
+            .xtest_fake_input(xproto::MOTION_NOTIFY_EVENT, 0, x11rb::CURRENT_TIME, self.root, x, y, 0)
+            .unwrap();
+        self.conn.flush().unwrap();
+    }
+
+    /// Shake horizontally, well above the default thresholds.
+    fn shake(&self) {
+        for index in 0..40 {
+            self.move_pointer(if index % 2 == 0 { 400 } else { 600 }, 500);
+            thread::sleep(Duration::from_millis(16));
+        }
+    }
+
+    fn cursor_size(&self) -> (u16, u16) {
+        let image = self.conn.xfixes_get_cursor_image().unwrap().reply().unwrap();
+        (image.width, image.height)
+    }
+
+    /// Wait until the cursor is `size` pixels square, returning the last
+    /// size seen.
+    fn wait_for_cursor(&self, size: u16) -> (u16, u16) {
+        let started = Instant::now();
+        loop {
+            let seen = self.cursor_size();
+            if seen == (size, size) || started.elapsed() > TIMEOUT {
+                return seen;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+    }
+}
+
+/// The daemon under test, sent SIGTERM when dropped.
+struct Daemon {
+    process: Child,
+    log: PathBuf,
+}
+
+impl Daemon {
+    /// Run shake-cursor on `xvfb` with the test theme and its own
+    /// runtime, config and state directories in `dir`.
+    fn start(xvfb: &Xvfb, dir: &Path) -> Self {
+        Self::start_with(xvfb, dir, &[])
+    }
+
+    /// The same, with extra command line arguments.
+    fn start_with(xvfb: &Xvfb, dir: &Path, args: &[&str]) -> Self {
+        write_theme(&dir.join("icons"));
+        let config = dir.join("config");
+        fs::write(
+            &config,
+            format!("enlarged_size = {}\ncooldown_ms = {}\neffects = enlarge\n", ENLARGED_SIZE, COOLDOWN_MS),
+        )
+        .unwrap();
+        let runtime = dir.join("runtime");
+        fs::create_dir_all(&runtime).unwrap();
+
+        let log = dir.join("daemon.log");
+        let process = Command::new(env!("CARGO_BIN_EXE_shake-cursor"))
+            .args(["--backend", "x11", "--config"])
+            .arg(&config)
+            .args(args)
+            .env("DISPLAY", &xvfb.display)
+            .env("XCURSOR_PATH", dir.join("icons"))
+            .env("HOME", dir)
+            .env("XDG_RUNTIME_DIR", &runtime)
+            .env("XDG_CONFIG_HOME", dir)
+            .env("XDG_STATE_HOME", dir)
+            .env("RUST_LOG", "debug")
+            .env_remove("DBUS_SESSION_BUS_ADDRESS")
+            .env_remove("NOTIFY_SOCKET")
+            .stdout(Stdio::null())
+            .stderr(fs::File::create(&log).unwrap())
+            .spawn()
+            .unwrap();
+        let mut daemon = Self { process, log };
+
+        // The control socket, named for the display, appears once the
+        // backend is listening
+        let socket = runtime.join(format!("shake-cursor-x11-{}.0.sock", &xvfb.display[1..]));
+        let started = Instant::now();
+        while !socket.exists() {
+            if let Some(status) = daemon.process.try_wait().unwrap() {
+                panic!("Daemon exited with {}:\n{}", status, daemon.log());
+            }
+            assert!(started.elapsed() < TIMEOUT, "Daemon didn't start:\n{}", daemon.log());
+            thread::sleep(Duration::from_millis(50));
+        }
+        daemon
+    }
+
+    fn log(&self) -> String {
+        fs::read_to_string(&self.log).unwrap_or_default()
+    }
+
+    /// Send SIGTERM and wait for a clean exit.
+    fn terminate(&mut self) {
+        // SAFETY: kill(2) has no memory safety requirements
+        unsafe { libc::kill(self.process.id() as libc::pid_t, libc::SIGTERM) };
+        let started = Instant::now();
+        while self.process.try_wait().unwrap().is_none() {
+            assert!(started.elapsed() < TIMEOUT, "Daemon ignored SIGTERM:\n{}", self.log());
+            thread::sleep(Duration::from_millis(20));
+        }
+    }
+}
+
+impl Drop for Daemon {
+    fn drop(&mut self) {
+        if self.process.try_wait().ok().flatten().is_none() {
+            self.terminate();
+        }
+    }
+}
+
+/// Write a cursor theme named `default` (what the daemon uses without
+/// Xcursor resources) with a square left_ptr at each of THEME_SIZES.
+fn write_theme(icons: &Path) {
+    let cursors = icons.join("default").join("cursors");
+    fs::create_dir_all(&cursors).unwrap();
+    fs::write(cursors.join("left_ptr"), xcursor_file(THEME_SIZES)).unwrap();
+}
+
+/// An Xcursor file with one opaque white square image per size.
+fn xcursor_file(sizes: &[u32]) -> Vec<u8> {
+    const IMAGE_TYPE: u32 = 0xfffd0002;
+    const FILE_HEADER: u32 = 16;
+    const IMAGE_HEADER: u32 = 36;
+
+    fn push(file: &mut Vec<u8>, value: u32) {
+        file.extend_from_slice(&value.to_le_bytes());
+    }
+
+    let mut file = Vec::new();
+
+    file.extend_from_slice(b"Xcur");
+    push(&mut file, FILE_HEADER);
+    push(&mut file, 0x10000);
+    push(&mut file, sizes.len() as u32);
+
+    // Table of contents, then the images in the same order
+    let mut position = FILE_HEADER + 12 * sizes.len() as u32;
+    for &size in sizes {
+        push(&mut file, IMAGE_TYPE);
+        push(&mut file, size);
+        push(&mut file, position);
+        position += IMAGE_HEADER + 4 * size * size;
+    }
+    for &size in sizes {
+        for value in [IMAGE_HEADER, IMAGE_TYPE, size, 1, size, size, 0, 0, 0] {
+            push(&mut file, value);
+        }
+        for _ in 0..size * size {
+            push(&mut file, 0xffffffff);
+        }
+    }
+    file
+}
+
+/// Start Xvfb and the daemon, or None (with a note) without Xvfb.
+fn setup(name: &str) -> Option<(TempDir, Xvfb, X, Daemon)> {
+    let Some(xvfb) = Xvfb::start() else {
+        eprintln!("Xvfb not found, skipping X11 end-to-end test");
+        return None;
+    };
+    let dir = TempDir::new(name);
+    let x = xvfb.connect();
+    x.name_root_cursor();
+    x.move_pointer(640, 512);
+    let daemon = Daemon::start(&xvfb, &dir.0);
+    Some((dir, xvfb, x, daemon))
+}
+
+#[test]
+fn shake_enlarges_cursor_until_cooldown() {
+    let Some((_dir, _xvfb, x, daemon)) = setup("e2e-cooldown") else {
+        return;
+    };
+    let initial = x.cursor_size();
+    assert_ne!(initial, (ENLARGED_SIZE, ENLARGED_SIZE));
+
+    x.shake();
+    assert_eq!(x.wait_for_cursor(ENLARGED_SIZE), (ENLARGED_SIZE, ENLARGED_SIZE), "{}", daemon.log());
+
+    // Still enlarged halfway through the cooldown
+    thread::sleep(Duration::from_millis(COOLDOWN_MS / 2));
+    assert_eq!(x.cursor_size(), (ENLARGED_SIZE, ENLARGED_SIZE), "{}", daemon.log());
+
+    // Restored once it runs out, while the pointer stays still
+    assert_eq!(x.wait_for_cursor(ORIGINAL_SIZE), (ORIGINAL_SIZE, ORIGINAL_SIZE), "{}", daemon.log());
+}
+
+#[test]
+fn shutdown_restores_enlarged_cursor() {
+    let Some((_dir, _xvfb, x, mut daemon)) = setup("e2e-shutdown") else {
+        return;
+    };
+
+    x.shake();
+    assert_eq!(x.wait_for_cursor(ENLARGED_SIZE), (ENLARGED_SIZE, ENLARGED_SIZE), "{}", daemon.log());
+
+    daemon.terminate();
+    assert_eq!(x.cursor_size(), (ORIGINAL_SIZE, ORIGINAL_SIZE), "{}", daemon.log());
+}
+
+#[test]
+fn replace_waits_for_an_instance_with_another_runtime_dir() {
+    let Some((_dir, xvfb, x, mut daemon)) = setup("e2e-replace") else {
+        return;
+    };
+
+    x.shake();
+    assert_eq!(x.wait_for_cursor(ENLARGED_SIZE), (ENLARGED_SIZE, ENLARGED_SIZE), "{}", daemon.log());
+
+    // With its own runtime directory, the new instance doesn't share the
+    // lock file and only learns of the old one through the selection.
+    // Once it has started, the old one has restored the cursor and quit.
+    let other = TempDir::new("e2e-replace-new");
+    let _replacement = Daemon::start_with(&xvfb, &other.0, &["--replace"]);
+    assert_eq!(x.cursor_size(), (ORIGINAL_SIZE, ORIGINAL_SIZE), "{}", daemon.log());
+    assert!(daemon.process.wait().unwrap().success(), "{}", daemon.log());
+}